@@ -4,17 +4,31 @@
 //! It validates the configuration file, simulates text layout for keys,
 //! and prints diagnostic information about the parsed configuration.
 
-use crate::config::{AppConfig, KeyConfig, OverlayConfig, DEFAULT_TEXT_SIZE_UNSCALED};
+use crate::config::{
+    included_tables, parse_color_string, process_config_source, read_config_file, struct_fields,
+    AppConfig, KeyConfig, KeymapConfig, LayerConfig, LayerKeyConfig, OverlayConfig, StyleConfig,
+    DEFAULT_TEXT_SIZE_UNSCALED,
+};
+use crate::diagnostics::{did_you_mean, line_column, Diagnostics, EntrySpans, Severity, Span};
+use crate::shape;
 use crate::text_utils::{layout_text, TextLayoutResult, TextLayoutParams};
-use cairo::{Context as CairoContext, FontFace as CairoFontFace, ImageSurface, Format};
+use cairo::{Context as CairoContext, ImageSurface, Format};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 
+/// Overlaps smaller than this, in square layout units, are rounding noise,
+/// e.g. between rotated keys that just touch.
+const MIN_OVERLAP_AREA: f32 = 0.01;
+
 /// Validates the application configuration for common issues.
 ///
 /// Checks for:
-/// - Overlapping keys (basic bounding box check, ignoring rotation).
-/// - Duplicate keycodes.
+/// - Overlapping keys, using their actual shape and rotation. All overlapping
+///   pairs are reported, with the area of their overlap.
+/// - Duplicate keycodes, on the base layout and on each layer.
 /// - Invalid values like non-positive width/height for keys, or negative text/border/radius values.
+/// - Colors that cannot be parsed (warnings, as the defaults are used instead).
+/// - Unknown fields, e.g. misspelled ones (warnings, as they are ignored).
 ///
 /// # Arguments
 ///
@@ -22,89 +36,303 @@ use std::collections::HashMap;
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the configuration passes all checks.
-/// * `Err(String)` with a descriptive error message if validation fails.
-pub fn validate_config(config: &AppConfig) -> Result<(), String> {
+/// All problems found, pointing at the offending entries of the TOML source
+/// if the configuration was loaded from one. The configuration is valid if
+/// there are no errors.
+pub fn validate_config(config: &AppConfig) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+
     // Check for overlapping keys
     for i in 0..config.key.len() {
         for j in (i + 1)..config.key.len() {
             let key1 = &config.key[i];
             let key2 = &config.key[j];
 
-            // Basic bounding box check (ignoring rotation for simplicity in this check)
-            let k1_left = key1.left;
-            let k1_right = key1.left + key1.width;
-            let k1_top = key1.top;
-            let k1_bottom = key1.top + key1.height;
-
-            let k2_left = key2.left;
-            let k2_right = key2.left + key2.width;
-            let k2_top = key2.top;
-            let k2_bottom = key2.top + key2.height;
-
-            if k1_left < k2_right && k1_right > k2_left && k1_top < k2_bottom && k1_bottom > k2_top
-            {
-                return Err(format!(
-                    "Configuration validation error: Key '{}' (at {:.1},{:.1} size {:.1}x{:.1}) overlaps with key '{}' (at {:.1},{:.1} size {:.1}x{:.1})",
-                    key1.name, key1.left, key1.top, key1.width, key1.height,
-                    key2.name, key2.left, key2.top, key2.width, key2.height
-                ));
+            // Compare the actual, rotated shapes, so that e.g. a key tucked into
+            // the notch of an ISO Enter or next to a rotated thumb key does not count.
+            let area = shape::overlap_area(key1, key2);
+            if area > MIN_OVERLAP_AREA {
+                diagnostics
+                    .error(format!(
+                        "Key '{}' (at {:.1},{:.1} size {:.1}x{:.1}) overlaps with key '{}' (at {:.1},{:.1} size {:.1}x{:.1}) by {:.1} square units.",
+                        key1.name, key1.left, key1.top, key1.width, key1.height,
+                        key2.name, key2.left, key2.top, key2.width, key2.height, area
+                    ))
+                    .with_label(key2.spans.entry_location(), format!("overlaps with key '{}'", key1.name))
+                    .with_label(key1.spans.entry_location(), format!("key '{}'", key1.name));
             }
         }
     }
 
     // Check for duplicate keycodes
-    let mut keycodes_seen = HashMap::new();
+    let mut keycodes_seen: HashMap<u32, &KeyConfig> = HashMap::new();
     for key_config in &config.key {
-        if let Some(existing_key_name) = keycodes_seen.get(&key_config.keycode) {
-            return Err(format!(
-                "Configuration validation error: Duplicate keycode {} detected. Used by key '{}' and key '{}'.",
-                key_config.keycode, existing_key_name, key_config.name
-            ));
+        if let Some(existing_key) = keycodes_seen.get(&key_config.keycode) {
+            diagnostics
+                .error(format!(
+                    "Duplicate keycode {} detected. Used by key '{}' and key '{}'.",
+                    key_config.keycode, existing_key.name, key_config.name
+                ))
+                .with_label(key_config.spans.field("keycode"), "duplicate keycode")
+                .with_label(existing_key.spans.field("keycode"), "first used here");
+            continue;
+        }
+        keycodes_seen.insert(key_config.keycode, key_config);
+    }
+    for layer_config in &config.layer {
+        let mut layer_keycodes_seen: HashMap<u32, &KeyConfig> = HashMap::new();
+        for key_config in &layer_config.keys {
+            if let Some(existing_key) = layer_keycodes_seen.get(&key_config.keycode) {
+                diagnostics
+                    .error(format!(
+                        "Duplicate keycode {} on layer '{}'. Used by key '{}' and key '{}'.",
+                        key_config.keycode, layer_config.name, existing_key.name, key_config.name
+                    ))
+                    .with_label(
+                        layer_config.spans.entry_location(),
+                        format!("layer '{}'", layer_config.name),
+                    );
+                continue;
+            }
+            layer_keycodes_seen.insert(key_config.keycode, key_config);
         }
-        keycodes_seen.insert(key_config.keycode, key_config.name.clone());
     }
 
     // Check for invalid values (e.g. negative width/height)
     for key_config in &config.key {
+        let spans = &key_config.spans;
         if key_config.width <= 0.0 {
-            return Err(format!(
-                "Configuration validation error: Key '{}' has non-positive width {:.1}.",
-                key_config.name, key_config.width
-            ));
+            diagnostics
+                .error(format!(
+                    "Key '{}' has non-positive width {:.1}.",
+                    key_config.name, key_config.width
+                ))
+                .with_label(spans.field("width"), "must be positive");
         }
         if key_config.height <= 0.0 {
-            return Err(format!(
-                "Configuration validation error: Key '{}' has non-positive height {:.1}.",
-                key_config.name, key_config.height
-            ));
+            diagnostics
+                .error(format!(
+                    "Key '{}' has non-positive height {:.1}.",
+                    key_config.name, key_config.height
+                ))
+                .with_label(spans.field("height"), "must be positive");
         }
-        if let Some(ts) = key_config.text_size {
-            if ts <= 0.0 {
-                return Err(format!(
-                    "Configuration validation error: Key '{}' has non-positive text_size {:.1}.",
-                    key_config.name, ts
-                ));
-            }
+        check_style_values(
+            &mut diagnostics,
+            &format!("Key '{}'", key_config.name),
+            &key_config.own_style(),
+            spans,
+        );
+    }
+    for (name, style) in &config.style {
+        check_style_values(
+            &mut diagnostics,
+            &format!("Style '{}'", name),
+            style,
+            &style.spans,
+        );
+    }
+
+    // Check for unknown fields in the tables read from TOML
+    check_unknown_fields(
+        &mut diagnostics,
+        &config.spans,
+        struct_fields::<AppConfig>(),
+        "the top level",
+    );
+    check_unknown_fields(
+        &mut diagnostics,
+        &config.overlay.spans,
+        struct_fields::<OverlayConfig>(),
+        "[overlay]",
+    );
+    if let Some(keymap_config) = &config.keymap {
+        check_unknown_fields(
+            &mut diagnostics,
+            &keymap_config.spans,
+            struct_fields::<KeymapConfig>(),
+            "[keymap]",
+        );
+    }
+    // Entries from included files are checked with the rest of their file below.
+    for key_config in config
+        .key
+        .iter()
+        .filter(|key_config| key_config.spans.file.is_none())
+    {
+        let table = format!("[[key]] '{}'", key_config.name);
+        check_unknown_fields(
+            &mut diagnostics,
+            &key_config.spans,
+            struct_fields::<KeyConfig>(),
+            &table,
+        );
+    }
+    for (name, style) in &config.style {
+        let table = format!("[style.{}]", name);
+        check_unknown_fields(
+            &mut diagnostics,
+            &style.spans,
+            struct_fields::<StyleConfig>(),
+            &table,
+        );
+    }
+    for layer_config in config
+        .layer
+        .iter()
+        .filter(|layer_config| layer_config.spans.file.is_none())
+    {
+        let table = format!("[[layer]] '{}'", layer_config.name);
+        check_unknown_fields(
+            &mut diagnostics,
+            &layer_config.spans,
+            struct_fields::<LayerConfig>(),
+            &table,
+        );
+        for override_config in &layer_config.key {
+            let table = format!("[[layer.key]] of layer '{}'", layer_config.name);
+            check_unknown_fields(
+                &mut diagnostics,
+                &override_config.spans,
+                struct_fields::<LayerKeyConfig>(),
+                &table,
+            );
         }
-        if let Some(cr) = key_config.corner_radius {
-            if cr < 0.0 {
-                return Err(format!(
-                    "Configuration validation error: Key '{}' has negative corner_radius {:.1}.",
-                    key_config.name, cr
-                ));
-            }
+    }
+    for file in &config.included_files {
+        for (table, known_fields, spans) in included_tables(file) {
+            let table = format!("{} of '{}'", table, file.name);
+            check_unknown_fields(&mut diagnostics, &spans, known_fields, &table);
         }
-        if let Some(bt) = key_config.border_thickness {
-            if bt < 0.0 {
-                return Err(format!(
-                    "Configuration validation error: Key '{}' has negative border_thickness {:.1}.",
-                    key_config.name, bt
-                ));
-            }
+    }
+
+    // Check the overlay colors
+    let overlay = &config.overlay;
+    for (field, color) in [
+        (
+            "background_color_inactive",
+            &overlay.background_color_inactive,
+        ),
+        ("background_color_active", &overlay.background_color_active),
+        (
+            "default_key_background_color",
+            &overlay.default_key_background_color,
+        ),
+        ("default_key_text_color", &overlay.default_key_text_color),
+        (
+            "default_key_outline_color",
+            &overlay.default_key_outline_color,
+        ),
+        (
+            "active_key_background_color",
+            &overlay.active_key_background_color,
+        ),
+        ("active_key_text_color", &overlay.active_key_text_color),
+    ] {
+        if let Err(e) = parse_color_string(color) {
+            diagnostics
+                .warning(format!(
+                    "Invalid overlay {}; a fallback color is used instead. {}",
+                    field, e
+                ))
+                .with_label(overlay.spans.field(field), "invalid color");
+        }
+    }
+    for (field, value) in [
+        (
+            "default_key_corner_radius",
+            overlay.default_key_corner_radius,
+        ),
+        (
+            "default_key_border_thickness",
+            overlay.default_key_border_thickness,
+        ),
+    ] {
+        if value < 0.0 {
+            diagnostics
+                .error(format!("Overlay has negative {} {:.1}.", field, value))
+                .with_label(overlay.spans.field(field), "must not be negative");
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks the appearance properties set on a key or style, described by `owner`.
+fn check_style_values(
+    diagnostics: &mut Diagnostics,
+    owner: &str,
+    style: &StyleConfig,
+    spans: &EntrySpans,
+) {
+    if let Some(ts) = style.text_size.filter(|&ts| ts <= 0.0) {
+        diagnostics
+            .error(format!("{} has non-positive text_size {:.1}.", owner, ts))
+            .with_label(spans.field("text_size"), "must be positive");
+    }
+    if let Some(cr) = style.corner_radius.filter(|&cr| cr < 0.0) {
+        diagnostics
+            .error(format!("{} has negative corner_radius {:.1}.", owner, cr))
+            .with_label(spans.field("corner_radius"), "must not be negative");
+    }
+    if let Some(bt) = style.border_thickness.filter(|&bt| bt < 0.0) {
+        diagnostics
+            .error(format!(
+                "{} has negative border_thickness {:.1}.",
+                owner, bt
+            ))
+            .with_label(spans.field("border_thickness"), "must not be negative");
+    }
+    for (field, color) in [
+        ("background_color", &style.background_color),
+        ("text_color", &style.text_color),
+        ("outline_color", &style.outline_color),
+        ("active_background_color", &style.active_background_color),
+        ("active_text_color", &style.active_text_color),
+        ("active_outline_color", &style.active_outline_color),
+    ] {
+        if let Some(Err(e)) = color.as_deref().map(parse_color_string) {
+            diagnostics
+                .warning(format!(
+                    "{} has an invalid {}; the default is used instead. {}",
+                    owner, field, e
+                ))
+                .with_label(spans.field(field), "invalid color");
         }
     }
-    Ok(())
+}
+
+/// Warns about fields of a TOML table that are not in `known_fields`, with a
+/// suggestion if one looks like a misspelling.
+fn check_unknown_fields(
+    diagnostics: &mut Diagnostics,
+    spans: &EntrySpans,
+    known_fields: &[&str],
+    table: &str,
+) {
+    let mut names: Vec<(&String, &Span)> = spans.field_names.iter().collect();
+    names.sort_by_key(|(_, span)| span.start);
+    for (name, span) in names {
+        if known_fields.contains(&name.as_str()) {
+            continue;
+        }
+        let (message, label) = match did_you_mean(name, known_fields) {
+            Some(suggestion) => (
+                format!(
+                    "Unknown field '{}' in {}; did you mean '{}'?",
+                    name, table, suggestion
+                ),
+                format!("did you mean '{}'?", suggestion),
+            ),
+            None => (
+                format!("Unknown field '{}' in {}.", name, table),
+                "unknown field".to_string(),
+            ),
+        };
+        diagnostics
+            .warning(message)
+            .with_label(Some(spans.locate(span.clone())), label);
+    }
 }
 
 /// Prints a summary of the `OverlayConfig` to standard output.
@@ -122,6 +350,11 @@ pub fn print_overlay_config_for_check(config: &OverlayConfig) {
         config.screen.as_deref().unwrap_or("Compositor default")
     );
     println!("  Position:             {:?}", config.position);
+    println!("  Display Mode:         {:?}", config.display_mode);
+    println!("  Label Position:       {:?}", config.label_position);
+    if config.display_mode == crate::config::DisplayMode::Strip {
+        println!("  Strip Timeout:        {}ms", config.strip_timeout_ms);
+    }
 
     let width_str = match config.size_width {
         Some(crate::config::SizeDimension::Pixels(px)) => format!("{}px", px),
@@ -176,6 +409,7 @@ pub fn print_overlay_config_for_check(config: &OverlayConfig) {
 /// # Arguments
 ///
 /// * `key_config` - A reference to the `KeyConfig` for the key.
+/// * `style` - The key's appearance properties, from `AppConfig::key_style`.
 /// * `cairo_ctx` - A reference to a Cairo `Context` initialized with the
 ///   appropriate font face.
 ///
@@ -185,78 +419,256 @@ pub fn print_overlay_config_for_check(config: &OverlayConfig) {
 /// * `Err(String)` if text layout simulation fails.
 pub fn simulate_text_layout_for_check(
     key_config: &KeyConfig,
+    style: &StyleConfig,
     cairo_ctx: &CairoContext,
 ) -> Result<TextLayoutResult, String> {
     let layout_params = TextLayoutParams {
         text: &key_config.name,
         key_width_px: key_config.width as f64,
         key_height_px: key_config.height as f64,
-        initial_font_size_pts: key_config.text_size.unwrap_or(DEFAULT_TEXT_SIZE_UNSCALED) as f64,
+        initial_font_size_pts: style.text_size.unwrap_or(DEFAULT_TEXT_SIZE_UNSCALED) as f64,
         min_font_size_pts_factor: 0.5,
         min_font_size_pts_abs: 6.0,
         padding_factor: 0.1,
         min_padding_abs: 2.0,
     };
 
-    // Pass the context directly to layout_text
-    layout_text(&layout_params, cairo_ctx)
+    // Pass the context directly to layout_text, with the key's font if it has one
+    cairo_ctx
+        .save()
+        .map_err(|e| format!("Failed to save Cairo context: {:?}", e))?;
+    if let Some(family) = &style.font {
+        crate::draw::set_font_family(cairo_ctx, family);
+    }
+    let result = layout_text(&layout_params, cairo_ctx);
+    cairo_ctx
+        .restore()
+        .map_err(|e| format!("Failed to restore Cairo context: {:?}", e))?;
+    result
+}
+
+/// Output format of `--check`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckFormat {
+    /// Human-readable report (default).
+    Text,
+    /// A single JSON document, for editor integrations and other tools.
+    Json,
+}
+
+/// Creates the 1x1 Cairo context with the default font used to simulate text layout.
+fn create_check_context() -> Result<CairoContext, String> {
+    let surface = ImageSurface::create(Format::A1, 1, 1)
+        .map_err(|e| format!("Failed to create Cairo ImageSurface for --check: {:?}", e))?;
+    let cairo_ctx = CairoContext::new(&surface)
+        .map_err(|e| format!("Failed to create Cairo Context for --check: {:?}", e))?;
+    cairo_ctx.set_font_face(&crate::draw::load_default_font_face());
+    Ok(cairo_ctx)
+}
+
+/// Converts diagnostics to JSON, with the line and column of each label if
+/// the source is known. Labels in an included file also name the `file`.
+fn diagnostics_json(diagnostics: &Diagnostics, source: Option<&str>) -> Value {
+    let items: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let labels: Vec<Value> = diagnostic
+                .labels
+                .iter()
+                .map(|(location, label)| {
+                    let span = &location.span;
+                    let mut value =
+                        json!({ "message": label, "start": span.start, "end": span.end });
+                    let label_source = match &location.file {
+                        Some(file) => {
+                            value["file"] = json!(file.name);
+                            Some(file.content.as_str())
+                        }
+                        None => source,
+                    };
+                    if let Some(source) = label_source {
+                        let (line, column) = line_column(source, span.start);
+                        value["line"] = json!(line);
+                        value["column"] = json!(column);
+                    }
+                    value
+                })
+                .collect();
+            json!({
+                "severity": diagnostic.severity.to_string(),
+                "message": diagnostic.message,
+                "labels": labels,
+            })
+        })
+        .collect();
+    Value::Array(items)
+}
+
+/// Builds the JSON report of `--check --format json`.
+///
+/// Contains the diagnostics, each key with its resolved keycode and simulated
+/// text layout, and the overlay configuration with defaults applied.
+fn check_report_json(
+    config_path: &str,
+    source: Option<&str>,
+    app_config: &AppConfig,
+    diagnostics: &Diagnostics,
+    cairo_ctx: &CairoContext,
+) -> Value {
+    let keys: Vec<Value> = app_config
+        .key
+        .iter()
+        .map(|key_config| {
+            let mut value = json!({
+                "name": key_config.name,
+                "keycode": key_config.keycode,
+                "left": key_config.left,
+                "top": key_config.top,
+                "width": key_config.width,
+                "height": key_config.height,
+            });
+            match simulate_text_layout_for_check(
+                key_config,
+                &app_config.key_style(key_config),
+                cairo_ctx,
+            ) {
+                Ok(layout_result) => {
+                    value["text_layout"] = json!({
+                        "final_text": layout_result.final_text,
+                        "final_font_size_pts": layout_result.final_font_size_pts,
+                        "truncated_chars": layout_result.truncated_chars,
+                    })
+                }
+                Err(e) => value["text_layout_error"] = json!(e),
+            }
+            let spans = &key_config.spans;
+            let key_source = match &spans.file {
+                Some(file) => {
+                    value["file"] = json!(file.name);
+                    Some(file.content.as_str())
+                }
+                None => source,
+            };
+            if let (Some(span), Some(source)) = (&spans.entry, key_source) {
+                value["line"] = json!(line_column(source, span.start).0);
+            }
+            value
+        })
+        .collect();
+    json!({
+        "config": config_path,
+        "valid": !diagnostics.has_errors(),
+        "errors": diagnostics.count(Severity::Error),
+        "warnings": diagnostics.count(Severity::Warning),
+        "diagnostics": diagnostics_json(diagnostics, source),
+        "keys": keys,
+        "overlay": app_config.overlay,
+    })
+}
+
+/// Reports why a configuration file failed to load for `--check`, and exits with status 1.
+///
+/// `error` is the message returned by `load_and_process_config`. In JSON, the
+/// file is processed again to report its problems as diagnostics.
+pub fn run_check_failed_load(
+    config_path: &str,
+    theme: Option<&str>,
+    error: &str,
+    format: CheckFormat,
+) -> ! {
+    if format == CheckFormat::Text {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    let source = read_config_file(config_path).ok();
+    let diagnostics = source
+        .as_deref()
+        .and_then(|source| process_config_source(source, config_path, theme).err())
+        .unwrap_or_else(|| {
+            let mut diagnostics = Diagnostics::default();
+            diagnostics.error(error);
+            diagnostics
+        });
+    let report = json!({
+        "config": config_path,
+        "valid": false,
+        "errors": diagnostics.count(Severity::Error),
+        "warnings": diagnostics.count(Severity::Warning),
+        "diagnostics": diagnostics_json(&diagnostics, source.as_deref()),
+        "keys": [],
+        "overlay": null,
+    });
+    println!("{:#}", report);
+    std::process::exit(1);
 }
 
 /// Runs the configuration check process.
 ///
 /// This is the main entry point for the `--check` command. It performs:
-/// 1. Basic configuration validation (`validate_config`).
+/// 1. Basic configuration validation (`validate_config`), printing every error and warning.
 /// 2. Sets up a dummy Cairo context with the default font.
 /// 3. Iterates through each key, simulating text layout (`simulate_text_layout_for_check`)
 ///    and printing information about its dimensions, keycode, and how its label fits.
 /// 4. Prints the overlay configuration details (`print_overlay_config_for_check`).
 ///
+/// With `CheckFormat::Json`, the same information is printed as one JSON
+/// document instead (see `check_report_json`).
+///
 /// Exits with status code 0 on success, or 1 if errors are found or setup fails.
 ///
 /// # Arguments
 ///
 /// * `config_path` - The path to the configuration file being checked (for display purposes).
+/// * `source` - The content of the configuration file, for showing where problems are.
 /// * `app_config` - A reference to the loaded `AppConfig`.
-pub fn run_check(config_path: &str, app_config: &AppConfig) {
+/// * `format` - Whether to print a human-readable report or JSON.
+/// * `strict` - Whether warnings (e.g. unknown fields) count as errors.
+pub fn run_check(
+    config_path: &str,
+    source: Option<&str>,
+    app_config: &AppConfig,
+    format: CheckFormat,
+    strict: bool,
+) {
+    let mut diagnostics = validate_config(app_config);
+    if strict {
+        diagnostics.deny_warnings();
+    }
+    let cairo_ctx = create_check_context().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    if format == CheckFormat::Json {
+        let report = check_report_json(config_path, source, app_config, &diagnostics, &cairo_ctx);
+        println!("{:#}", report);
+        std::process::exit(if diagnostics.has_errors() { 1 } else { 0 });
+    }
+
     println!(
         "Performing configuration check for '{}'...",
         config_path
     );
 
-    if let Err(e) = validate_config(app_config) {
-        eprintln!("Configuration validation failed: {}", e);
+    if !diagnostics.is_empty() {
+        eprintln!("{}\n", diagnostics.render(config_path, source));
+    }
+    let (errors, warnings) = (
+        diagnostics.count(Severity::Error),
+        diagnostics.count(Severity::Warning),
+    );
+    if errors > 0 {
+        eprintln!(
+            "Configuration validation failed: {} error(s), {} warning(s).",
+            errors, warnings
+        );
         std::process::exit(1);
+    } else if warnings > 0 {
+        println!("Basic validation (overlaps, duplicates, positive dimensions) passed with {} warning(s).", warnings);
     } else {
         println!("Basic validation (overlaps, duplicates, positive dimensions) passed.");
     }
 
-    let surface = ImageSurface::create(Format::A1, 1, 1)
-        .map_err(|e| format!("Failed to create Cairo ImageSurface for --check: {:?}", e));
-    if surface.is_err() {
-        eprintln!("{}", surface.err().unwrap());
-        std::process::exit(1);
-    }
-    let surface = surface.unwrap();
-
-    let cairo_ctx = CairoContext::new(&surface)
-        .map_err(|e| format!("Failed to create Cairo Context for --check: {:?}", e));
-    if cairo_ctx.is_err() {
-        eprintln!("{}", cairo_ctx.err().unwrap());
-        std::process::exit(1);
-    }
-    let cairo_ctx = cairo_ctx.unwrap();
-
-    let font_data: &[u8] = include_bytes!("../default-font/DejaVuSansMono.ttf");
-    // Create a FontFace from the embedded font data using FreeType, then bridge to Cairo.
-    // This is necessary because cairo-rs (0.19) doesn't provide a direct "font_face_create_from_data"
-    // without an existing FreeType face object when using its FreeType backend.
-    // The `freetype-rs` crate is used here as a loader for the `cairo::FontFace`.
-    let ft_library = freetype::Library::init().expect("FT init for cairo font face in check");
-    let ft_face = ft_library.new_memory_face(font_data.to_vec(), 0).expect("FT face for cairo font face in check");
-    let cairo_font_face = CairoFontFace::create_from_ft(&ft_face).expect("Cairo FT face creation for check");
-
-    cairo_ctx.set_font_face(&cairo_font_face);
-
     println!("\nKey Information (Layout from TOML, Text metrics simulated with Cairo):");
     println!(
         "{:<20} | {:<25} | {:<10} | {:<10} | {:<20}",
@@ -275,11 +687,12 @@ pub fn run_check(config_path: &str, app_config: &AppConfig) {
             key_config_item.left, key_config_item.top, right_edge, bottom_edge
         );
 
-        let initial_font_size = key_config_item
+        let style = app_config.key_style(key_config_item);
+        let initial_font_size = style
             .text_size
             .unwrap_or(DEFAULT_TEXT_SIZE_UNSCALED) as f64;
 
-            match simulate_text_layout_for_check(key_config_item, &cairo_ctx) {
+            match simulate_text_layout_for_check(key_config_item, &style, &cairo_ctx) {
                 Ok(layout_result) => {
                 let font_scale = if initial_font_size > 0.0 {
                         layout_result.final_font_size_pts / initial_font_size
@@ -318,3 +731,128 @@ pub fn run_check(config_path: &str, app_config: &AppConfig) {
     println!("\nConfiguration check finished.");
     std::process::exit(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Diagnostic;
+    use std::fs;
+
+    #[test]
+    fn test_check_report_json() {
+        let content = "[[key]]\nname = \"A\"\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n\
+                       text_color = \"#GG0000\"\n\
+                       [[key]]\nname = \"B\"\nleft = 40.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n";
+        let config = process_config_source(content, "keys.toml", None).unwrap();
+        let diagnostics = validate_config(&config);
+        let cairo_ctx = create_check_context().unwrap();
+        let report = check_report_json(
+            "keys.toml",
+            Some(content),
+            &config,
+            &diagnostics,
+            &cairo_ctx,
+        );
+        let report: Value = serde_json::from_str(&report.to_string()).unwrap();
+
+        assert_eq!(report["valid"], false);
+        assert_eq!(
+            (report["errors"].as_u64(), report["warnings"].as_u64()),
+            (Some(1), Some(1))
+        );
+        let diagnostics = report["diagnostics"].as_array().unwrap();
+        let summary: Vec<(&str, &str, u64, u64)> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let label = &diagnostic["labels"][0];
+                (
+                    diagnostic["severity"].as_str().unwrap(),
+                    label["message"].as_str().unwrap(),
+                    label["line"].as_u64().unwrap(),
+                    label["column"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("error", "overlaps with key 'A'", 8, 1),
+                ("warning", "invalid color", 7, 14)
+            ]
+        );
+        assert!(diagnostics[0]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Key 'A' (at 0.0,0.0 size 50.0x50.0) overlaps with key 'B'"));
+        assert!(diagnostics[1]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Key 'A' has an invalid text_color"));
+        assert_eq!(report["keys"][1]["name"], "B");
+        assert_eq!(report["keys"][1]["line"], 8);
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let content = "[[key]]\nname = \"Esc\"\nkeycode = \"esc\"\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n\
+                       bakground_color = \"#000000\"\n";
+        let config = process_config_source(content, "keys.toml", None).unwrap();
+        let mut diagnostics = validate_config(&config);
+        let warnings: Vec<&Diagnostic> = diagnostics.iter().collect();
+        // `keycode`, which fills `raw_keycode`, is not unknown
+        assert_eq!(warnings.len(), 1, "{}", diagnostics);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(
+            warnings[0].message,
+            "Unknown field 'bakground_color' in [[key]] 'Esc'; did you mean 'background_color'?"
+        );
+        let (location, label) = &warnings[0].labels[0];
+        assert_eq!(label, "did you mean 'background_color'?");
+        assert_eq!(&content[location.span.clone()], "bakground_color");
+        assert_eq!(line_column(content, location.span.start), (8, 1));
+        assert!(!diagnostics.has_errors());
+
+        // As with --strict
+        diagnostics.deny_warnings();
+        assert!(diagnostics.has_errors());
+    }
+
+    #[test]
+    fn test_unknown_fields_in_included_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let included = "[[key]]\nname = \"Esc\"\nkeycode = 1\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n\
+                        bakground_color = \"#000000\"\n";
+        let included_path = dir.path().join("team.toml");
+        fs::write(&included_path, included).unwrap();
+        let content =
+            "include = [\"team.toml\"]\n[[key]]\nname = \"Esc\"\ntext_colr = \"#FFFFFF\"\n";
+        let config = process_config_source(
+            content,
+            dir.path().join("keys.toml").to_str().unwrap(),
+            None,
+        )
+        .unwrap();
+        let diagnostics = validate_config(&config);
+        let warnings: Vec<&Diagnostic> = diagnostics.iter().collect();
+        assert_eq!(warnings.len(), 2, "{}", diagnostics);
+
+        let included_name = included_path.display().to_string();
+        assert_eq!(
+            warnings[0].message,
+            "Unknown field 'text_colr' in [[key]] 'Esc'; did you mean 'text_color'?"
+        );
+        assert_eq!(warnings[0].labels[0].0.file, None);
+        assert_eq!(
+            line_column(content, warnings[0].labels[0].0.span.start),
+            (4, 1)
+        );
+        assert_eq!(
+            warnings[1].message,
+            format!("Unknown field 'bakground_color' in [[key]] 'Esc' of '{}'; did you mean 'background_color'?", included_name)
+        );
+        let location = &warnings[1].labels[0].0;
+        let file = location.file.as_ref().unwrap();
+        assert_eq!(file.name, included_name);
+        assert_eq!(line_column(&file.content, location.span.start), (8, 1));
+    }
+}
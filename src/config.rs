@@ -4,17 +4,27 @@
 //! loaded from a TOML file. It includes parsing for colors, sizes,
 //! and key definitions, along with default values and validation.
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
 
+use crate::check;
+use crate::diagnostics::{did_you_mean, Diagnostics, EntrySpans, Severity, SourceFile};
+use crate::includes::{self, EntryOrigin, Includes};
 use crate::keycodes::{self, KeycodeRepr};
+use crate::keymap::{self, KeymapLabels};
+use crate::theme;
 
 /// Represents a size dimension that can be specified in absolute pixels or as a ratio.
 ///
 /// Used in TOML configuration for `size_width` and `size_height` of the overlay.
 /// It can be deserialized from an integer (interpreted as pixels) or a float
 /// (interpreted as a ratio, e.g., 0.5 for 50%).
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
 #[serde(untagged)] // Allows parsing "100" as Pixels(100) or "0.5" as Ratio(0.5)
 pub enum SizeDimension {
     /// Size in absolute pixels.
@@ -26,7 +36,7 @@ pub enum SizeDimension {
 /// Enum for specifying the anchor position of the overlay on the screen.
 ///
 /// Used in TOML as `overlay.position`.
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum OverlayPosition {
     /// Top edge, centered horizontally by default unless combined with Left/Right.
@@ -49,11 +59,166 @@ pub enum OverlayPosition {
     CenterRight,
 }
 
+/// What the OSD displays.
+///
+/// Used in TOML as `overlay.display_mode`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    /// A diagram of the configured keys, highlighting pressed ones (default).
+    #[default]
+    Keyboard,
+    /// A strip of recently typed text and key combos (e.g., "Ctrl+Shift+T").
+    Strip,
+}
+
+/// Where the label band (showing the active modifier chord) is drawn
+/// relative to the keyboard.
+///
+/// Used in TOML as `overlay.label_position`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LabelPosition {
+    /// No label band (default).
+    #[default]
+    None,
+    /// Above the keyboard.
+    Above,
+    /// Below the keyboard.
+    Below,
+}
+
+/// Where key labels are derived from when `[keymap]` is configured.
+///
+/// Used in TOML as `keymap.source`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeymapSource {
+    /// The keymap the compositor sends for the seat's keyboard (default).
+    #[default]
+    Compositor,
+    /// A keymap compiled from the RMLVO names in `[keymap]`.
+    Names,
+}
+
+/// Configuration for deriving key labels from an XKB keymap.
+///
+/// Keys without a `name` are labelled with what they type in this keymap.
+/// Empty RMLVO names fall back to the libxkbcommon defaults (and its
+/// `XKB_DEFAULT_*` environment variables).
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[cfg_attr(not(feature = "xkb"), allow(dead_code))] // RMLVO names are only read by libxkbcommon
+pub struct KeymapConfig {
+    /// Whether to use the compositor's keymap or compile one from names.
+    #[serde(default)]
+    pub source: KeymapSource,
+    /// XKB rules (e.g., "evdev").
+    #[serde(default)]
+    pub rules: String,
+    /// XKB model (e.g., "pc105").
+    #[serde(default)]
+    pub model: String,
+    /// XKB layout (e.g., "de" or "us").
+    #[serde(default)]
+    pub layout: String,
+    /// XKB layout variant (e.g., "dvorak").
+    #[serde(default)]
+    pub variant: String,
+    /// XKB options (e.g., "caps:escape").
+    #[serde(default)]
+    pub options: Option<String>,
+    /// Where the `[keymap]` table is in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
+}
+
+/// Where a legend is placed on a keycap.
+///
+/// Used in TOML as `legend_position` and as the `position` of secondary legends.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LegendPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// A keyboard layer that can have its own legend on a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegendLayer {
+    Shift,
+    AltGr,
+    Fn,
+}
+
+impl LegendLayer {
+    /// Order in which held layers take precedence when several are held.
+    pub const PRIORITY: [LegendLayer; 3] =
+        [LegendLayer::Fn, LegendLayer::AltGr, LegendLayer::Shift];
+
+    /// Returns the keycodes that activate this layer while held.
+    pub fn keycodes(self) -> &'static [u32] {
+        match self {
+            LegendLayer::Shift => &[42, 54], // leftshift, rightshift
+            LegendLayer::AltGr => &[100],    // rightalt
+            LegendLayer::Fn => &[464],       // fn
+        }
+    }
+
+    /// Returns where this layer's legend goes unless configured otherwise.
+    pub fn default_position(self) -> LegendPosition {
+        match self {
+            LegendLayer::Shift => LegendPosition::TopLeft,
+            LegendLayer::AltGr => LegendPosition::BottomRight,
+            LegendLayer::Fn => LegendPosition::TopRight,
+        }
+    }
+}
+
+/// A secondary legend on a key, e.g. the shifted symbol.
+///
+/// Can be specified in TOML as a plain string (e.g., `shift_legend = "!"`),
+/// placed at the layer's default position, or as a table with an explicit
+/// position (e.g., `shift_legend = { text = "!", position = "top" }`).
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum LegendConfig {
+    /// Legend text at the layer's default position.
+    Text(String),
+    /// Legend text at the given position.
+    Placed {
+        text: String,
+        position: LegendPosition,
+    },
+}
+
+impl LegendConfig {
+    pub fn text(&self) -> &str {
+        match self {
+            LegendConfig::Text(text) | LegendConfig::Placed { text, .. } => text,
+        }
+    }
+
+    /// Returns the configured position, or `default` for a plain string.
+    pub fn position_or(&self, default: LegendPosition) -> LegendPosition {
+        match self {
+            LegendConfig::Text(_) => default,
+            LegendConfig::Placed { position, .. } => *position,
+        }
+    }
+}
+
 /// Configuration for the OSD overlay window.
 ///
 /// Defines properties like target screen, position, size, margins,
 /// and default colors for various elements.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct OverlayConfig {
     /// Optional identifier for the target screen (e.g., "DP-1", "0").
     /// If `None`, the compositor chooses the screen.
@@ -62,6 +227,15 @@ pub struct OverlayConfig {
     /// Position of the overlay on the screen.
     #[serde(default = "default_overlay_position")]
     pub position: OverlayPosition,
+    /// Whether to show the keyboard diagram or the typed-text strip.
+    #[serde(default)]
+    pub display_mode: DisplayMode,
+    /// In strip mode, how long an entry stays visible (including its fade-out), in milliseconds.
+    #[serde(default = "default_strip_timeout_ms")]
+    pub strip_timeout_ms: u64,
+    /// In keyboard mode, where to show the active modifier chord (e.g., "Ctrl+Alt+Del").
+    #[serde(default)]
+    pub label_position: LabelPosition,
     /// Optional width of the overlay. Can be pixels or ratio.
     pub size_width: Option<SizeDimension>,
     /// Optional height of the overlay. Can be pixels or ratio.
@@ -100,12 +274,30 @@ pub struct OverlayConfig {
     /// Default text color for key labels when they are active (pressed).
     #[serde(default = "default_active_key_text_color_string")]
     pub active_key_text_color: String,
+    /// Default font family for key labels. `None` uses the built-in DejaVu Sans Mono.
+    pub default_key_font: Option<String>,
+    /// Default corner radius for keys (unscaled).
+    #[serde(default = "default_key_corner_radius")]
+    pub default_key_corner_radius: f32,
+    /// Default border thickness for keys (unscaled).
+    #[serde(default = "default_key_border_thickness")]
+    pub default_key_border_thickness: f32,
+    /// Optional theme (a built-in theme name or the path to a theme file) to
+    /// take the appearance settings from that this configuration doesn't set.
+    pub theme: Option<String>,
+    /// Where the `[overlay]` table is in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
 }
 
 /// Returns the default `OverlayPosition` (`BottomCenter`).
 fn default_overlay_position() -> OverlayPosition {
     OverlayPosition::BottomCenter
 }
+/// Returns the default strip entry timeout (`2500` ms).
+fn default_strip_timeout_ms() -> u64 {
+    2500
+}
 /// Returns the default margin value (`0`).
 fn default_overlay_margin() -> i32 {
     0
@@ -138,12 +330,23 @@ fn default_active_key_background_color_string() -> String {
 fn default_active_key_text_color_string() -> String {
     default_key_text_color_string()
 }
+/// Returns the default key corner radius (`DEFAULT_CORNER_RADIUS_UNSCALED`).
+fn default_key_corner_radius() -> f32 {
+    DEFAULT_CORNER_RADIUS_UNSCALED
+}
+/// Returns the default key border thickness (`DEFAULT_BORDER_THICKNESS_UNSCALED`).
+fn default_key_border_thickness() -> f32 {
+    DEFAULT_BORDER_THICKNESS_UNSCALED
+}
 
 impl Default for OverlayConfig {
     fn default() -> Self {
         OverlayConfig {
             screen: None,
             position: default_overlay_position(),
+            display_mode: DisplayMode::default(),
+            strip_timeout_ms: default_strip_timeout_ms(),
+            label_position: LabelPosition::default(),
             size_width: None,
             size_height: Some(SizeDimension::Ratio(0.3)),
             margin_top: default_overlay_margin(),
@@ -157,36 +360,88 @@ impl Default for OverlayConfig {
             default_key_outline_color: default_key_outline_color_string(),
             active_key_background_color: default_active_key_background_color_string(),
             active_key_text_color: default_active_key_text_color_string(),
+            default_key_font: None,
+            default_key_corner_radius: default_key_corner_radius(),
+            default_key_border_thickness: default_key_border_thickness(),
+            theme: None,
+            spans: EntrySpans::default(),
+        }
+    }
+}
+
+impl OverlayConfig {
+    /// Returns the default appearance of keys as a style, the last fallback
+    /// after a key's own properties and those of its style.
+    pub fn key_defaults(&self) -> StyleConfig {
+        StyleConfig {
+            text_size: None,
+            corner_radius: Some(self.default_key_corner_radius),
+            border_thickness: Some(self.default_key_border_thickness),
+            font: self.default_key_font.clone(),
+            background_color: Some(self.default_key_background_color.clone()),
+            text_color: Some(self.default_key_text_color.clone()),
+            outline_color: Some(self.default_key_outline_color.clone()),
+            active_background_color: Some(self.active_key_background_color.clone()),
+            active_text_color: Some(self.active_key_text_color.clone()),
+            // Pressed keys keep their outline color.
+            active_outline_color: None,
+            spans: EntrySpans::default(),
         }
     }
 }
 
+/// Outline of a key that is not a plain rectangle, e.g. an ISO Enter.
+///
+/// Coordinates are in layout units relative to the key's `left` and `top`,
+/// and must lie within its `width` x `height` box. In TOML, either
+/// `shape = { rects = [[0, 0, 80, 50], [12.5, 0, 67.5, 105]] }` or
+/// `shape = { vertices = [[0, 0], [80, 0], [80, 105], ...] }`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyShape {
+    /// Union of rectangles, each `[left, top, width, height]`.
+    Rects(Vec<[f32; 4]>),
+    /// Polygon through the given `[x, y]` corners.
+    Vertices(Vec<[f32; 2]>),
+}
+
 /// Configuration for a single key displayed on the OSD.
 ///
 /// Defines the key's appearance (name, dimensions, position, colors) and
 /// its corresponding input keycode.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct KeyConfig {
     /// Display name or label for the key (e.g., "A", "Shift").
+    /// If omitted, the label is derived from the `[keymap]`, or shows the keycode.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
-    /// Width of the key in abstract layout units.
-    pub width: f32,
-    /// Height of the key in abstract layout units.
-    pub height: f32,
+    /// Whether `name` was derived rather than set in the configuration.
+    /// Derived names are replaced when keymap labels change.
+    #[serde(skip)]
+    pub name_derived: bool,
     /// X-coordinate of the top-left corner in abstract layout units.
     pub left: f32,
     /// Y-coordinate of the top-left corner in abstract layout units.
     pub top: f32,
+    /// Width of the key in abstract layout units.
+    pub width: f32,
+    /// Height of the key in abstract layout units.
+    pub height: f32,
     /// Raw keycode representation from TOML (string or number).
     /// This is processed into the `keycode` field.
-    #[serde(alias = "keycode")]
+    #[serde(alias = "keycode", rename(serialize = "keycode"))]
     pub raw_keycode: Option<KeycodeRepr>,
     /// Resolved numerical keycode (e.g., from `linux/input-event-codes.h`).
     /// This field is populated by `load_and_process_config`.
-    #[serde(skip_deserializing)]
+    #[serde(skip)]
     pub keycode: u32,
     /// Optional rotation of the key in degrees.
     pub rotation_degrees: Option<f32>,
+    /// Optional non-rectangular outline within the key's box.
+    pub shape: Option<KeyShape>,
+    /// Optional name of a `[style.<name>]` table to take unset appearance
+    /// properties (text size, corner radius, border, font, colors) from.
+    pub style: Option<String>,
     /// Optional custom text size for this key (unscaled points).
     pub text_size: Option<f32>,
     /// Optional custom corner radius for this key (unscaled).
@@ -195,19 +450,335 @@ pub struct KeyConfig {
     pub border_thickness: Option<f32>,
     /// Optional custom background color string for this key.
     pub background_color: Option<String>,
+    /// Optional custom text color string for this key.
+    pub text_color: Option<String>,
+    /// Optional custom outline color string for this key.
+    pub outline_color: Option<String>,
+    /// Optional custom background color string for this key while it is pressed.
+    pub active_background_color: Option<String>,
+    /// Optional custom text color string for this key while it is pressed.
+    pub active_text_color: Option<String>,
+    /// Optional custom outline color string for this key while it is pressed.
+    /// Defaults to `outline_color`, as outlines don't change globally.
+    pub active_outline_color: Option<String>,
+    /// Optional font family for this key's text (e.g., "Noto Sans").
+    /// Defaults to the built-in DejaVu Sans Mono.
+    pub font: Option<String>,
+    /// Optional position of `name` on the keycap. Defaults to the center, or
+    /// to the bottom left if the key has secondary legends.
+    pub legend_position: Option<LegendPosition>,
+    /// Optional legend for the Shift layer (e.g., "!" on the "1" key).
+    pub shift_legend: Option<LegendConfig>,
+    /// Optional legend for the AltGr layer (e.g., "@" on a German "Q" key).
+    pub altgr_legend: Option<LegendConfig>,
+    /// Optional legend for the Fn layer (e.g., a media key on "F7").
+    pub fn_legend: Option<LegendConfig>,
+    /// Where the key is defined in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
+}
+
+impl KeyConfig {
+    /// Returns the legend configured for a layer, if any.
+    pub fn layer_legend(&self, layer: LegendLayer) -> Option<&LegendConfig> {
+        match layer {
+            LegendLayer::Shift => self.shift_legend.as_ref(),
+            LegendLayer::AltGr => self.altgr_legend.as_ref(),
+            LegendLayer::Fn => self.fn_legend.as_ref(),
+        }
+    }
+
+    /// Returns `true` if the key has a legend for any layer.
+    pub fn has_layer_legends(&self) -> bool {
+        LegendLayer::PRIORITY
+            .iter()
+            .any(|&layer| self.layer_legend(layer).is_some())
+    }
+
+    /// Returns the appearance properties set on the key itself.
+    pub fn own_style(&self) -> StyleConfig {
+        StyleConfig {
+            text_size: self.text_size,
+            corner_radius: self.corner_radius,
+            border_thickness: self.border_thickness,
+            font: self.font.clone(),
+            background_color: self.background_color.clone(),
+            text_color: self.text_color.clone(),
+            outline_color: self.outline_color.clone(),
+            active_background_color: self.active_background_color.clone(),
+            active_text_color: self.active_text_color.clone(),
+            active_outline_color: self.active_outline_color.clone(),
+            spans: EntrySpans::default(),
+        }
+    }
+}
+
+/// A named set of appearance properties shared by keys.
+///
+/// Used in TOML as `[style.<name>]` tables and referenced from keys with
+/// `style = "<name>"`. Properties set on a key win over its style, and
+/// properties set in neither come from the `[overlay]` defaults.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct StyleConfig {
+    /// Text size (unscaled points).
+    pub text_size: Option<f32>,
+    /// Corner radius (unscaled).
+    pub corner_radius: Option<f32>,
+    /// Border thickness (unscaled).
+    pub border_thickness: Option<f32>,
+    /// Font family of the text.
+    pub font: Option<String>,
+    /// Background color string.
+    pub background_color: Option<String>,
+    /// Text color string.
+    pub text_color: Option<String>,
+    /// Outline color string.
+    pub outline_color: Option<String>,
+    /// Background color string while pressed.
+    pub active_background_color: Option<String>,
+    /// Text color string while pressed.
+    pub active_text_color: Option<String>,
+    /// Outline color string while pressed.
+    pub active_outline_color: Option<String>,
+    /// Where the style is defined in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
+}
+
+impl StyleConfig {
+    /// Returns `self` with the properties it doesn't set taken from `fallback`.
+    pub fn or(self, fallback: &StyleConfig) -> StyleConfig {
+        StyleConfig {
+            text_size: self.text_size.or(fallback.text_size),
+            corner_radius: self.corner_radius.or(fallback.corner_radius),
+            border_thickness: self.border_thickness.or(fallback.border_thickness),
+            font: self.font.or_else(|| fallback.font.clone()),
+            background_color: self
+                .background_color
+                .or_else(|| fallback.background_color.clone()),
+            text_color: self.text_color.or_else(|| fallback.text_color.clone()),
+            outline_color: self
+                .outline_color
+                .or_else(|| fallback.outline_color.clone()),
+            active_background_color: self
+                .active_background_color
+                .or_else(|| fallback.active_background_color.clone()),
+            active_text_color: self
+                .active_text_color
+                .or_else(|| fallback.active_text_color.clone()),
+            active_outline_color: self
+                .active_outline_color
+                .or_else(|| fallback.active_outline_color.clone()),
+            spans: self.spans,
+        }
+    }
+}
+
+/// Overrides for one base layout key on a layer.
+///
+/// Fields that are not set keep the base key's value.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LayerKeyConfig {
+    /// The base layout key to override, identified by its keycode (string or number).
+    pub key: KeycodeRepr,
+    /// Display name on this layer.
+    pub name: Option<String>,
+    /// Keycode the key sends on this layer (string or number).
+    #[serde(alias = "keycode", rename(serialize = "keycode"))]
+    pub raw_keycode: Option<KeycodeRepr>,
+    /// Position of `name` on the keycap on this layer.
+    pub legend_position: Option<LegendPosition>,
+    /// Shift legend on this layer.
+    pub shift_legend: Option<LegendConfig>,
+    /// AltGr legend on this layer.
+    pub altgr_legend: Option<LegendConfig>,
+    /// Fn legend on this layer.
+    pub fn_legend: Option<LegendConfig>,
+    /// Where the override is defined in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
+}
+
+/// A layer of a QMK/ZMK-style keyboard, changing the meaning of some keys.
+///
+/// Used in TOML as `[[layer]]` tables. A layer is active while its
+/// `momentary` key is held, or from one press of its `toggle` key to the
+/// next. If several layers are active, the one defined last wins.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LayerConfig {
+    /// Layer name, shown in the label band while the layer is active.
+    pub name: String,
+    /// Key (string or number) that activates the layer while held.
+    pub momentary: Option<KeycodeRepr>,
+    /// Key (string or number) that switches the layer on and off.
+    pub toggle: Option<KeycodeRepr>,
+    /// Overrides for keys of the base layout.
+    #[serde(default)]
+    pub key: Vec<LayerKeyConfig>,
+    /// Resolved `momentary` keycode, populated by `load_and_process_config`.
+    #[serde(skip)]
+    pub momentary_keycode: Option<u32>,
+    /// Resolved `toggle` keycode, populated by `load_and_process_config`.
+    #[serde(skip)]
+    pub toggle_keycode: Option<u32>,
+    /// All keys on this layer: the base keys with this layer's overrides applied.
+    /// Populated by `load_and_process_config`.
+    #[serde(skip)]
+    pub keys: Vec<KeyConfig>,
+    /// Where the layer is defined in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
 }
 
 /// Root structure for the application configuration.
 ///
-/// Contains a list of `KeyConfig` definitions and an `OverlayConfig`.
-#[derive(Deserialize, Debug, Clone)]
+/// Contains a list of `KeyConfig` definitions, optional layers and an `OverlayConfig`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AppConfig {
+    /// Configuration files this one extends, merged by `includes::resolve_includes`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
     /// Vector of key configurations.
     #[serde(default)]
     pub key: Vec<KeyConfig>,
     /// Configuration for the overlay window.
     #[serde(default)]
     pub overlay: OverlayConfig,
+    /// Optional XKB keymap used to label keys without a `name`.
+    #[serde(default)]
+    pub keymap: Option<KeymapConfig>,
+    /// Optional layers overriding keys of the base layout.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layer: Vec<LayerConfig>,
+    /// Named styles that keys can refer to, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub style: BTreeMap<String, StyleConfig>,
+    /// Where the top-level fields are in the TOML source, for diagnostics.
+    #[serde(skip)]
+    pub spans: EntrySpans,
+    /// The files merged in through `include`, e.g. to check them for unknown fields.
+    #[serde(skip)]
+    pub included_files: Vec<Arc<SourceFile>>,
+}
+
+impl AppConfig {
+    /// Returns the keys as they are on `layer`, or the base keys for `None`.
+    pub fn keys_for_layer(&self, layer: Option<usize>) -> &[KeyConfig] {
+        match layer.and_then(|index| self.layer.get(index)) {
+            Some(layer_conf) => &layer_conf.keys,
+            None => &self.key,
+        }
+    }
+
+    /// Returns the appearance properties of a key: its own, then those of its
+    /// style, then the overlay defaults.
+    pub fn key_style(&self, key: &KeyConfig) -> StyleConfig {
+        let own_style = match key.style.as_ref().and_then(|name| self.style.get(name)) {
+            Some(style) => key.own_style().or(style),
+            None => key.own_style(),
+        };
+        own_style.or(&self.overlay.key_defaults())
+    }
+
+    /// Returns the keycodes of all keys on the base layout and on any layer.
+    pub fn monitored_keycodes(&self) -> impl Iterator<Item = u32> + '_ {
+        self.key
+            .iter()
+            .chain(
+                self.layer
+                    .iter()
+                    .flat_map(|layer_conf| layer_conf.keys.iter()),
+            )
+            .map(|k| k.keycode)
+    }
+
+    /// Sets the names of keys without a configured `name` from `labels`.
+    ///
+    /// Keys missing from `labels` are named after their keycode (e.g., "#30").
+    /// Names set in the configuration, also on layers, are left untouched.
+    pub fn apply_keymap_labels(&mut self, labels: &KeymapLabels) {
+        let layer_keys = self
+            .layer
+            .iter_mut()
+            .flat_map(|layer_conf| layer_conf.keys.iter_mut());
+        for key_conf in self
+            .key
+            .iter_mut()
+            .chain(layer_keys)
+            .filter(|k| k.name_derived)
+        {
+            key_conf.name = labels
+                .get(&key_conf.keycode)
+                .cloned()
+                .unwrap_or_else(|| format!("#{}", key_conf.keycode));
+        }
+    }
+}
+
+/// Serializes a configuration back to TOML, e.g. for an imported layout.
+///
+/// Keycodes are written as given in `raw_keycode`; keys without one are
+/// written without a `keycode` field.
+pub fn to_toml_string(config: &AppConfig) -> Result<String, String> {
+    let mut value = toml::Value::try_from(config)
+        .map_err(|e| format!("Failed to serialize configuration to TOML: {}", e))?;
+    shorten_floats(&mut value);
+    toml::to_string(&value).map_err(|e| format!("Failed to serialize configuration to TOML: {}", e))
+}
+
+/// Rewrites floats with the shortest representation of their `f32` value,
+/// so that e.g. `0.3` is not written as `0.30000001192092896`.
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(f) => {
+            *f = (*f as f32).to_string().parse().unwrap_or(*f);
+        }
+        toml::Value::Array(values) => values.iter_mut().for_each(shorten_floats),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| shorten_floats(v)),
+        _ => {}
+    }
+}
+
+/// Checks that a key shape is well-formed and fits in the key's `width` x `height` box.
+fn validate_shape(shape: &KeyShape, width: f32, height: f32) -> Result<(), String> {
+    // Allow for rounding in generated or imported layouts.
+    let tolerance = 0.01;
+    let fits = |x: f32, y: f32| {
+        x >= -tolerance && y >= -tolerance && x <= width + tolerance && y <= height + tolerance
+    };
+    match shape {
+        KeyShape::Rects(rects) => {
+            if rects.is_empty() {
+                return Err("'rects' is empty.".to_string());
+            }
+            for &[x, y, w, h] in rects {
+                if w <= 0.0 || h <= 0.0 {
+                    return Err(format!(
+                        "rectangle [{}, {}, {}, {}] has a non-positive size.",
+                        x, y, w, h
+                    ));
+                }
+                if !fits(x, y) || !fits(x + w, y + h) {
+                    return Err(format!(
+                        "rectangle [{}, {}, {}, {}] is outside the key's {}x{} box.",
+                        x, y, w, h, width, height
+                    ));
+                }
+            }
+        }
+        KeyShape::Vertices(vertices) => {
+            if vertices.len() < 3 {
+                return Err("'vertices' needs at least 3 points.".to_string());
+            }
+            if let Some([x, y]) = vertices.iter().find(|&&[x, y]| !fits(x, y)) {
+                return Err(format!(
+                    "vertex [{}, {}] is outside the key's {}x{} box.",
+                    x, y, width, height
+                ));
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Parses a color string into a tuple of (r, g, b, a) components.
@@ -302,6 +873,104 @@ pub fn parse_color_string(color_str: &str) -> Result<(f64, f64, f64, f64), Strin
     }
 }
 
+/// Resolves a keycode given in TOML as a key name or a number.
+pub fn resolve_keycode_repr(repr: &KeycodeRepr) -> Result<u32, String> {
+    match repr {
+        KeycodeRepr::Text(s) => keycodes::get_keycode_from_string(s),
+        KeycodeRepr::Number(n) => Ok(*n),
+    }
+}
+
+/// Resolves a keycode of a layer, recording an error at `field` of `spans` if it is invalid.
+fn resolve_layer_keycode(
+    layer_name: &str,
+    repr: &KeycodeRepr,
+    what: &str,
+    (spans, field): (&EntrySpans, &str),
+    diagnostics: &mut Diagnostics,
+) -> Option<u32> {
+    resolve_keycode_repr(repr)
+        .map_err(|e| {
+            diagnostics
+                .error(format!(
+                    "Error processing {} of layer '{}': {}",
+                    what, layer_name, e
+                ))
+                .with_label(spans.field(field), "invalid keycode");
+        })
+        .ok()
+}
+
+/// Resolves the keycodes of a layer and builds its full key list from `base_keys`.
+fn process_layer(
+    layer_conf: &mut LayerConfig,
+    base_keys: &[KeyConfig],
+    diagnostics: &mut Diagnostics,
+) {
+    let name = layer_conf.name.as_str();
+    let spans = &layer_conf.spans;
+    layer_conf.momentary_keycode = layer_conf.momentary.as_ref().and_then(|r| {
+        resolve_layer_keycode(name, r, "momentary key", (spans, "momentary"), diagnostics)
+    });
+    layer_conf.toggle_keycode = layer_conf
+        .toggle
+        .as_ref()
+        .and_then(|r| resolve_layer_keycode(name, r, "toggle key", (spans, "toggle"), diagnostics));
+    if layer_conf.momentary.is_none() && layer_conf.toggle.is_none() {
+        diagnostics
+            .error(format!(
+                "Layer '{}' has neither a 'momentary' nor a 'toggle' key, so it can never be activated.",
+                name
+            ))
+            .with_label(spans.entry_location(), "layer without an activation key");
+    }
+
+    let mut keys = base_keys.to_vec();
+    for override_conf in &layer_conf.key {
+        let spans = &override_conf.spans;
+        let Some(base_keycode) = resolve_layer_keycode(
+            name,
+            &override_conf.key,
+            "a key",
+            (spans, "key"),
+            diagnostics,
+        ) else {
+            continue;
+        };
+        let Some(key_conf) = keys.iter_mut().find(|k| k.keycode == base_keycode) else {
+            diagnostics
+                .error(format!(
+                    "Layer '{}' overrides keycode {}, which is not part of the base layout.",
+                    name, base_keycode
+                ))
+                .with_label(spans.field("key"), "no base key with this keycode");
+            continue;
+        };
+        if let Some(name) = &override_conf.name {
+            key_conf.name = name.clone();
+            key_conf.name_derived = false;
+        }
+        if let Some(keycode) = override_conf.raw_keycode.as_ref().and_then(|r| {
+            resolve_layer_keycode(name, r, "a keycode", (spans, "keycode"), diagnostics)
+        }) {
+            key_conf.keycode = keycode;
+        }
+        if override_conf.legend_position.is_some() {
+            key_conf.legend_position = override_conf.legend_position;
+        }
+        if override_conf.shift_legend.is_some() {
+            key_conf.shift_legend = override_conf.shift_legend.clone();
+        }
+        if override_conf.altgr_legend.is_some() {
+            key_conf.altgr_legend = override_conf.altgr_legend.clone();
+        }
+        if override_conf.fn_legend.is_some() {
+            key_conf.fn_legend = override_conf.fn_legend.clone();
+        }
+    }
+    layer_conf.keys = keys;
+}
+
 /// Default corner radius for keys, in unscaled layout units.
 pub const DEFAULT_CORNER_RADIUS_UNSCALED: f32 = 8.0;
 /// Default border thickness for keys, in unscaled layout units.
@@ -313,48 +982,593 @@ pub const DEFAULT_ROTATION_DEGREES: f32 = 0.0;
 
 /// Loads and processes the application configuration from a TOML file.
 ///
-/// This function reads the specified TOML file, deserializes it into an
-/// `AppConfig` struct, and then processes each `KeyConfig` to:
+/// This function reads the specified TOML file, merges its theme under it (see
+/// `process_config_source`), deserializes it into an `AppConfig` struct, and
+/// then processes each `KeyConfig` to:
 /// 1. Validate that key width and height are positive.
 /// 2. Resolve the `raw_keycode` (which can be a string name or a number from TOML)
 ///    into a numerical `keycode`. If `raw_keycode` is not specified, it attempts
 ///    to derive the keycode from the key's `name` field.
+/// 3. Resolve the keycodes of each `[[layer]]` and build its key list from the
+///    base keys and the layer's overrides.
+/// 4. Name keys without a `name`, from the `[keymap]` if it is compiled from
+///    RMLVO names. With a compositor keymap, labels are applied once it arrives.
 ///
 /// # Arguments
 ///
 /// * `config_path` - Path to the TOML configuration file.
+/// * `theme` - Theme to use instead of the configuration's `overlay.theme`, if any.
 ///
 /// # Returns
 ///
 /// * `Ok(AppConfig)` if loading and processing are successful.
 /// * `Err(String)` with a descriptive error message if any part of the process fails
-///   (file reading, TOML parsing, key validation, or keycode resolution).
-pub fn load_and_process_config(config_path: &str) -> Result<AppConfig, String> {
-    let config_content = fs::read_to_string(config_path)
-        .map_err(|e| format!("Failed to read configuration file '{}': {}", config_path, e))?;
+///   (file reading, TOML parsing, key validation, or keycode resolution). Problems
+///   found while processing are all listed, with snippets of the file.
+pub fn load_and_process_config(
+    config_path: &str,
+    theme: Option<&str>,
+) -> Result<AppConfig, String> {
+    let config_content = read_config_file(config_path)?;
+    process_config_str(&config_content, config_path, theme)
+}
+
+/// Name of the configuration file looked for in the configuration directories.
+pub const CONFIG_FILE_NAME: &str = "keys.toml";
+
+/// Returns the directories searched for `CONFIG_FILE_NAME`, in order:
+/// `$XDG_CONFIG_HOME/wayland-kbd-osd` (by default `~/.config/wayland-kbd-osd`),
+/// then `wayland-kbd-osd` in each of `$XDG_CONFIG_DIRS` (by default `/etc/xdg`).
+pub fn config_search_dirs() -> Vec<PathBuf> {
+    search_dirs_from(
+        std::env::var_os("XDG_CONFIG_HOME"),
+        std::env::var_os("HOME"),
+        std::env::var_os("XDG_CONFIG_DIRS"),
+    )
+}
+
+/// Like `config_search_dirs`, with the values of `$XDG_CONFIG_HOME`, `$HOME`
+/// and `$XDG_CONFIG_DIRS` given.
+fn search_dirs_from(
+    config_home: Option<OsString>,
+    home: Option<OsString>,
+    config_dirs: Option<OsString>,
+) -> Vec<PathBuf> {
+    let non_empty = |value: Option<OsString>| value.filter(|value| !value.is_empty());
+    let config_home = non_empty(config_home)
+        .map(PathBuf::from)
+        .or_else(|| non_empty(home).map(|home| Path::new(&home).join(".config")));
+    let config_dirs = non_empty(config_dirs).unwrap_or_else(|| "/etc/xdg".into());
+    config_home
+        .into_iter()
+        .chain(std::env::split_paths(&config_dirs))
+        // Relative paths are invalid in these variables and are ignored.
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join("wayland-kbd-osd"))
+        .collect()
+}
 
-    let mut app_config: AppConfig = toml::from_str(&config_content).map_err(|e| {
+/// Finds the configuration file to use when none is given, the first
+/// `CONFIG_FILE_NAME` in `config_search_dirs`.
+pub fn find_config_file() -> Option<PathBuf> {
+    find_config_in(&config_search_dirs())
+}
+
+/// Returns the first `CONFIG_FILE_NAME` that exists in `dirs`.
+fn find_config_in(dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads a configuration file, e.g. to keep its content for rendering diagnostics.
+pub fn read_config_file(config_path: &str) -> Result<String, String> {
+    fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read configuration file '{}': {}", config_path, e))
+}
+
+/// Parses and processes configuration TOML that has already been read.
+///
+/// Performs the same processing as `load_and_process_config`. `source_name`
+/// identifies the configuration in error messages (e.g., the file path).
+pub fn process_config_str(
+    config_content: &str,
+    source_name: &str,
+    theme: Option<&str>,
+) -> Result<AppConfig, String> {
+    process_config_source(config_content, source_name, theme).map_err(|diagnostics| {
         format!(
-            "Failed to parse TOML configuration from '{}': {}",
-            config_path, e
+            "Errors found in configuration '{}':\n{}",
+            source_name,
+            diagnostics.render(source_name, Some(config_content))
         )
-    })?;
+    })
+}
+
+/// Parses and processes configuration TOML, returning every problem found.
+///
+/// Like `process_config_str`, but keeps the problems as diagnostics pointing
+/// into `config_content`, including a TOML syntax error.
+///
+/// The configurations listed in `include` are merged first, as described in
+/// the `includes` module, relative to the configuration file.
+///
+/// If a theme is selected, by `theme` or else by `overlay.theme`, it is merged
+/// under the configuration: settings the configuration makes itself win. A
+/// theme file named in `overlay.theme` is looked up relative to the
+/// configuration file, one given as `theme` relative to the working directory.
+pub fn process_config_source(
+    config_content: &str,
+    source_name: &str,
+    theme: Option<&str>,
+) -> Result<AppConfig, Diagnostics> {
+    let toml_error = |e: toml::de::Error| {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics
+            .error(format!(
+                "Failed to parse TOML configuration: {}",
+                e.message()
+            ))
+            .with_label(e.span(), "invalid TOML");
+        diagnostics
+    };
+    let config_dir = Path::new(source_name).parent().unwrap_or(Path::new(""));
+    let mut table: toml::Table = toml::from_str(config_content).map_err(toml_error)?;
+    let mut includes = None;
+    let mut app_config: AppConfig = if table.contains_key("include") {
+        match includes::resolve_includes(&mut table, config_dir) {
+            Ok(resolved) => includes = Some(resolved),
+            Err(e) => {
+                let root_spans = toml::from_str::<SpannedTable>(config_content)
+                    .map(|table| EntrySpans::from(&table))
+                    .unwrap_or_default();
+                let mut diagnostics = Diagnostics::default();
+                diagnostics
+                    .error(e)
+                    .with_label(root_spans.field("include"), "included here");
+                return Err(diagnostics);
+            }
+        }
+        // The merged table has no source to point into.
+        toml::Value::Table(table.clone())
+            .try_into()
+            .map_err(|e: toml::de::Error| {
+                let mut diagnostics = Diagnostics::default();
+                diagnostics.error(format!(
+                    "Failed to parse TOML configuration merged with its includes: {}",
+                    e.message()
+                ));
+                diagnostics
+            })?
+    } else {
+        // Parsed from the source again, for the span of a type error.
+        toml::from_str(config_content).map_err(toml_error)?
+    };
+    attach_spans(&mut app_config, config_content, includes.as_ref());
+
+    if let Some(theme_name) = theme
+        .map(str::to_string)
+        .or_else(|| app_config.overlay.theme.clone())
+    {
+        let base_dir = match theme {
+            Some(_) => Path::new(""),
+            None => config_dir,
+        };
+        // Theme values are merged at the TOML level, where it is known which
+        // settings the configuration leaves unset.
+        let themed = theme::load_theme(&theme_name, base_dir).and_then(|theme_table| {
+            theme::merge_under(&mut table, theme_table);
+            toml::Value::Table(table)
+                .try_into::<AppConfig>()
+                .map_err(|e| format!("Failed to apply theme '{}': {}", theme_name, e.message()))
+        });
+        match themed {
+            Ok(mut themed_config) => {
+                attach_spans(&mut themed_config, config_content, includes.as_ref());
+                app_config = themed_config;
+            }
+            Err(e) => {
+                let mut diagnostics = Diagnostics::default();
+                let span = if theme.is_none() {
+                    app_config.overlay.spans.field("theme")
+                } else {
+                    None
+                };
+                diagnostics.error(e).with_label(span, "theme selected here");
+                return Err(diagnostics);
+            }
+        }
+    }
+    process_config(app_config, source_name)
+}
+
+/// Applies a theme to a configuration built in code (e.g., a preset).
+///
+/// Unlike with configuration files, the theme wins over the overlay
+/// settings, which were not chosen by a user.
+pub fn apply_theme(app_config: &mut AppConfig, theme_name: &str) -> Result<(), String> {
+    let mut table = theme::load_theme(theme_name, Path::new(""))?;
+    let overlay = toml::Value::try_from(&app_config.overlay)
+        .map_err(|e| format!("Failed to apply theme '{}': {}", theme_name, e))?;
+    theme::merge_under(
+        &mut table,
+        toml::Table::from_iter([("overlay".to_string(), overlay)]),
+    );
+    let themed: AppConfig = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e| format!("Failed to apply theme '{}': {}", theme_name, e.message()))?;
+    app_config.overlay = themed.overlay;
+    app_config.style = themed.style;
+    Ok(())
+}
+
+/// A TOML table with the span of the table and of each of its keys and values.
+type SpannedTable = Spanned<BTreeMap<Spanned<String>, Spanned<toml::Value>>>;
+
+/// Source locations of the entries of a configuration, parsed alongside `AppConfig`.
+#[derive(Deserialize)]
+struct SourceSpans {
+    #[serde(default)]
+    key: Vec<SpannedTable>,
+    #[serde(default)]
+    layer: Vec<SpannedTable>,
+    overlay: Option<SpannedTable>,
+    keymap: Option<SpannedTable>,
+    #[serde(default)]
+    style: BTreeMap<String, SpannedTable>,
+}
+
+/// Source locations of the `[[layer.key]]` overrides, by layer.
+#[derive(Deserialize)]
+struct LayerOverrideSpans {
+    #[serde(default)]
+    layer: Vec<OverrideSpans>,
+}
+
+#[derive(Deserialize)]
+struct OverrideSpans {
+    #[serde(default)]
+    key: Vec<SpannedTable>,
+}
+
+impl From<&SpannedTable> for EntrySpans {
+    fn from(table: &SpannedTable) -> Self {
+        let fields = table.get_ref();
+        EntrySpans {
+            entry: Some(table.span()),
+            fields: fields
+                .iter()
+                .map(|(name, value)| (name.get_ref().clone(), value.span()))
+                .collect(),
+            field_names: fields
+                .keys()
+                .map(|name| (name.get_ref().clone(), name.span()))
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// The spans of the entries of one TOML source: the configuration itself or
+/// a file it includes.
+struct FileSpans {
+    file: Option<Arc<SourceFile>>,
+    entries: Option<SourceSpans>,
+    overrides: Option<LayerOverrideSpans>,
+}
+
+impl FileSpans {
+    fn parse(content: &str, file: Option<Arc<SourceFile>>) -> Self {
+        FileSpans {
+            entries: toml::from_str(content).ok(),
+            overrides: toml::from_str(content).ok(),
+            file,
+        }
+    }
+
+    /// Returns the spans of the file for `origin`, parsing it if it is not in `files` yet.
+    fn find<'a>(files: &'a mut Vec<FileSpans>, origin: &EntryOrigin) -> &'a FileSpans {
+        let same_file = |spans: &FileSpans| match (&spans.file, &origin.file) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let index = match files.iter().position(same_file) {
+            Some(index) => index,
+            None => {
+                let file = origin
+                    .file
+                    .as_ref()
+                    .expect("the configuration itself is parsed first");
+                files.push(FileSpans::parse(&file.content, Some(file.clone())));
+                files.len() - 1
+            }
+        };
+        &files[index]
+    }
+
+    fn entry_spans(&self, table: &SpannedTable) -> EntrySpans {
+        EntrySpans {
+            file: self.file.clone(),
+            ..table.into()
+        }
+    }
+
+    fn key(&self, index: usize) -> Option<EntrySpans> {
+        self.entries
+            .as_ref()?
+            .key
+            .get(index)
+            .map(|table| self.entry_spans(table))
+    }
+
+    fn layer(&self, index: usize) -> Option<EntrySpans> {
+        self.entries
+            .as_ref()?
+            .layer
+            .get(index)
+            .map(|table| self.entry_spans(table))
+    }
+
+    fn layer_overrides(&self, index: usize) -> Vec<EntrySpans> {
+        let layer = self
+            .overrides
+            .as_ref()
+            .and_then(|spans| spans.layer.get(index));
+        layer.map_or_else(Vec::new, |layer| {
+            layer
+                .key
+                .iter()
+                .map(|table| self.entry_spans(table))
+                .collect()
+        })
+    }
+}
+
+/// Records where each entry of `app_config` is in `content`, which it was parsed from.
+///
+/// With `includes`, `app_config` was merged with included configurations:
+/// entries may have moved, and those only defined in an included file point
+/// into that file.
+fn attach_spans(app_config: &mut AppConfig, content: &str, includes: Option<&Includes>) {
+    // These all parse, since `content` was just deserialized into `app_config`.
+    if let Ok(table) = toml::from_str::<SpannedTable>(content) {
+        app_config.spans = (&table).into();
+    }
+    let mut files = vec![FileSpans::parse(content, None)];
+    let origin = |origins: Option<&Vec<EntryOrigin>>, index: usize| match origins {
+        Some(origins) => origins.get(index).cloned(),
+        None => Some(EntryOrigin { file: None, index }),
+    };
+    for (index, key_conf) in app_config.key.iter_mut().enumerate() {
+        let Some(origin) = origin(includes.map(|i| &i.origins.key), index) else {
+            continue;
+        };
+        if let Some(spans) = FileSpans::find(&mut files, &origin).key(origin.index) {
+            key_conf.spans = spans;
+        }
+    }
+    for (index, layer_conf) in app_config.layer.iter_mut().enumerate() {
+        let Some(origin) = origin(includes.map(|i| &i.origins.layer), index) else {
+            continue;
+        };
+        let file_spans = FileSpans::find(&mut files, &origin);
+        if let Some(spans) = file_spans.layer(origin.index) {
+            layer_conf.spans = spans;
+        }
+        for (override_conf, spans) in layer_conf
+            .key
+            .iter_mut()
+            .zip(file_spans.layer_overrides(origin.index))
+        {
+            override_conf.spans = spans;
+        }
+    }
+    if let Some(spans) = &files[0].entries {
+        if let Some(table) = &spans.overlay {
+            app_config.overlay.spans = table.into();
+        }
+        if let (Some(keymap_conf), Some(table)) = (app_config.keymap.as_mut(), &spans.keymap) {
+            keymap_conf.spans = table.into();
+        }
+        for (name, style_conf) in app_config.style.iter_mut() {
+            if let Some(table) = spans.style.get(name) {
+                style_conf.spans = table.into();
+            }
+        }
+    }
+    app_config.included_files = includes.map_or_else(Vec::new, |includes| includes.files.clone());
+}
+
+/// Returns the tables of an included file, each with a description (e.g.
+/// `[[key]] 'Esc'`), the fields it accepts and where it is, to look for
+/// unknown fields.
+pub fn included_tables(
+    file: &Arc<SourceFile>,
+) -> Vec<(String, &'static [&'static str], EntrySpans)> {
+    let file_spans = FileSpans::parse(&file.content, Some(file.clone()));
+    let mut tables = Vec::new();
+    if let Ok(table) = toml::from_str::<SpannedTable>(&file.content) {
+        tables.push((
+            "the top level".to_string(),
+            struct_fields::<AppConfig>(),
+            file_spans.entry_spans(&table),
+        ));
+    }
+    let Some(spans) = &file_spans.entries else {
+        return tables;
+    };
+    let name = |table: &SpannedTable| {
+        let name = table
+            .get_ref()
+            .get("name")
+            .and_then(|value| value.get_ref().as_str());
+        name.unwrap_or_default().to_string()
+    };
+    if let Some(table) = &spans.overlay {
+        tables.push((
+            "[overlay]".to_string(),
+            struct_fields::<OverlayConfig>(),
+            file_spans.entry_spans(table),
+        ));
+    }
+    if let Some(table) = &spans.keymap {
+        tables.push((
+            "[keymap]".to_string(),
+            struct_fields::<KeymapConfig>(),
+            file_spans.entry_spans(table),
+        ));
+    }
+    for table in &spans.key {
+        let description = format!("[[key]] '{}'", name(table));
+        tables.push((
+            description,
+            struct_fields::<KeyConfig>(),
+            file_spans.entry_spans(table),
+        ));
+    }
+    for (style_name, table) in &spans.style {
+        let description = format!("[style.{}]", style_name);
+        tables.push((
+            description,
+            struct_fields::<StyleConfig>(),
+            file_spans.entry_spans(table),
+        ));
+    }
+    for (index, table) in spans.layer.iter().enumerate() {
+        let layer_name = name(table);
+        tables.push((
+            format!("[[layer]] '{}'", layer_name),
+            struct_fields::<LayerConfig>(),
+            file_spans.entry_spans(table),
+        ));
+        for override_spans in file_spans.layer_overrides(index) {
+            let description = format!("[[layer.key]] of layer '{}'", layer_name);
+            tables.push((
+                description,
+                struct_fields::<LayerKeyConfig>(),
+                override_spans,
+            ));
+        }
+    }
+    tables
+}
+
+/// Returns the names of the fields a configuration struct accepts in TOML,
+/// including aliases, e.g. to detect misspelled fields.
+pub fn struct_fields<T: serde::de::DeserializeOwned>() -> &'static [&'static str] {
+    use serde::de::{self, Visitor};
+
+    /// Deserializer that records the field names a struct asks for, then gives up.
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("field names collected"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Processes a configuration that was parsed or built in code, e.g. by an importer.
+///
+/// Performs the same processing as `load_and_process_config`, starting from
+/// the deserialized `AppConfig`, and returns every problem found.
+pub fn process_config(
+    mut app_config: AppConfig,
+    source_name: &str,
+) -> Result<AppConfig, Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    let style_names: Vec<&str> = app_config.style.keys().map(String::as_str).collect();
+    let mut failed_keys = Vec::new();
+    for (index, key_conf) in app_config.key.iter_mut().enumerate() {
+        let errors_before = diagnostics.count(Severity::Error);
+        key_conf.name_derived = key_conf.name.is_empty();
+        let spans = &key_conf.spans;
+        if key_conf.name_derived && key_conf.raw_keycode.is_none() {
+            diagnostics
+                .error(format!(
+                    "Key at ({}, {}) has neither a 'name' nor a 'keycode'. Please specify at least one.",
+                    key_conf.left, key_conf.top
+                ))
+                .with_label(spans.entry_location(), "key without a name or keycode");
+            failed_keys.push(index);
+            continue;
+        }
 
-    let mut keycode_resolution_errors = Vec::new();
-    for key_conf in app_config.key.iter_mut() {
         // Basic validation for key dimensions, moved here from main's --check logic
         // as it's fundamental to a valid key definition before resolving keycodes.
         if key_conf.width <= 0.0 {
-            keycode_resolution_errors.push(format!(
-                "Key '{}' has invalid width: {}. Width must be positive.",
-                key_conf.name, key_conf.width
-            ));
+            diagnostics
+                .error(format!(
+                    "Key '{}' has invalid width: {}. Width must be positive.",
+                    key_conf.name, key_conf.width
+                ))
+                .with_label(spans.field("width"), "must be positive");
         }
         if key_conf.height <= 0.0 {
-            keycode_resolution_errors.push(format!(
-                "Key '{}' has invalid height: {}. Height must be positive.",
-                key_conf.name, key_conf.height
-            ));
+            diagnostics
+                .error(format!(
+                    "Key '{}' has invalid height: {}. Height must be positive.",
+                    key_conf.name, key_conf.height
+                ))
+                .with_label(spans.field("height"), "must be positive");
+        }
+
+        if let Some(shape) = &key_conf.shape {
+            if let Err(e) = validate_shape(shape, key_conf.width, key_conf.height) {
+                diagnostics
+                    .error(format!(
+                        "Key '{}' has an invalid shape: {}",
+                        key_conf.name, e
+                    ))
+                    .with_label(spans.field("shape"), "invalid shape");
+            }
+        }
+
+        if let Some(style) = key_conf
+            .style
+            .as_deref()
+            .filter(|name| !style_names.contains(name))
+        {
+            let (message, label) = match did_you_mean(style, &style_names) {
+                Some(suggestion) => (
+                    format!(
+                        "Key '{}' uses unknown style '{}'; did you mean '{}'?",
+                        key_conf.name, style, suggestion
+                    ),
+                    format!("did you mean '{}'?", suggestion),
+                ),
+                None => (
+                    format!(
+                        "Key '{}' uses unknown style '{}'. Define it as a [style.{}] table.",
+                        key_conf.name, style, style
+                    ),
+                    "unknown style".to_string(),
+                ),
+            };
+            diagnostics
+                .error(message)
+                .with_label(spans.field("style"), label);
         }
 
         // Resolve keycode:
@@ -364,11 +1578,9 @@ pub fn load_and_process_config(config_path: &str) -> Result<AppConfig, String> {
         // If `raw_keycode` is None (i.e., the 'keycode' field is missing in TOML for this key),
         // we attempt to derive the keycode from the key's 'name' field.
         let resolved_code = match key_conf.raw_keycode.as_ref() {
-            // Case 1: Keycode is specified as Text (string)
-            Some(KeycodeRepr::Text(s)) => keycodes::get_keycode_from_string(s),
-            // Case 2: Keycode is specified as a Number (u32)
-            Some(KeycodeRepr::Number(n)) => Ok(*n),
-            // Case 3: 'keycode' field is not specified in TOML for this key.
+            // Case 1: Keycode is specified as Text (string) or as a Number (u32)
+            Some(repr) => resolve_keycode_repr(repr),
+            // Case 2: 'keycode' field is not specified in TOML for this key.
             // Attempt to resolve the keycode from the key's 'name' field (e.g. name = "A" -> keycode for A).
             None => keycodes::get_keycode_from_string(&key_conf.name),
         };
@@ -376,28 +1588,220 @@ pub fn load_and_process_config(config_path: &str) -> Result<AppConfig, String> {
         match resolved_code {
             Ok(code) => key_conf.keycode = code, // Store the successfully resolved u32 keycode.
             Err(e) => {
-                let error_msg = if key_conf.raw_keycode.is_none() {
-                    format!(
-                        "Error processing key '{}': Could not resolve default keycode from name ('{}'). Please specify a 'keycode' field. Details: {}",
-                        key_conf.name, key_conf.name, e
-                    )
+                if key_conf.raw_keycode.is_none() {
+                    diagnostics
+                        .error(format!(
+                            "Error processing key '{}': Could not resolve default keycode from name ('{}'). Please specify a 'keycode' field. Details: {}",
+                            key_conf.name, key_conf.name, e
+                        ))
+                        .with_label(spans.field("name"), "no keycode for this name");
                 } else {
-                    format!(
-                        "Error processing keycode for key '{}': {}",
-                        key_conf.name, e
-                    )
-                };
-                keycode_resolution_errors.push(error_msg);
+                    diagnostics
+                        .error(format!(
+                            "Error processing keycode for key '{}': {}",
+                            key_conf.name, e
+                        ))
+                        .with_label(spans.field("keycode"), "unknown keycode");
+                }
             }
         }
+        if diagnostics.count(Severity::Error) > errors_before {
+            failed_keys.push(index);
+        }
     }
 
-    if !keycode_resolution_errors.is_empty() {
-        return Err(format!(
-            "Errors found during keycode resolution:\n- {}",
-            keycode_resolution_errors.join("\n- ")
-        ));
+    let base_keys = app_config.key.clone();
+    for layer_conf in app_config.layer.iter_mut() {
+        process_layer(layer_conf, &base_keys, &mut diagnostics);
+    }
+
+    if diagnostics.has_errors() {
+        // Report what the other checks find as well, leaving out the keys that
+        // failed, as e.g. their keycodes are unknown.
+        let mut index = 0;
+        app_config.key.retain(|_| {
+            index += 1;
+            !failed_keys.contains(&(index - 1))
+        });
+        diagnostics.append(check::validate_config(&app_config));
+        return Err(diagnostics);
     }
 
+    let labels = match app_config.keymap.as_ref() {
+        Some(keymap_conf) => {
+            let labels = keymap::check_supported().and_then(|()| match keymap_conf.source {
+                KeymapSource::Names => keymap::labels_from_names(keymap_conf),
+                KeymapSource::Compositor => Ok(KeymapLabels::new()),
+            });
+            match labels {
+                Ok(labels) => labels,
+                Err(e) => {
+                    diagnostics.error(format!("Invalid [keymap] in '{}': {}", source_name, e));
+                    return Err(diagnostics);
+                }
+            }
+        }
+        None => KeymapLabels::new(),
+    };
+    app_config.apply_keymap_labels(&labels);
+
     Ok(app_config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{line_column, Location};
+
+    fn os(value: &str) -> Option<OsString> {
+        Some(value.into())
+    }
+
+    #[test]
+    fn test_config_search_dirs() {
+        let dirs = search_dirs_from(os("/cfg"), os("/home/u"), os("/etc/a:relative:/etc/b"));
+        assert_eq!(
+            dirs,
+            [
+                "/cfg/wayland-kbd-osd",
+                "/etc/a/wayland-kbd-osd",
+                "/etc/b/wayland-kbd-osd"
+            ]
+            .map(PathBuf::from)
+        );
+        for (config_home, config_dirs) in [(None, None), (os(""), os(""))] {
+            assert_eq!(
+                search_dirs_from(config_home, os("/home/u"), config_dirs),
+                [
+                    "/home/u/.config/wayland-kbd-osd",
+                    "/etc/xdg/wayland-kbd-osd"
+                ]
+                .map(PathBuf::from)
+            );
+        }
+        assert_eq!(
+            search_dirs_from(os("relative"), None, os("/etc/a")),
+            [PathBuf::from("/etc/a/wayland-kbd-osd")]
+        );
+    }
+
+    #[test]
+    fn test_find_config_file() {
+        let root = tempfile::tempdir().unwrap();
+        let root_str = root.path().to_str().unwrap();
+        let dirs = search_dirs_from(
+            os(&format!("{}/home", root_str)),
+            None,
+            os(&format!("{}/etc1:{}/etc2", root_str, root_str)),
+        );
+        assert_eq!(find_config_in(&dirs), None);
+
+        let create = |dir: &Path| {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join(CONFIG_FILE_NAME), "").unwrap();
+            dir.join(CONFIG_FILE_NAME)
+        };
+        let etc2 = create(&dirs[2]);
+        assert_eq!(find_config_in(&dirs), Some(etc2));
+        let etc1 = create(&dirs[1]);
+        assert_eq!(find_config_in(&dirs), Some(etc1));
+        let home = create(&dirs[0]);
+        assert_eq!(find_config_in(&dirs), Some(home));
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let content = r##"[[key]]
+name = "A"
+left = 0.0
+top = 0.0
+width = 50.0
+height = 50.0
+
+[[key]]
+name = "B"
+left = 40.0
+top = 0.0
+width = 50.0
+height = 50.0
+
+[[key]]
+name = "C"
+left = 100.0
+top = 0.0
+width = 50.0
+height = 50.0
+text_color = "#GG0000"
+
+[[key]]
+name = "D"
+keycode = "no_such_key"
+left = 160.0
+top = 0.0
+width = 50.0
+height = 50.0
+"##;
+        let diagnostics = process_config_source(content, "keys.toml", None).unwrap_err();
+        let reported: Vec<(Severity, &str, (usize, usize))> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (location, label) = &diagnostic.labels[0];
+                (
+                    diagnostic.severity,
+                    label.as_str(),
+                    line_column(content, location.span.start),
+                )
+            })
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (Severity::Error, "unknown keycode", (25, 11)),
+                (Severity::Error, "overlaps with key 'A'", (8, 1)),
+                (Severity::Warning, "invalid color", (21, 14)),
+            ],
+            "{}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_spans_of_included_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let included = "[[key]]\nname = \"Esc\"\nkeycode = 1\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n\
+                        [[key]]\nname = \"Tab\"\nkeycode = 15\nleft = 0.0\ntop = 60.0\nwidth = 50.0\nheight = 50.0\n";
+        fs::write(dir.path().join("team.toml"), included).unwrap();
+        let content = "include = [\"team.toml\"]\n\
+                       [[key]]\nname = \"Fn\"\nkeycode = 464\nleft = 60.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n\
+                       [[key]]\nname = \"Esc\"\nbackground_color = \"#FF0000\"\n";
+        let source_name = dir.path().join("keys.toml");
+        let config = process_config_source(content, source_name.to_str().unwrap(), None).unwrap();
+        let line = |location: Option<Location>| {
+            let location = location.unwrap();
+            let source = location
+                .file
+                .as_ref()
+                .map_or(content, |file| file.content.as_str());
+            line_column(source, location.span.start).0
+        };
+        let names: Vec<&str> = config.key.iter().map(|key| key.name.as_str()).collect();
+        assert_eq!(names, ["Esc", "Tab", "Fn"]);
+
+        // The overriding entry points into the including file, even for fields it doesn't set
+        let esc = &config.key[0].spans;
+        assert_eq!(esc.file, None);
+        assert_eq!(line(esc.field("background_color")), 11);
+        assert_eq!(line(esc.field("left")), 9);
+        // A key only defined in the included file points into that file
+        let tab = &config.key[1].spans;
+        assert_eq!(
+            tab.file.as_ref().map(|file| file.name.as_str()),
+            Some(dir.path().join("team.toml").to_str().unwrap())
+        );
+        assert_eq!(line(tab.field("top")), 12);
+        assert_eq!(line(tab.entry_location()), 8);
+        // The appended key keeps its own entry
+        assert_eq!(config.key[2].spans.file, None);
+        assert_eq!(line(config.key[2].spans.field("keycode")), 4);
+    }
+}
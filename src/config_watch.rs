@@ -0,0 +1,173 @@
+// src/config_watch.rs
+
+//! This module watches the configuration file for changes using inotify.
+//!
+//! The directory containing the file is watched rather than the file itself:
+//! many editors save by writing a new file and renaming it over the old one,
+//! which would silently end a watch on the original inode. Events are filtered
+//! by file name. The inotify fd is registered with `FdPoller`.
+
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+/// Size of the fixed part of `struct inotify_event` (wd, mask, cookie, len).
+const INOTIFY_EVENT_HEADER_LEN: usize = 16;
+
+/// Watches a single file for completed writes, renames onto it and deletion.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    file_name: OsString,
+    inotify_fd: OwnedFd,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`. The file's directory must exist.
+    pub fn new(path: &str) -> Result<Self, String> {
+        let path = PathBuf::from(path);
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("Cannot watch '{}': not a file path", path.display()))?
+            .to_os_string();
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let raw_fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if raw_fd < 0 {
+            return Err(format!(
+                "inotify_init1 failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: raw_fd was just returned by inotify_init1 and is owned by nobody else.
+        let inotify_fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+        let mut dir_bytes = dir.as_os_str().as_bytes().to_vec();
+        dir_bytes.push(0);
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE;
+        let wd = unsafe {
+            libc::inotify_add_watch(
+                inotify_fd.as_raw_fd(),
+                dir_bytes.as_ptr() as *const libc::c_char,
+                mask,
+            )
+        };
+        if wd < 0 {
+            return Err(format!(
+                "Failed to watch directory '{}': {}",
+                dir.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        log::info!(
+            "Watching configuration file '{}' for changes.",
+            path.display()
+        );
+        Ok(ConfigWatcher {
+            path,
+            file_name,
+            inotify_fd,
+        })
+    }
+
+    /// Returns the path of the watched file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Drains pending inotify events and returns `true` if any of them
+    /// concerned the watched file.
+    ///
+    /// Must be called when the inotify fd is readable.
+    pub fn take_changed(&mut self) -> bool {
+        let mut changed = false;
+        // Large enough for several events with NAME_MAX names.
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.inotify_fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n < 0 {
+                let err = std::io::Error::last_os_error();
+                match err.kind() {
+                    ErrorKind::Interrupted => continue,
+                    ErrorKind::WouldBlock => break,
+                    _ => {
+                        log::warn!(
+                            "Failed to read inotify events for '{}': {}",
+                            self.path.display(),
+                            err
+                        );
+                        break;
+                    }
+                }
+            }
+            if n == 0 {
+                break;
+            }
+
+            let mut offset = 0usize;
+            while offset + INOTIFY_EVENT_HEADER_LEN <= n as usize {
+                let header = &buf[offset..offset + INOTIFY_EVENT_HEADER_LEN];
+                let name_len =
+                    u32::from_ne_bytes([header[12], header[13], header[14], header[15]]) as usize;
+                let name_start = offset + INOTIFY_EVENT_HEADER_LEN;
+                let name_end = (name_start + name_len).min(n as usize);
+                // The name is NUL-padded to an alignment boundary.
+                let name: Vec<u8> = buf[name_start..name_end]
+                    .iter()
+                    .copied()
+                    .take_while(|&b| b != 0)
+                    .collect();
+                if OsString::from_vec(name) == self.file_name {
+                    changed = true;
+                }
+                offset = name_start + name_len;
+            }
+        }
+        changed
+    }
+}
+
+impl AsRawFd for ConfigWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify_fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_detects_writes_and_renames_of_the_watched_file_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.toml");
+        fs::write(&path, "a").unwrap();
+        let mut watcher = ConfigWatcher::new(path.to_str().unwrap()).unwrap();
+        assert!(!watcher.take_changed());
+
+        fs::write(dir.path().join("other.toml"), "b").unwrap();
+        assert!(!watcher.take_changed());
+
+        fs::write(&path, "c").unwrap();
+        assert!(watcher.take_changed());
+        assert!(!watcher.take_changed());
+
+        // Editors that save atomically write a temporary file and rename it.
+        let tmp = dir.path().join(".keys.toml.swp");
+        fs::write(&tmp, "d").unwrap();
+        fs::rename(&tmp, &path).unwrap();
+        assert!(watcher.take_changed());
+    }
+}
@@ -0,0 +1,371 @@
+// src/diagnostics.rs
+
+//! This module collects configuration errors and warnings.
+//!
+//! Loading and validating a configuration reports every problem it finds as a
+//! `Diagnostic` instead of stopping at the first one. Diagnostics point at the
+//! TOML source through byte spans, recorded per entry and field in
+//! `EntrySpans`, and are rendered as rustc-style snippets when the source is
+//! available. Configurations built in code (presets, imports) have no spans.
+//! Entries merged from an included file point into that file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A byte range in the TOML source.
+pub type Span = Range<usize>;
+
+/// A file that a configuration includes, for diagnostics pointing into it.
+#[derive(Debug, PartialEq)]
+pub struct SourceFile {
+    /// The path of the file, as shown in diagnostics.
+    pub name: String,
+    pub content: String,
+}
+
+/// A span in the configuration's own source, or in a file it includes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub span: Span,
+    /// The included file the span is in, or `None` for the configuration itself.
+    pub file: Option<Arc<SourceFile>>,
+}
+
+impl From<Span> for Location {
+    fn from(span: Span) -> Self {
+        Location { span, file: None }
+    }
+}
+
+/// Where a configuration entry (e.g. a `[[key]]` table) and its fields are in
+/// the TOML source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntrySpans {
+    /// The whole entry, from its header to its last field.
+    pub entry: Option<Span>,
+    /// The value of each field set in the entry, by field name.
+    pub fields: HashMap<String, Span>,
+    /// The name of each field set in the entry, e.g. to point at a misspelled one.
+    pub field_names: HashMap<String, Span>,
+    /// The included file the spans are in, or `None` for the configuration itself.
+    pub file: Option<Arc<SourceFile>>,
+}
+
+impl EntrySpans {
+    /// Returns the location of a field's value, or of the whole entry if the field is not set.
+    pub fn field(&self, name: &str) -> Option<Location> {
+        self.fields
+            .get(name)
+            .cloned()
+            .or_else(|| self.entry.clone())
+            .map(|span| self.locate(span))
+    }
+
+    /// Returns the location of the whole entry.
+    pub fn entry_location(&self) -> Option<Location> {
+        self.entry.clone().map(|span| self.locate(span))
+    }
+
+    /// Returns the location of a span of this entry's file.
+    pub fn locate(&self, span: Span) -> Location {
+        Location {
+            span,
+            file: self.file.clone(),
+        }
+    }
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The configuration cannot be used.
+    Error,
+    /// The configuration works, but probably not as intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A single problem found in a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Source locations with a short label; the first one is the primary location.
+    pub labels: Vec<(Location, String)>,
+}
+
+impl Diagnostic {
+    /// Adds a labelled source location, if it is known.
+    pub fn with_label(
+        &mut self,
+        location: Option<impl Into<Location>>,
+        label: impl Into<String>,
+    ) -> &mut Self {
+        if let Some(location) = location {
+            self.labels.push((location.into(), label.into()));
+        }
+        self
+    }
+}
+
+/// All problems found in a configuration, in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Records an error, returning it to attach source locations.
+    pub fn error(&mut self, message: impl Into<String>) -> &mut Diagnostic {
+        self.push(Severity::Error, message.into())
+    }
+
+    /// Records a warning, returning it to attach source locations.
+    pub fn warning(&mut self, message: impl Into<String>) -> &mut Diagnostic {
+        self.push(Severity::Warning, message.into())
+    }
+
+    fn push(&mut self, severity: Severity, message: String) -> &mut Diagnostic {
+        self.items.push(Diagnostic {
+            severity,
+            message,
+            labels: Vec::new(),
+        });
+        self.items.last_mut().unwrap()
+    }
+
+    /// Adds the diagnostics of `other` after these.
+    pub fn append(&mut self, mut other: Diagnostics) {
+        self.items.append(&mut other.items);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    /// Turns all warnings into errors, for strict checking.
+    pub fn deny_warnings(&mut self) {
+        for diagnostic in &mut self.items {
+            diagnostic.severity = Severity::Error;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the number of diagnostics with the given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.items.iter().filter(|d| d.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    /// Renders all diagnostics, with snippets of `source` where spans are known.
+    ///
+    /// `source_name` is shown in the location lines (e.g. `--> keys.toml:12:1`).
+    /// Without a source, only the messages and the locations in included
+    /// files are rendered.
+    pub fn render(&self, source_name: &str, source: Option<&str>) -> String {
+        let mut rendered = Vec::new();
+        for diagnostic in &self.items {
+            let mut out = format!("{}: {}\n", diagnostic.severity, diagnostic.message);
+            let labels: Vec<((&str, &str), &Span, &String)> = diagnostic
+                .labels
+                .iter()
+                .filter_map(|(location, label)| {
+                    let file = match &location.file {
+                        Some(file) => (file.name.as_str(), file.content.as_str()),
+                        None => (source_name, source?),
+                    };
+                    Some((file, &location.span, label))
+                })
+                .collect();
+            // Line numbers of all snippets are right-aligned to the widest.
+            let gutter_width = labels
+                .iter()
+                .map(|((_, content), span, _)| line_column(content, span.start).0.to_string().len())
+                .max()
+                .unwrap_or(1);
+            for (index, (file, span, label)) in labels.into_iter().enumerate() {
+                render_snippet(&mut out, file, span, label, index == 0, gutter_width);
+            }
+            rendered.push(out.trim_end().to_string());
+        }
+        // Like rustc, separate diagnostics with snippets by a blank line.
+        rendered.join(if source.is_some() { "\n\n" } else { "\n" })
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    /// Formats the messages without source snippets, one per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self
+            .items
+            .iter()
+            .map(|d| format!("{}: {}", d.severity, d.message))
+            .collect();
+        f.write_str(&messages.join("\n"))
+    }
+}
+
+/// Returns the candidate that `name` is most likely a misspelling of, if any.
+///
+/// Candidates within an edit distance of a third of `name`'s length (at
+/// least 1) are considered.
+pub fn did_you_mean<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|&candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between two strings, in characters, counting insertions,
+/// deletions, substitutions and swaps of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Returns the 1-based line and column of a byte offset in `source`.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Appends a rustc-style snippet of the first source line of `span`.
+///
+/// The primary location is introduced with `-->` and underlined with `^`,
+/// others with `:::` and `-`. Multi-line spans (e.g. a whole `[[key]]` entry)
+/// are underlined on their first line.
+fn render_snippet(
+    out: &mut String,
+    (source_name, source): (&str, &str),
+    span: &Span,
+    label: &str,
+    primary: bool,
+    gutter_width: usize,
+) {
+    let (line, column) = line_column(source, span.start);
+    let line_text = source.lines().nth(line - 1).unwrap_or("");
+    let width = source
+        .get(span.clone())
+        .and_then(|text| text.lines().next())
+        .map_or(1, |first| first.chars().count().max(1));
+    let gutter = " ".repeat(gutter_width);
+    let (arrow, marker) = if primary { ("-->", "^") } else { (":::", "-") };
+    let padding: String = line_text
+        .chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    out.push_str(&format!(
+        "{}{} {}:{}:{}\n",
+        gutter, arrow, source_name, line, column
+    ));
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!(
+        "{:>width$} | {}\n",
+        line,
+        line_text,
+        width = gutter_width
+    ));
+    out.push_str(format!("{} | {}{} {}", gutter, padding, marker.repeat(width), label).trim_end());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_snippets() {
+        let source = "[[key]]\nname = \"A\"\nkeycode = \"nope\"\n\n[[key]]\nname = \"B\"\n";
+        let mut diagnostics = Diagnostics::default();
+        let start = source.find("\"nope\"").unwrap();
+        diagnostics
+            .error("Unknown keycode 'nope'.")
+            .with_label(Some(start..start + 6), "unknown keycode");
+        let second = source.rfind("[[key]]").unwrap();
+        diagnostics
+            .warning("Keys overlap.")
+            .with_label(Some(0..20), "key 'A'")
+            .with_label(Some(second..source.len()), "key 'B'");
+        assert!(diagnostics.has_errors());
+        assert_eq!(diagnostics.count(Severity::Warning), 1);
+        assert_eq!(
+            diagnostics.render("keys.toml", Some(source)),
+            "error: Unknown keycode 'nope'.
+ --> keys.toml:3:11
+  |
+3 | keycode = \"nope\"
+  |           ^^^^^^ unknown keycode
+
+warning: Keys overlap.
+ --> keys.toml:1:1
+  |
+1 | [[key]]
+  | ^^^^^^^ key 'A'
+ ::: keys.toml:5:1
+  |
+5 | [[key]]
+  | ------- key 'B'"
+        );
+        assert_eq!(
+            diagnostics.to_string(),
+            "error: Unknown keycode 'nope'.\nwarning: Keys overlap."
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let fields = ["name", "keycode", "background_color", "border_thickness"];
+        assert_eq!(
+            did_you_mean("bakground_color", &fields),
+            Some("background_color")
+        );
+        assert_eq!(did_you_mean("keycdoe", &fields), Some("keycode"));
+        assert_eq!(did_you_mean("nmae", &fields), Some("name"));
+        assert_eq!(did_you_mean("colour", &fields), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
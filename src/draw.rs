@@ -1,7 +1,55 @@
 // Drawing the keyboard
 
+use crate::config::LegendPosition;
 use crate::text_utils::{layout_text, TextLayoutParams}; // Removed CairoMetricsProvider
 use cairo::{Context, FontFace as CairoFontFace};
+use freetype::Library as FreeTypeLibrary;
+
+/// Loads the embedded DejaVu Sans Mono font as a Cairo font face.
+///
+/// cairo-rs (0.19) has no "font_face_create_from_data" without an existing
+/// FreeType face when using its FreeType backend, so `freetype-rs` is used to
+/// load the face and Cairo is bridged on top of it.
+pub fn load_default_font_face() -> CairoFontFace {
+    let font_data: &[u8] = include_bytes!("../default-font/DejaVuSansMono.ttf");
+    let ft_library = FreeTypeLibrary::init().expect("FT init failed");
+    let ft_face = ft_library
+        .new_memory_face(font_data.to_vec(), 0)
+        .expect("FT face load failed");
+    CairoFontFace::create_from_ft(&ft_face).expect("Cairo FT face creation failed")
+}
+
+/// Selects a font family installed on the system for the text drawn next.
+///
+/// Uses Cairo's "toy" font API, which asks fontconfig for the closest match,
+/// so an unknown family still yields some font. On failure, the current
+/// font face is kept.
+pub fn set_font_family(ctx: &Context, family: &str) {
+    match CairoFontFace::toy_create(family, cairo::FontSlant::Normal, cairo::FontWeight::Normal) {
+        Ok(font_face) => ctx.set_font_face(&font_face),
+        Err(e) => log::warn!(
+            "Failed to select font '{}': {}. Using the default font.",
+            family,
+            e
+        ),
+    }
+}
+
+/// Font size of a legend relative to `KeyDisplay::text_size`, for keys with several legends.
+const LEGEND_TEXT_SCALE: f64 = 0.55;
+/// Font size of an emphasized legend relative to `KeyDisplay::text_size`.
+const EMPHASIZED_LEGEND_TEXT_SCALE: f64 = 0.75;
+/// Opacity of the other legends while one legend on the key is emphasized.
+const DIMMED_LEGEND_ALPHA: f64 = 0.4;
+
+/// A legend placed on a keycap, for keys with several legends (e.g., "1" and "!").
+#[derive(Debug, Clone)]
+pub struct Legend {
+    pub text: String,
+    pub position: LegendPosition,
+    /// Drawn larger, with the other legends dimmed, e.g. while its layer's modifier is held.
+    pub emphasized: bool,
+}
 
 // Struct to hold key properties for drawing (calculated from KeyConfig and AppState)
 // This struct is prepared by AppState::draw and passed to paint_all_keys
@@ -19,6 +67,143 @@ pub struct KeyDisplay {
     pub border_color: (f64, f64, f64, f64),
     pub background_color: (f64, f64, f64, f64),
     pub text_color: (f64, f64, f64, f64),
+    /// Legends drawn at their positions instead of the centered `text`.
+    /// Empty for keys with a single, centered legend.
+    pub legends: Vec<Legend>,
+    /// Outlines of a non-rectangular key, relative to the top-left corner of
+    /// its `width` x `height` box. Empty for rectangular keys.
+    pub outlines: Vec<Vec<(f32, f32)>>,
+    /// Font family of the text, or `None` for the font face set on the context.
+    pub font: Option<String>,
+}
+
+/// Returns the horizontal and vertical alignment of a legend position,
+/// from 0.0 (left/top) to 1.0 (right/bottom).
+fn legend_alignment(position: LegendPosition) -> (f64, f64) {
+    match position {
+        LegendPosition::TopLeft => (0.0, 0.0),
+        LegendPosition::Top => (0.5, 0.0),
+        LegendPosition::TopRight => (1.0, 0.0),
+        LegendPosition::Left => (0.0, 0.5),
+        LegendPosition::Center => (0.5, 0.5),
+        LegendPosition::Right => (1.0, 0.5),
+        LegendPosition::BottomLeft => (0.0, 1.0),
+        LegendPosition::Bottom => (0.5, 1.0),
+        LegendPosition::BottomRight => (1.0, 1.0),
+    }
+}
+
+/// Draws the legends of a key with several legends.
+///
+/// The context origin must be the top-left corner of the key. Legends in the
+/// left or right column may take up half the key width; text that does not
+/// fit is shrunk and truncated like a centered label.
+fn draw_legends(ctx: &Context, key: &KeyDisplay, width: f64, height: f64) {
+    let any_emphasized = key.legends.iter().any(|legend| legend.emphasized);
+    let padding = (width.min(height) * 0.1).max(2.0);
+    let (r, g, b, a) = key.text_color;
+
+    for legend in &key.legends {
+        let (align_x, align_y) = legend_alignment(legend.position);
+        let (scale, alpha) = match (legend.emphasized, any_emphasized) {
+            (true, _) => (EMPHASIZED_LEGEND_TEXT_SCALE, a),
+            (false, true) => (LEGEND_TEXT_SCALE, a * DIMMED_LEGEND_ALPHA),
+            (false, false) => (LEGEND_TEXT_SCALE, a),
+        };
+        let cell_width = if align_x == 0.5 { width } else { width / 2.0 };
+        let text_layout_params = TextLayoutParams {
+            text: &legend.text,
+            key_width_px: cell_width,
+            key_height_px: height,
+            initial_font_size_pts: key.text_size as f64 * scale,
+            min_font_size_pts_factor: 0.5,
+            min_font_size_pts_abs: 6.0,
+            padding_factor: 0.1,
+            min_padding_abs: 2.0,
+        };
+        match layout_text(&text_layout_params, ctx) {
+            Ok(layout_result) => {
+                ctx.set_font_size(layout_result.final_font_size_pts);
+                let text_extents = ctx
+                    .text_extents(&layout_result.final_text)
+                    .expect("Failed to get text extents for legend");
+                let text_x = padding + (width - 2.0 * padding - text_extents.width()) * align_x
+                    - text_extents.x_bearing();
+                let text_y = padding + (height - 2.0 * padding - text_extents.height()) * align_y
+                    - text_extents.y_bearing();
+
+                ctx.set_source_rgba(r, g, b, alpha);
+                ctx.move_to(text_x, text_y);
+                ctx.show_text(&layout_result.final_text)
+                    .expect("Cairo show_text failed");
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to layout legend '{}' for key '{}': {}",
+                    legend.text,
+                    key.text,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Adds a closed polygon with rounded corners to the current path.
+///
+/// Each corner is rounded with `radius`, reduced where the adjacent edges are
+/// too short. Concave corners (e.g. the inner corner of an ISO Enter) are
+/// rounded the same way.
+fn rounded_polygon_path(ctx: &Context, points: &[(f32, f32)], radius: f64) {
+    use std::f64::consts::PI;
+
+    let n = points.len();
+    let point = |i: usize| {
+        let (x, y) = points[i % n];
+        (x as f64, y as f64)
+    };
+    ctx.new_sub_path();
+    for i in 0..n {
+        let (px, py) = point(i + n);
+        let (ax, ay) = point(i + n - 1);
+        let (bx, by) = point(i + 1);
+        let (len_a, len_b) = ((ax - px).hypot(ay - py), (bx - px).hypot(by - py));
+        if len_a == 0.0 || len_b == 0.0 {
+            continue;
+        }
+        // Unit vectors from the corner towards its neighbours.
+        let (ux, uy) = ((ax - px) / len_a, (ay - py) / len_a);
+        let (vx, vy) = ((bx - px) / len_b, (by - py) / len_b);
+        let half_angle = (ux * vx + uy * vy).clamp(-1.0, 1.0).acos() / 2.0;
+        // Sharp corners, spikes and straight angles are not rounded.
+        let straight = (std::f64::consts::FRAC_PI_2 - half_angle).abs() < 1e-6;
+        if radius <= 0.0 || half_angle.tan() < 1e-6 || straight {
+            ctx.line_to(px, py);
+            continue;
+        }
+        // Distance from the corner to where the arc meets the edges.
+        let tangent = (radius / half_angle.tan())
+            .min(len_a / 2.0)
+            .min(len_b / 2.0);
+        let r = tangent * half_angle.tan();
+        let (wx, wy) = (ux + vx, uy + vy);
+        let w_len = wx.hypot(wy);
+        let center_distance = r / half_angle.sin();
+        let (cx, cy) = (
+            px + wx / w_len * center_distance,
+            py + wy / w_len * center_distance,
+        );
+        let start = (py + uy * tangent - cy).atan2(px + ux * tangent - cx);
+        let end = (py + vy * tangent - cy).atan2(px + vx * tangent - cx);
+        // Take the short way around, which may be clockwise or counter-clockwise.
+        let sweep = (end - start + 3.0 * PI).rem_euclid(2.0 * PI) - PI;
+        if sweep >= 0.0 {
+            ctx.arc(cx, cy, r, start, start + sweep);
+        } else {
+            ctx.arc_negative(cx, cy, r, start, start + sweep);
+        }
+    }
+    ctx.close_path();
 }
 
 /// Draws a single key using Cairo.
@@ -38,6 +223,9 @@ pub fn draw_single_key_cairo(ctx: &Context, key: &KeyDisplay) {
 
     // Save the current Cairo context state to isolate transformations and style changes.
     ctx.save().expect("Failed to save cairo context state");
+    if let Some(family) = &key.font {
+        set_font_family(ctx, family);
+    }
 
     // --- Transformations ---
     // Translate to the key's center, rotate, then translate back by half width/height
@@ -46,44 +234,51 @@ pub fn draw_single_key_cairo(ctx: &Context, key: &KeyDisplay) {
     ctx.rotate(rotation_radians);
     ctx.translate(-width / 2.0, -height / 2.0); // Origin is now top-left of the key box
 
-    // --- Draw Key Shape (Rounded Rectangle) ---
-    // Start a new path for the rounded rectangle.
-    // The path is constructed by drawing arcs for the corners and lines for the sides.
-    // Arcs are drawn in clockwise order starting from the top-right corner.
-    ctx.new_sub_path();
-    // Top-right corner arc
-    ctx.arc(
-        width - corner_radius, // center_x of arc
-        corner_radius,         // center_y of arc
-        corner_radius,         // radius
-        -std::f64::consts::PI / 2.0, // start_angle (pointing upwards)
-        0.0,                         // end_angle (pointing rightwards)
-    );
-    // Bottom-right corner arc
-    ctx.arc(
-        width - corner_radius,
-        height - corner_radius,
-        corner_radius,
-        0.0, // start_angle (pointing rightwards)
-        std::f64::consts::PI / 2.0, // end_angle (pointing downwards)
-    );
-    // Bottom-left corner arc
-    ctx.arc(
-        corner_radius,
-        height - corner_radius,
-        corner_radius,
-        std::f64::consts::PI / 2.0, // start_angle (pointing downwards)
-        std::f64::consts::PI,       // end_angle (pointing leftwards)
-    );
-    // Top-left corner arc
-    ctx.arc(
-        corner_radius,
-        corner_radius,
-        corner_radius,
-        std::f64::consts::PI, // start_angle (pointing leftwards)
-        3.0 * std::f64::consts::PI / 2.0, // end_angle (pointing upwards)
-    );
-    ctx.close_path(); // Connects the last arc to the first line/arc, completing the shape.
+    // --- Draw Key Shape ---
+    if !key.outlines.is_empty() {
+        ctx.set_fill_rule(cairo::FillRule::EvenOdd);
+        for outline in &key.outlines {
+            rounded_polygon_path(ctx, outline, corner_radius);
+        }
+    } else {
+        // Start a new path for the rounded rectangle.
+        // The path is constructed by drawing arcs for the corners and lines for the sides.
+        // Arcs are drawn in clockwise order starting from the top-right corner.
+        ctx.new_sub_path();
+        // Top-right corner arc
+        ctx.arc(
+            width - corner_radius,       // center_x of arc
+            corner_radius,               // center_y of arc
+            corner_radius,               // radius
+            -std::f64::consts::PI / 2.0, // start_angle (pointing upwards)
+            0.0,                         // end_angle (pointing rightwards)
+        );
+        // Bottom-right corner arc
+        ctx.arc(
+            width - corner_radius,
+            height - corner_radius,
+            corner_radius,
+            0.0,                        // start_angle (pointing rightwards)
+            std::f64::consts::PI / 2.0, // end_angle (pointing downwards)
+        );
+        // Bottom-left corner arc
+        ctx.arc(
+            corner_radius,
+            height - corner_radius,
+            corner_radius,
+            std::f64::consts::PI / 2.0, // start_angle (pointing downwards)
+            std::f64::consts::PI,       // end_angle (pointing leftwards)
+        );
+        // Top-left corner arc
+        ctx.arc(
+            corner_radius,
+            corner_radius,
+            corner_radius,
+            std::f64::consts::PI,             // start_angle (pointing leftwards)
+            3.0 * std::f64::consts::PI / 2.0, // end_angle (pointing upwards)
+        );
+        ctx.close_path(); // Connects the last arc to the first line/arc, completing the shape.
+    }
 
     // --- Fill Background ---
     let (r, g, b, a) = key.background_color;
@@ -98,6 +293,12 @@ pub fn draw_single_key_cairo(ctx: &Context, key: &KeyDisplay) {
     ctx.stroke().expect("Cairo stroke failed");
 
     // --- Draw Text ---
+    if !key.legends.is_empty() {
+        draw_legends(ctx, key, width, height);
+        ctx.restore()
+            .expect("Failed to restore cairo context state");
+        return;
+    }
     let (r, g, b, a) = key.text_color;
     ctx.set_source_rgba(r, g, b, a);
 
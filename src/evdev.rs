@@ -0,0 +1,229 @@
+// src/evdev.rs
+
+//! This module implements an `InputSource` that reads raw evdev devices.
+//!
+//! Unlike libinput, this needs neither udev nor a seat: every
+//! `/dev/input/event*` node that reports keyboard keys is opened directly and
+//! its `struct input_event` records are read. All device fds are registered
+//! with an epoll instance whose fd is what `FdPoller` watches.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use crate::input_source::{InputSource, KeyEvent};
+
+/// `EV_KEY` event type from `linux/input-event-codes.h`.
+const EV_KEY: u16 = 1;
+/// Codes at or above `BTN_MISC` are buttons (mouse, joystick, ...), not keyboard keys.
+const BTN_MISC: usize = 0x100;
+/// Size of the `EV_KEY` capability bitmap requested from the kernel (`KEY_MAX` is 0x2ff).
+const KEY_BITMAP_BYTES: usize = 0x300 / 8;
+
+/// Builds the `EVIOCGBIT(ev, len)` ioctl request number.
+fn eviocgbit(ev: u32, len: usize) -> libc::c_ulong {
+    const IOC_READ: libc::c_ulong = 2;
+    (IOC_READ << 30)
+        | ((len as libc::c_ulong) << 16)
+        | ((b'E' as libc::c_ulong) << 8)
+        | (0x20 + ev as libc::c_ulong)
+}
+
+/// Returns `true` if the device reports any keyboard key (as opposed to only buttons).
+fn is_keyboard(file: &File) -> bool {
+    let mut bits = [0u8; KEY_BITMAP_BYTES];
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            eviocgbit(EV_KEY as u32, bits.len()),
+            bits.as_mut_ptr(),
+        )
+    };
+    if ret < 0 {
+        return false;
+    }
+    (1..BTN_MISC).any(|code| bits[code / 8] & (1 << (code % 8)) != 0)
+}
+
+struct EvdevDevice {
+    path: PathBuf,
+    file: File,
+}
+
+/// Key events read directly from `/dev/input/event*` keyboard devices.
+pub struct EvdevSource {
+    devices: Vec<Option<EvdevDevice>>,
+    epoll_fd: OwnedFd,
+}
+
+impl EvdevSource {
+    /// Opens all keyboard devices in `/dev/input`.
+    ///
+    /// Returns `Err` if no keyboard device could be opened, which usually
+    /// means the user lacks permission to read input devices.
+    pub fn new() -> Result<Self, String> {
+        Self::from_dir(Path::new("/dev/input"))
+    }
+
+    fn from_dir(dir: &Path) -> Result<Self, String> {
+        let raw_fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if raw_fd < 0 {
+            return Err(format!(
+                "epoll_create1 failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: raw_fd was just returned by epoll_create1 and is owned by nobody else.
+        let epoll_fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map_err(|e| format!("Failed to list {:?}: {}", dir, e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("event"))
+            })
+            .collect();
+        paths.sort();
+
+        let mut devices = Vec::new();
+        for path in paths {
+            let file = match OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+                .open(&path)
+            {
+                Ok(f) => f,
+                Err(e) => {
+                    log::debug!("Skipping {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            if !is_keyboard(&file) {
+                log::trace!("Skipping {:?}: not a keyboard", path);
+                continue;
+            }
+
+            let mut ev = libc::epoll_event {
+                events: libc::EPOLLIN as u32,
+                u64: devices.len() as u64,
+            };
+            let ret = unsafe {
+                libc::epoll_ctl(
+                    epoll_fd.as_raw_fd(),
+                    libc::EPOLL_CTL_ADD,
+                    file.as_raw_fd(),
+                    &mut ev,
+                )
+            };
+            if ret < 0 {
+                log::warn!(
+                    "Failed to watch {:?}: {}",
+                    path,
+                    std::io::Error::last_os_error()
+                );
+                continue;
+            }
+            log::info!("Reading key events from evdev device {:?}", path);
+            devices.push(Some(EvdevDevice { path, file }));
+        }
+
+        if devices.is_empty() {
+            return Err(format!(
+                "No readable keyboard devices found in {:?}. Check user permissions (e.g., 'input' group).",
+                dir
+            ));
+        }
+        Ok(EvdevSource { devices, epoll_fd })
+    }
+
+    /// Reads all pending `input_event`s from one device, appending key events to `out`.
+    ///
+    /// Returns `false` if the device is gone and should be dropped.
+    fn drain_device(device: &mut EvdevDevice, out: &mut Vec<KeyEvent>) -> bool {
+        let event_size = std::mem::size_of::<libc::input_event>();
+        let mut buf = vec![0u8; event_size * 64];
+        loop {
+            match device.file.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    for chunk in buf[..n].chunks_exact(event_size) {
+                        // SAFETY: chunk is exactly size_of::<input_event>() bytes as written by the kernel.
+                        let ev: libc::input_event = unsafe {
+                            std::ptr::read_unaligned(chunk.as_ptr() as *const libc::input_event)
+                        };
+                        // value: 0 = release, 1 = press, 2 = autorepeat (ignored)
+                        if ev.type_ == EV_KEY && (ev.value == 0 || ev.value == 1) {
+                            out.push(KeyEvent {
+                                keycode: ev.code as u32,
+                                pressed: ev.value == 1,
+                            });
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!(
+                        "Evdev device {:?} is no longer readable: {}",
+                        device.path,
+                        e
+                    );
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+impl AsRawFd for EvdevSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll_fd.as_raw_fd()
+    }
+}
+
+impl InputSource for EvdevSource {
+    fn name(&self) -> &'static str {
+        "evdev"
+    }
+
+    fn read_events(&mut self) -> Result<Vec<KeyEvent>, String> {
+        let mut ready = [libc::epoll_event { events: 0, u64: 0 }; 16];
+        let count = unsafe {
+            libc::epoll_wait(
+                self.epoll_fd.as_raw_fd(),
+                ready.as_mut_ptr(),
+                ready.len() as i32,
+                0,
+            )
+        };
+        if count < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(format!("epoll_wait on evdev devices failed: {}", err));
+        }
+
+        let mut events = Vec::new();
+        for ev in &ready[..count as usize] {
+            let index = ev.u64 as usize;
+            let keep = match self.devices.get_mut(index).and_then(|d| d.as_mut()) {
+                Some(device) => Self::drain_device(device, &mut events),
+                None => continue,
+            };
+            if !keep {
+                // Closing the file removes it from the epoll set.
+                self.devices[index] = None;
+            }
+        }
+
+        if self.devices.iter().all(|d| d.is_none()) {
+            return Err("All evdev keyboard devices were removed".to_string());
+        }
+        Ok(events)
+    }
+}
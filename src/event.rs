@@ -1,111 +1,465 @@
 // Input event handling
 
-use input::event::keyboard::{KeyState, KeyboardEvent, KeyboardEventTrait};
-use input::event::Event as LibinputEvent;
-use libc::{O_NONBLOCK, O_RDWR};
-use std::fs::OpenOptions;
-use std::os::unix::fs::OpenOptionsExt;
-use std::os::unix::io::{AsRawFd, OwnedFd}; // Added AsRawFd
-use std::path::Path;
-
 // Assuming AppState is defined in wayland.rs and passed here
+use crate::check::validate_config;
+use crate::config::{process_config_str, read_config_file, LayerConfig};
 use crate::wayland::AppState;
 
-pub struct MyLibinputInterface;
-
-impl input::LibinputInterface for MyLibinputInterface {
-    fn open_restricted(&mut self, path: &Path, _flags: i32) -> Result<OwnedFd, i32> {
-        // flags are ignored because O_RDWR | O_NONBLOCK are always used.
-        log::debug!("Attempting to open input device: {:?}", path);
-        OpenOptions::new()
-            .custom_flags(O_RDWR | O_NONBLOCK) // Explicitly use these flags
-            .read(true) // Required by libinput
-            .write(true) // Required by libinput for some operations like LED changes (though not used by this app)
-            .open(path)
-            .map(|file| file.into())
-            .map_err(|e| {
-                let errno = e.raw_os_error().unwrap_or(libc::EIO);
-                match errno {
-                    libc::EPERM => log::error!(
-                        "Permission denied when opening {:?}. Check user permissions (e.g., 'input' group). Error: {}",
-                        path, e
-                    ),
-                    libc::ENOENT => log::error!(
-                        "Device {:?} not found. It might have been unplugged. Error: {}",
-                        path, e
-                    ),
-                    libc::EACCES => log::error!( // EACCES can also mean permission issues
-                        "Access denied when opening {:?}. Similar to EPERM, check permissions. Error: {}",
-                        path, e
-                    ),
-                    _ => log::error!(
-                        "Failed to open input device {:?} with flags O_RDWR | O_NONBLOCK. Error: {} (errno: {})",
-                        path, e, errno
-                    ),
-                }
-                errno // Return the original errno
-            })
+/// A modifier key group. Left and right keys count as the same modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Super,
+}
+
+impl Modifier {
+    /// Order in which modifiers are listed in chord labels.
+    pub const DISPLAY_ORDER: [Modifier; 4] = [
+        Modifier::Ctrl,
+        Modifier::Alt,
+        Modifier::Shift,
+        Modifier::Super,
+    ];
+
+    /// Returns the modifier a keycode belongs to, if it is a modifier key.
+    pub fn from_keycode(keycode: u32) -> Option<Self> {
+        match keycode {
+            29 | 97 => Some(Modifier::Ctrl),    // leftctrl, rightctrl
+            56 | 100 => Some(Modifier::Alt),    // leftalt, rightalt
+            42 | 54 => Some(Modifier::Shift),   // leftshift, rightshift
+            125 | 126 => Some(Modifier::Super), // leftmeta, rightmeta
+            _ => None,
+        }
     }
 
-    fn close_restricted(&mut self, fd: OwnedFd) {
-        // OwnedFd handles closing the file descriptor when it's dropped.
-        // We can log the action if desired.
-        log::debug!("Closing input device (FD: {}) via OwnedFd drop.", fd.as_raw_fd());
-        drop(fd);
+    pub fn label(self) -> &'static str {
+        match self {
+            Modifier::Ctrl => "Ctrl",
+            Modifier::Alt => "Alt",
+            Modifier::Shift => "Shift",
+            Modifier::Super => "Super",
+        }
     }
 }
 
-pub fn handle_libinput_events(app_state: &mut AppState) {
-    if let Some(ref mut context) = app_state.input_context {
-        if let Err(e) = context.dispatch() {
-            // An error from dispatch() typically means libinput is no longer usable.
-            // This might happen if the underlying udev resources are gone.
-            log::error!("Libinput dispatch error: {}. Input monitoring may stop.", e);
-            // Consider setting a flag to stop trying to use libinput or reinitialize.
-            // For now, we'll log and continue; FdPoller might detect FD errors later.
-            return;
+/// Tracks held modifier keys and the chord they form.
+///
+/// The chord is the held modifiers plus the last other key pressed while
+/// they were held, e.g. "Ctrl+Alt+Del". It is cleared once all modifiers
+/// are released.
+#[derive(Debug, Default, Clone)]
+pub struct ModifierState {
+    held_keycodes: Vec<u32>,
+    chord_key: Option<String>,
+}
+
+impl ModifierState {
+    /// Applies a key event. `key_label` names the key if it becomes part of the chord.
+    ///
+    /// Returns `true` if the chord label changed.
+    pub fn handle_key(&mut self, keycode: u32, pressed: bool, key_label: &str) -> bool {
+        let before = self.chord_label();
+        if Modifier::from_keycode(keycode).is_some() {
+            if pressed {
+                if !self.held_keycodes.contains(&keycode) {
+                    self.held_keycodes.push(keycode);
+                }
+            } else {
+                self.held_keycodes.retain(|&code| code != keycode);
+                if self.held_keycodes.is_empty() {
+                    self.chord_key = None;
+                }
+            }
+        } else if pressed && !self.held_keycodes.is_empty() {
+            self.chord_key = Some(key_label.to_string());
+        }
+        self.chord_label() != before
+    }
+
+    /// Returns `true` if any key of the given modifier group is held.
+    pub fn is_held(&self, modifier: Modifier) -> bool {
+        self.held_keycodes
+            .iter()
+            .any(|&code| Modifier::from_keycode(code) == Some(modifier))
+    }
+
+    /// Returns the held modifiers in display order.
+    pub fn held(&self) -> Vec<Modifier> {
+        Modifier::DISPLAY_ORDER
+            .into_iter()
+            .filter(|&m| self.is_held(m))
+            .collect()
+    }
+
+    /// Formats the held modifiers followed by `key`, e.g. "Ctrl+Shift+T".
+    pub fn format_combo(&self, key: &str) -> String {
+        let mut parts: Vec<&str> = self.held().into_iter().map(Modifier::label).collect();
+        parts.push(key);
+        parts.join("+")
+    }
+
+    /// Returns the active chord, or `None` if no modifier is held.
+    pub fn chord_label(&self) -> Option<String> {
+        if self.held_keycodes.is_empty() {
+            return None;
         }
+        Some(match &self.chord_key {
+            Some(key) => self.format_combo(key),
+            None => self
+                .held()
+                .into_iter()
+                .map(Modifier::label)
+                .collect::<Vec<_>>()
+                .join("+"),
+        })
+    }
+}
+
+/// Tracks which layers are active from their momentary and toggle keys.
+///
+/// Layers are identified by their index in `AppConfig::layer`, so the state
+/// must be reset when the layers change.
+#[derive(Debug, Default, Clone)]
+pub struct LayerState {
+    held: Vec<usize>,
+    toggled: Vec<usize>,
+}
 
-        for event in context.by_ref() {
-            if let LibinputEvent::Keyboard(KeyboardEvent::Key(key_event)) = event {
-                let key_code = key_event.key(); // This is the raw scancode from libinput
-                let key_state = key_event.key_state();
-                let pressed = key_state == KeyState::Pressed;
-
-                // Attempt to find the key name from our config for logging
-                // This is a linear search, might be slow if there are many keys.
-                // For frequent logging, consider a reverse map if performance becomes an issue.
-                let key_name_for_log: String = app_state
-                    .config
-                    .key
-                    .iter()
-                    .find(|k| k.keycode == key_code)
-                    .map(|k| k.name.clone())
-                    .unwrap_or_else(|| "Unknown".to_string());
-
-                if let Some(current_state) = app_state.key_states.get_mut(&key_code) {
-                    if *current_state != pressed {
-                        *current_state = pressed;
-                        app_state.needs_redraw = true;
-                        log::debug!(
-                            "Key Event: Code {}, Name '{}', State: {:?} -> {}",
-                            key_code,
-                            key_name_for_log,
-                            key_state,
-                            if pressed { "Pressed" } else { "Released" }
-                        );
-                    }
+impl LayerState {
+    /// Applies a key event to the layers whose momentary or toggle key it is.
+    ///
+    /// Returns `true` if the active layer changed.
+    pub fn handle_key(&mut self, layers: &[LayerConfig], keycode: u32, pressed: bool) -> bool {
+        let before = self.active_layer();
+        for (index, layer) in layers.iter().enumerate() {
+            if layer.momentary_keycode == Some(keycode) {
+                self.held.retain(|&i| i != index);
+                if pressed {
+                    self.held.push(index);
+                }
+            }
+            if layer.toggle_keycode == Some(keycode) && pressed {
+                if self.toggled.contains(&index) {
+                    self.toggled.retain(|&i| i != index);
                 } else {
-                    // This case should ideally not happen if key_states is populated correctly
-                    // from all keys defined in the config.
-                    log::warn!(
-                        "Key Event for unmonitored key: Code {}, Name '{}', State: {:?}",
-                        key_code,
-                        key_name_for_log,
-                        key_state
-                    );
+                    self.toggled.push(index);
                 }
             }
         }
+        self.active_layer() != before
+    }
+
+    /// Returns the index of the active layer, or `None` for the base layout.
+    ///
+    /// If several layers are active, the one defined last wins.
+    pub fn active_layer(&self) -> Option<usize> {
+        self.held.iter().chain(self.toggled.iter()).copied().max()
+    }
+}
+
+/// Reads pending events from the active input source and applies them.
+///
+/// If the source reports an error it is dropped and `false` is returned, so
+/// the caller can rebuild its `FdPoller` without the stale fd.
+pub fn handle_input_events(app_state: &mut AppState) -> bool {
+    let result = match app_state.input_source.as_mut() {
+        Some(source) => source.read_events().map_err(|e| (source.name(), e)),
+        None => return true,
+    };
+
+    match result {
+        Ok(key_events) => {
+            for key_event in key_events {
+                process_key_event(app_state, key_event.keycode, key_event.pressed);
+            }
+            true
+        }
+        Err((name, e)) => {
+            log::error!("Input source '{}' failed: {}. Key press/release events will no longer be monitored.", name, e);
+            app_state.input_source = None;
+            false
+        }
+    }
+}
+
+/// Reloads the configuration if the config watcher reports a change to the file.
+///
+/// The new configuration is loaded and validated like at startup. If that
+/// fails, the error is logged and the previous configuration stays active.
+pub fn handle_config_change(app_state: &mut AppState) {
+    let Some(watcher) = app_state.config_watcher.as_mut() else {
+        return;
+    };
+    if !watcher.take_changed() {
+        return;
+    }
+    let path = watcher.path().to_string_lossy().into_owned();
+
+    match load_and_apply_config(app_state, &path) {
+        Ok(()) => log::info!(
+            "Configuration file '{}' changed, reloaded {} keys.",
+            path,
+            app_state.config.key.len()
+        ),
+        Err(e) => {
+            log::error!(
+                "Failed to reload configuration from '{}': {}. Keeping the previous configuration.",
+                path,
+                e
+            );
+        }
+    }
+}
+
+/// Loads and validates the configuration at `path` and makes it the active one.
+///
+/// On error the current configuration is left untouched.
+pub fn load_and_apply_config(app_state: &mut AppState, path: &str) -> Result<(), String> {
+    let source = read_config_file(path)?;
+    let new_config = process_config_str(&source, path, app_state.theme.as_deref())?;
+    let diagnostics = validate_config(&new_config);
+    if diagnostics.has_errors() {
+        return Err(format!(
+            "Invalid configuration:\n{}",
+            diagnostics.render(path, Some(&source))
+        ));
+    }
+    if !diagnostics.is_empty() {
+        log::warn!(
+            "Configuration '{}' has warnings:\n{}",
+            path,
+            diagnostics.render(path, Some(&source))
+        );
+    }
+    app_state.apply_config(new_config);
+    app_state.config_path = Some(path.to_string());
+    Ok(())
+}
+
+/// Applies a single key press or release to `AppState::key_states`.
+///
+/// Shared by all input sources. Every event is published on the key event
+/// stream; the state is marked for redraw only if the key's pressed state,
+/// the active layer, the modifier chord or the strip actually changed.
+pub fn process_key_event(app_state: &mut AppState, key_code: u32, pressed: bool) {
+    if app_state
+        .layers
+        .handle_key(&app_state.config.layer, key_code, pressed)
+    {
+        app_state.needs_redraw = true;
+        log::debug!("Active layer: {:?}", app_state.active_layer_name());
+    }
+
+    // Attempt to find the key name on the active layer for logging
+    // This is a linear search, might be slow if there are many keys.
+    // For frequent logging, consider a reverse map if performance becomes an issue.
+    let key_name: Option<&str> = app_state
+        .config
+        .keys_for_layer(app_state.layers.active_layer())
+        .iter()
+        .find(|k| k.keycode == key_code)
+        .map(|k| k.name.as_str());
+    app_state.event_stream.emit(key_code, key_name, pressed);
+
+    let chord_key_label = key_name
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("#{}", key_code));
+    if app_state
+        .modifiers
+        .handle_key(key_code, pressed, &chord_key_label)
+    {
+        app_state.needs_redraw = true;
+    }
+    if let Some(strip) = app_state.strip.as_mut() {
+        if strip.handle_key(
+            key_code,
+            pressed,
+            &app_state.modifiers,
+            key_name,
+            std::time::Instant::now(),
+        ) {
+            app_state.needs_redraw = true;
+        }
+    }
+
+    let key_name_for_log = key_name.unwrap_or("Unknown").to_string();
+    let state_str = if pressed { "Pressed" } else { "Released" };
+
+    if let Some(current_state) = app_state.key_states.get_mut(&key_code) {
+        if *current_state != pressed {
+            *current_state = pressed;
+            app_state.needs_redraw = true;
+            log::debug!(
+                "Key Event: Code {}, Name '{}', State: {}",
+                key_code,
+                key_name_for_log,
+                state_str
+            );
+        }
+    } else if app_state.strip.is_none() {
+        // This case should ideally not happen if key_states is populated correctly
+        // from all keys defined in the config. The strip needs no [[key]] entries.
+        log::warn!(
+            "Key Event for unmonitored key: Code {}, Name '{}', State: {}",
+            key_code,
+            key_name_for_log,
+            state_str
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_watch::ConfigWatcher;
+    use crate::input_source::SyntheticSource;
+
+    fn test_state() -> AppState {
+        let config = process_config_str(
+            r#"
+            [[key]]
+            name = "A"
+            left = 0.0
+            top = 0.0
+            width = 50.0
+            height = 50.0
+            "#,
+            "test",
+            None,
+        )
+        .unwrap();
+        AppState::new(config, true, (0.0, 0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn test_synthetic_source_updates_key_states() {
+        let mut app_state = test_state();
+        let mut source = SyntheticSource::new();
+        source.push(30, true);
+        source.push(48, true); // Not in the config, only logged
+        app_state.input_source = Some(Box::new(source));
+        app_state.needs_redraw = false;
+
+        assert!(handle_input_events(&mut app_state));
+        assert_eq!(app_state.key_states.get(&30), Some(&true));
+        assert!(!app_state.key_states.contains_key(&48));
+        assert!(app_state.needs_redraw);
+    }
+
+    #[test]
+    fn test_repeated_state_does_not_request_redraw() {
+        let mut app_state = test_state();
+        process_key_event(&mut app_state, 30, true);
+        app_state.needs_redraw = false;
+        process_key_event(&mut app_state, 30, true);
+        assert!(!app_state.needs_redraw);
+        process_key_event(&mut app_state, 30, false);
+        assert!(app_state.needs_redraw);
+        assert_eq!(app_state.key_states.get(&30), Some(&false));
+    }
+
+    #[test]
+    fn test_config_change_reloads_or_keeps_previous_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.toml");
+        let key = |name: &str, left: f32| {
+            format!(
+                "[[key]]\nname = \"{}\"\nleft = {}\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n",
+                name, left
+            )
+        };
+        std::fs::write(&path, key("A", 0.0)).unwrap();
+
+        let mut app_state = test_state();
+        app_state.config_watcher = Some(ConfigWatcher::new(path.to_str().unwrap()).unwrap());
+        process_key_event(&mut app_state, 30, true);
+
+        std::fs::write(&path, key("A", 0.0) + &key("B", 55.0)).unwrap();
+        handle_config_change(&mut app_state);
+        assert_eq!(app_state.config.key.len(), 2);
+        assert_eq!(app_state.key_states.get(&30), Some(&true)); // Held key stays pressed
+        assert_eq!(app_state.key_states.get(&48), Some(&false));
+
+        // Overlapping keys fail validation; the previous config is kept.
+        std::fs::write(&path, key("A", 0.0) + &key("B", 10.0)).unwrap();
+        handle_config_change(&mut app_state);
+        assert_eq!(app_state.config.key[1].left, 55.0);
+    }
+
+    #[test]
+    fn test_modifier_chord() {
+        let mut mods = ModifierState::default();
+        assert!(mods.handle_key(97, true, "RCtrl")); // rightctrl
+        assert!(mods.handle_key(56, true, "LAlt"));
+        assert_eq!(mods.chord_label().as_deref(), Some("Ctrl+Alt"));
+        assert!(mods.handle_key(111, true, "Del"));
+        assert!(!mods.handle_key(111, false, "Del"));
+        assert_eq!(mods.chord_label().as_deref(), Some("Ctrl+Alt+Del"));
+
+        // Releasing one modifier keeps the chord key; releasing all clears the chord.
+        assert!(mods.handle_key(56, false, "LAlt"));
+        assert_eq!(mods.chord_label().as_deref(), Some("Ctrl+Del"));
+        assert!(mods.handle_key(97, false, "RCtrl"));
+        assert_eq!(mods.chord_label(), None);
+
+        // Keys pressed without modifiers do not form a chord.
+        assert!(!mods.handle_key(30, true, "A"));
+    }
+
+    #[test]
+    fn test_layer_switching() {
+        let config = crate::config::process_config_str(
+            r#"
+            [[key]]
+            name = "Q"
+            left = 0.0
+            top = 0.0
+            width = 50.0
+            height = 50.0
+
+            [[layer]]
+            name = "Lower"
+            momentary = "f13"
+            [[layer.key]]
+            key = "q"
+            name = "1"
+            keycode = "1"
+
+            [[layer]]
+            name = "Raise"
+            toggle = "f14"
+            "#,
+            "test",
+            None,
+        )
+        .unwrap();
+        let mut app_state = AppState::new(config, true, (0.0, 0.0, 0.0, 1.0));
+        assert_eq!(app_state.key_states.get(&2), Some(&false)); // Layer keycodes are monitored
+
+        process_key_event(&mut app_state, 183, true); // f13
+        assert_eq!(app_state.active_layer_name(), Some("Lower"));
+        process_key_event(&mut app_state, 2, true);
+        assert_eq!(app_state.key_states.get(&2), Some(&true));
+
+        process_key_event(&mut app_state, 184, true); // f14: the later layer wins
+        assert_eq!(app_state.active_layer_name(), Some("Raise"));
+        process_key_event(&mut app_state, 184, false);
+        assert_eq!(app_state.active_layer_name(), Some("Raise"));
+        process_key_event(&mut app_state, 184, true);
+        assert_eq!(app_state.active_layer_name(), Some("Lower"));
+        process_key_event(&mut app_state, 183, false);
+        assert_eq!(app_state.active_layer_name(), None);
+    }
+
+    #[test]
+    fn test_chord_change_requests_redraw() {
+        let mut app_state = test_state();
+        app_state.needs_redraw = false;
+        process_key_event(&mut app_state, 29, true); // Not in the layout, but still a modifier
+        assert!(app_state.needs_redraw);
+        process_key_event(&mut app_state, 30, true);
+        assert_eq!(app_state.modifiers.chord_label().as_deref(), Some("Ctrl+A"));
     }
 }
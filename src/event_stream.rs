@@ -0,0 +1,156 @@
+// src/event_stream.rs
+
+//! This module publishes processed key events as a JSON-lines stream.
+//!
+//! Every key press/release that goes through `event::process_key_event` is
+//! written as one JSON object per line, e.g.
+//! `{"keycode":30,"name":"A","state":"pressed","timestamp_ms":1718000000000}`,
+//! to stdout (with `--emit-events`) and to IPC clients that sent the
+//! `subscribe` command. Writes never block the main loop: a subscriber that
+//! cannot keep up or has disconnected is dropped.
+
+use serde::Serialize;
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::net::UnixStream;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One key event as written to the stream.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct KeyEventRecord<'a> {
+    /// Linux keycode (e.g., 30 for `KEY_A`).
+    pub keycode: u32,
+    /// Name of the key in the configuration, `null` for keys not in the layout.
+    pub name: Option<&'a str>,
+    /// `"pressed"` or `"released"`.
+    pub state: &'static str,
+    /// Milliseconds since the Unix epoch at which the event was processed.
+    pub timestamp_ms: u64,
+}
+
+/// The destinations key events are written to.
+#[derive(Default)]
+pub struct EventStream {
+    emit_stdout: bool,
+    subscribers: Vec<UnixStream>,
+}
+
+impl EventStream {
+    /// Creates a stream that writes to stdout if `emit_stdout` is set.
+    pub fn new(emit_stdout: bool) -> Self {
+        EventStream {
+            emit_stdout,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if there is anyone to write events to.
+    pub fn is_active(&self) -> bool {
+        self.emit_stdout || !self.subscribers.is_empty()
+    }
+
+    /// Adds an IPC client that receives all following events.
+    pub fn add_subscriber(&mut self, stream: UnixStream) -> io::Result<()> {
+        stream.set_nonblocking(true)?;
+        self.subscribers.push(stream);
+        log::info!(
+            "Key event subscriber added ({} total).",
+            self.subscribers.len()
+        );
+        Ok(())
+    }
+
+    /// Writes a key event to stdout and all subscribers.
+    pub fn emit(&mut self, keycode: u32, name: Option<&str>, pressed: bool) {
+        if !self.is_active() {
+            return;
+        }
+        let record = KeyEventRecord {
+            keycode,
+            name,
+            state: if pressed { "pressed" } else { "released" },
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        let mut line = match serde_json::to_string(&record) {
+            Ok(json) => json,
+            Err(e) => {
+                log::error!("Failed to serialize key event: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        if self.emit_stdout {
+            let mut stdout = io::stdout().lock();
+            if let Err(e) = stdout
+                .write_all(line.as_bytes())
+                .and_then(|_| stdout.flush())
+            {
+                log::warn!(
+                    "Failed to write key event to stdout: {}. Disabling --emit-events.",
+                    e
+                );
+                self.emit_stdout = false;
+            }
+        }
+
+        self.subscribers
+            .retain_mut(|subscriber| match subscriber.write(line.as_bytes()) {
+                Ok(n) if n == line.len() => true,
+                // A partial line would corrupt the stream, so treat it like a full buffer.
+                Ok(_) => {
+                    log::warn!("Key event subscriber is not keeping up; disconnecting it.");
+                    false
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    log::warn!("Key event subscriber is not keeping up; disconnecting it.");
+                    false
+                }
+                Err(e) => {
+                    log::info!("Key event subscriber disconnected: {}", e);
+                    false
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_subscribers_receive_json_lines() {
+        let mut stream = EventStream::new(false);
+        assert!(!stream.is_active());
+
+        let (server_end, client_end) = UnixStream::pair().unwrap();
+        stream.add_subscriber(server_end).unwrap();
+        stream.emit(30, Some("A"), true);
+        stream.emit(99, None, false);
+
+        let mut lines = BufReader::new(client_end).lines();
+        let first: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(first["keycode"], 30);
+        assert_eq!(first["name"], "A");
+        assert_eq!(first["state"], "pressed");
+        assert!(first["timestamp_ms"].as_u64().unwrap() > 0);
+        let second: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(second["name"], serde_json::Value::Null);
+        assert_eq!(second["state"], "released");
+    }
+
+    #[test]
+    fn test_disconnected_subscriber_is_dropped() {
+        let mut stream = EventStream::new(false);
+        let (server_end, client_end) = UnixStream::pair().unwrap();
+        stream.add_subscriber(server_end).unwrap();
+        drop(client_end);
+        stream.emit(30, Some("A"), true);
+        assert!(!stream.is_active());
+    }
+}
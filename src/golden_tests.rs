@@ -0,0 +1,325 @@
+// src/golden_tests.rs
+
+//! Golden-image regression tests for the renderer.
+//!
+//! Fixture configs in `tests/golden/` are rendered offscreen through
+//! `draw::paint_all_keys` (via `render::render_to_surface`) and
+//! `draw::draw_single_key_cairo`, and compared against the reference PNGs
+//! checked in next to them.
+//!
+//! Small differences from anti-aliasing and font rasterization across
+//! FreeType/Cairo versions are tolerated: a channel may differ by up to
+//! `CHANNEL_TOLERANCE`, and up to `MAX_MISMATCHED_FRACTION` of the pixels may
+//! exceed that. On failure a diff image is written to `target/golden-diffs/`.
+//!
+//! To (re)generate the reference images after an intended rendering change,
+//! run the tests with `UPDATE_GOLDEN=1` and review the new PNGs.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use cairo::{Context, Format, ImageSurface};
+
+use crate::config::load_and_process_config;
+use crate::draw::{self, KeyDisplay};
+use crate::render;
+use crate::strip;
+
+/// Maximum difference per color/alpha channel for a pixel to count as matching.
+const CHANNEL_TOLERANCE: u8 = 24;
+/// Fraction of pixels that may exceed `CHANNEL_TOLERANCE` before a comparison fails.
+const MAX_MISMATCHED_FRACTION: f64 = 0.002;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn diff_dir() -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("golden-diffs")
+}
+
+/// Returns the pixels of `surface` as ARGB32 words, converting other formats first.
+fn argb_pixels(surface: &ImageSurface) -> Vec<u32> {
+    let (width, height) = (surface.width(), surface.height());
+    let converted =
+        ImageSurface::create(Format::ARgb32, width, height).expect("Failed to create surface");
+    {
+        let ctx = Context::new(&converted).expect("Failed to create context");
+        ctx.set_source_surface(surface, 0.0, 0.0).unwrap();
+        ctx.set_operator(cairo::Operator::Source);
+        ctx.paint().unwrap();
+    }
+    converted.flush();
+
+    let stride = converted.stride() as usize;
+    let data = converted
+        .take_data()
+        .expect("Surface data is still borrowed");
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as usize {
+        let row = &data[y * stride..y * stride + width as usize * 4];
+        pixels.extend(
+            row.chunks_exact(4)
+                .map(|p| u32::from_ne_bytes([p[0], p[1], p[2], p[3]])),
+        );
+    }
+    pixels
+}
+
+fn pixel_matches(a: u32, b: u32) -> bool {
+    a.to_be_bytes()
+        .iter()
+        .zip(b.to_be_bytes().iter())
+        .all(|(x, y)| x.abs_diff(*y) <= CHANNEL_TOLERANCE)
+}
+
+/// Writes an image highlighting mismatched pixels in red over a faded copy of the reference.
+fn write_diff_image(
+    name: &str,
+    width: i32,
+    height: i32,
+    actual: &[u32],
+    expected: &[u32],
+) -> PathBuf {
+    let mut diff =
+        ImageSurface::create(Format::ARgb32, width, height).expect("Failed to create diff surface");
+    let stride = diff.stride() as usize;
+    {
+        let mut data = diff.data().expect("Failed to access diff surface data");
+        for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+            let (x, y) = (i % width as usize, i / width as usize);
+            let pixel: u32 = if pixel_matches(*a, *e) {
+                // Luminance of the reference, faded towards white.
+                let [_, r, g, b] = e.to_be_bytes();
+                let luma = (r as u32 * 3 + g as u32 * 6 + b as u32) / 10;
+                let faded = 192 + luma / 4;
+                0xFF00_0000 | (faded << 16) | (faded << 8) | faded
+            } else {
+                0xFFFF_0000
+            };
+            let offset = y * stride + x * 4;
+            data[offset..offset + 4].copy_from_slice(&pixel.to_ne_bytes());
+        }
+    }
+
+    let dir = diff_dir();
+    fs::create_dir_all(&dir).expect("Failed to create diff directory");
+    let write_png = |surface: &ImageSurface, path: &Path| {
+        let mut file = File::create(path).expect("Failed to create diff file");
+        surface
+            .write_to_png(&mut file)
+            .expect("Failed to write diff PNG");
+    };
+    let diff_path = dir.join(format!("{}.diff.png", name));
+    write_png(&diff, &diff_path);
+    diff_path
+}
+
+/// Compares `actual` against `tests/golden/<name>.png`, or writes it there when
+/// `UPDATE_GOLDEN` is set.
+fn assert_matches_golden(name: &str, actual: &ImageSurface) {
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let mut file = File::create(&reference_path).expect("Failed to create reference PNG");
+        actual
+            .write_to_png(&mut file)
+            .expect("Failed to write reference PNG");
+        eprintln!("Updated golden image {:?}", reference_path);
+        return;
+    }
+
+    let expected = match File::open(&reference_path) {
+        Ok(mut file) => {
+            ImageSurface::create_from_png(&mut file).expect("Failed to decode reference PNG")
+        }
+        Err(e) => panic!(
+            "Missing golden image {:?} ({}). Run the tests with UPDATE_GOLDEN=1 to create it.",
+            reference_path, e
+        ),
+    };
+
+    assert_eq!(
+        (actual.width(), actual.height()),
+        (expected.width(), expected.height()),
+        "Size of rendered image '{}' differs from the reference",
+        name
+    );
+
+    let actual_pixels = argb_pixels(actual);
+    let expected_pixels = argb_pixels(&expected);
+    let mismatched = actual_pixels
+        .iter()
+        .zip(expected_pixels.iter())
+        .filter(|(a, e)| !pixel_matches(**a, **e))
+        .count();
+    let allowed = (actual_pixels.len() as f64 * MAX_MISMATCHED_FRACTION) as usize;
+
+    if mismatched > allowed {
+        let actual_path = diff_dir().join(format!("{}.actual.png", name));
+        let diff_path = write_diff_image(
+            name,
+            actual.width(),
+            actual.height(),
+            &actual_pixels,
+            &expected_pixels,
+        );
+        let mut file = File::create(&actual_path).expect("Failed to create actual PNG");
+        actual
+            .write_to_png(&mut file)
+            .expect("Failed to write actual PNG");
+        panic!(
+            "Rendered image '{}' differs from {:?}: {} pixels mismatched (allowed {}).\n  actual: {:?}\n  diff:   {:?}",
+            name, reference_path, mismatched, allowed, actual_path, diff_path
+        );
+    }
+}
+
+fn render_fixture(config_name: &str, pressed: &[&str], width: u32, height: u32) -> ImageSurface {
+    let path = golden_dir().join(config_name);
+    let config = load_and_process_config(path.to_str().unwrap(), None).unwrap();
+    let pressed: Vec<String> = pressed.iter().map(|s| s.to_string()).collect();
+    let key_states = render::resolve_pressed_keys(&config, &pressed).unwrap();
+    render::render_to_surface(&config, &key_states, width, height).unwrap()
+}
+
+#[test]
+fn golden_basic_idle() {
+    assert_matches_golden("basic-idle", &render_fixture("basic.toml", &[], 320, 200));
+}
+
+#[test]
+fn golden_basic_pressed() {
+    assert_matches_golden(
+        "basic-pressed",
+        &render_fixture("basic.toml", &["leftshift", "a"], 320, 200),
+    );
+}
+
+#[test]
+fn golden_styled() {
+    assert_matches_golden(
+        "styled",
+        &render_fixture("styled.toml", &["space"], 360, 200),
+    );
+}
+
+#[test]
+fn golden_key_colors() {
+    assert_matches_golden(
+        "colors",
+        &render_fixture("colors.toml", &["leftshift", "j", "home"], 320, 200),
+    );
+}
+
+#[test]
+fn golden_styles() {
+    assert_matches_golden(
+        "styles",
+        &render_fixture("styles.toml", &["leftctrl", "j"], 320, 240),
+    );
+}
+
+#[test]
+fn golden_theme_file() {
+    assert_matches_golden("themed", &render_fixture("themed.toml", &["s"], 320, 160));
+}
+
+#[test]
+fn golden_chord_label() {
+    assert_matches_golden(
+        "chord",
+        &render_fixture("chord.toml", &["leftctrl", "leftshift", "q"], 320, 240),
+    );
+}
+
+#[test]
+fn golden_layer_legends() {
+    assert_matches_golden(
+        "legends",
+        &render_fixture("legends.toml", &["leftshift"], 320, 200),
+    );
+}
+
+#[test]
+fn golden_momentary_layer() {
+    assert_matches_golden(
+        "layers",
+        &render_fixture("layers.toml", &["f13", "tab"], 320, 240),
+    );
+}
+
+#[test]
+fn golden_single_key() {
+    let surface = ImageSurface::create(Format::ARgb32, 160, 120).unwrap();
+    {
+        let ctx = Context::new(&surface).unwrap();
+        ctx.set_source_rgba(0.0, 0.0, 0.0, 1.0);
+        ctx.paint().unwrap();
+        ctx.set_font_face(&draw::load_default_font_face());
+        draw::draw_single_key_cairo(
+            &ctx,
+            &KeyDisplay {
+                text: "Enter".to_string(),
+                center_x: 80.0,
+                center_y: 60.0,
+                width: 120.0,
+                height: 80.0,
+                corner_radius: 12.0,
+                border_thickness: 3.0,
+                rotation_degrees: -10.0,
+                text_size: 28.0,
+                border_color: (0.9, 0.9, 0.9, 1.0),
+                background_color: (0.2, 0.4, 0.2, 1.0),
+                text_color: (1.0, 1.0, 0.6, 1.0),
+                legends: Vec::new(),
+                outlines: Vec::new(),
+                font: None,
+            },
+        );
+    }
+    surface.flush();
+    assert_matches_golden("single-key", &surface);
+}
+
+#[test]
+fn golden_strip() {
+    let path = golden_dir().join("strip.toml");
+    let config = load_and_process_config(path.to_str().unwrap(), None).unwrap();
+    let entries: Vec<(String, f64)> = [("Ctrl+Shift+T", 0.4), ("hello␣world", 1.0), ("⏎", 1.0)]
+        .iter()
+        .map(|(text, alpha)| (text.to_string(), *alpha))
+        .collect();
+
+    let surface = ImageSurface::create(Format::ARgb32, 640, 80).unwrap();
+    {
+        let ctx = Context::new(&surface).unwrap();
+        strip::paint_strip(
+            &ctx,
+            &config,
+            &entries,
+            640,
+            80,
+            &draw::load_default_font_face(),
+        );
+    }
+    surface.flush();
+    assert_matches_golden("strip", &surface);
+}
+
+#[test]
+fn golden_comparison_detects_changes() {
+    // Guard against a harness that accepts anything: an idle and a pressed
+    // rendering of the same layout must not compare as equal.
+    let idle = argb_pixels(&render_fixture("basic.toml", &[], 320, 200));
+    let pressed = argb_pixels(&render_fixture("basic.toml", &["leftshift", "a"], 320, 200));
+    let mismatched = idle
+        .iter()
+        .zip(pressed.iter())
+        .filter(|(a, b)| !pixel_matches(**a, **b))
+        .count();
+    assert!(mismatched as f64 > idle.len() as f64 * MAX_MISMATCHED_FRACTION);
+}
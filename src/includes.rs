@@ -0,0 +1,514 @@
+// src/includes.rs
+
+//! This module merges the configurations a configuration includes with
+//! `include = ["base.toml"]`, e.g. to extend a shared team layout.
+//!
+//! Included files are merged in order, each over the previous ones, and the
+//! including file over all of them. `[[key]]` entries are matched by `name`:
+//! a key with the name of an included key overrides the fields it sets, other
+//! keys are appended. If several included keys have the name (e.g. both
+//! "Shift" keys), the overriding key's `keycode` picks one. A `[[layer]]`
+//! replaces the included layer of the same name. Other tables, like
+//! `[overlay]` and `[style.<name>]`, are merged field by field.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::resolve_keycode_repr;
+use crate::diagnostics::SourceFile;
+use crate::keycodes::{self, KeycodeRepr};
+use crate::theme::{self, BUILTIN_THEMES};
+
+/// Where a merged `[[key]]` or `[[layer]]` is defined, to find it in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryOrigin {
+    /// The included file, or `None` for the including configuration.
+    pub file: Option<Arc<SourceFile>>,
+    /// The index of the entry in the file's list.
+    pub index: usize,
+}
+
+/// The origin of each merged `[[key]]` and `[[layer]]`, in order.
+#[derive(Debug, Default, PartialEq)]
+pub struct EntryOrigins {
+    pub key: Vec<EntryOrigin>,
+    pub layer: Vec<EntryOrigin>,
+}
+
+/// What resolving the includes of a configuration found.
+#[derive(Debug, Default)]
+pub struct Includes {
+    pub origins: EntryOrigins,
+    /// All included files, including those included by included files.
+    pub files: Vec<Arc<SourceFile>>,
+}
+
+/// A configuration table with the origins of its keys and layers.
+#[derive(Default)]
+struct Merged {
+    table: toml::Table,
+    origins: EntryOrigins,
+}
+
+impl Merged {
+    /// Wraps the table of a single file, whose entries all come from `file`.
+    fn from_file(table: toml::Table, file: Option<Arc<SourceFile>>) -> Self {
+        let entry_origins = |field: &str| match table.get(field) {
+            Some(toml::Value::Array(entries)) => (0..entries.len())
+                .map(|index| EntryOrigin {
+                    file: file.clone(),
+                    index,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let origins = EntryOrigins {
+            key: entry_origins("key"),
+            layer: entry_origins("layer"),
+        };
+        Merged { table, origins }
+    }
+}
+
+/// Fields a key needs unless it overrides an included key.
+const REQUIRED_KEY_FIELDS: &[&str] = &["left", "top", "width", "height"];
+
+/// Replaces the `include` list of `config`, read from a file in `base_dir`,
+/// by the content of the included files.
+pub fn resolve_includes(config: &mut toml::Table, base_dir: &Path) -> Result<Includes, String> {
+    let mut files = Vec::new();
+    let base = load_includes(config, base_dir, &mut Vec::new(), &mut files)?;
+    let merged = merge_over(base, Merged::from_file(std::mem::take(config), None))?;
+    *config = merged.table;
+    Ok(Includes {
+        origins: merged.origins,
+        files,
+    })
+}
+
+/// Loads and merges the files in the `include` list of `config`, removing it.
+///
+/// `stack` holds the files being included, to detect cycles, and `files`
+/// collects all files loaded.
+fn load_includes(
+    config: &mut toml::Table,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<Arc<SourceFile>>,
+) -> Result<Merged, String> {
+    let paths = match config.remove("include") {
+        None => return Ok(Merged::default()),
+        Some(toml::Value::Array(paths)) => paths,
+        Some(_) => return Err("'include' must be a list of file paths".to_string()),
+    };
+    let mut merged = Merged::default();
+    for path in paths {
+        let path = path
+            .as_str()
+            .ok_or("'include' must be a list of file paths")?;
+        let included = load_included_file(&base_dir.join(path), stack, files)?;
+        merged = merge_over(merged, included)
+            .map_err(|e| format!("In included configuration '{}': {}", path, e))?;
+    }
+    Ok(merged)
+}
+
+/// Reads an included file and resolves its own includes.
+fn load_included_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<Arc<SourceFile>>,
+) -> Result<Merged, String> {
+    let canonical = fs::canonicalize(path).map_err(|e| {
+        format!(
+            "Failed to read included configuration '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    if stack.contains(&canonical) {
+        let cycle: Vec<String> = stack
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!(
+            "Configurations include each other: {}",
+            cycle.join(" -> ")
+        ));
+    }
+    let content = fs::read_to_string(&canonical).map_err(|e| {
+        format!(
+            "Failed to read included configuration '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    let mut config: toml::Table = toml::from_str(&content).map_err(|e| {
+        format!(
+            "Failed to parse included configuration '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+
+    let dir = canonical.parent().unwrap_or(Path::new("/")).to_path_buf();
+    // A theme file is found next to the configuration naming it.
+    if let Some(toml::Value::String(theme)) =
+        config.get_mut("overlay").and_then(|o| o.get_mut("theme"))
+    {
+        if !BUILTIN_THEMES.iter().any(|(name, _)| name == theme) {
+            *theme = dir.join(&*theme).display().to_string();
+        }
+    }
+
+    let file = Arc::new(SourceFile {
+        name: path.display().to_string(),
+        content,
+    });
+    files.push(file.clone());
+    stack.push(canonical);
+    let base = load_includes(&mut config, &dir, stack, files);
+    stack.pop();
+    merge_over(base?, Merged::from_file(config, Some(file)))
+        .map_err(|e| format!("In included configuration '{}': {}", path.display(), e))
+}
+
+/// Merges `config` over `base`.
+fn merge_over(base: Merged, config: Merged) -> Result<Merged, String> {
+    let Merged {
+        table: mut merged,
+        mut origins,
+    } = base;
+    for (field, value) in config.table {
+        let base_value = merged.remove(&field);
+        let value = match (field.as_str(), base_value, value) {
+            ("key", Some(toml::Value::Array(base_keys)), toml::Value::Array(keys)) => {
+                let base_origins = std::mem::take(&mut origins.key);
+                let (keys, key_origins) = merge_keys(
+                    (base_keys, base_origins),
+                    (keys, config.origins.key.clone()),
+                )?;
+                origins.key = key_origins;
+                toml::Value::Array(keys)
+            }
+            ("layer", Some(toml::Value::Array(base_layers)), toml::Value::Array(layers)) => {
+                let base_origins = std::mem::take(&mut origins.layer);
+                let (layers, layer_origins) = merge_layers(
+                    (base_layers, base_origins),
+                    (layers, config.origins.layer.clone()),
+                );
+                origins.layer = layer_origins;
+                toml::Value::Array(layers)
+            }
+            (_, Some(toml::Value::Table(base_table)), toml::Value::Table(mut table)) => {
+                theme::merge_under(&mut table, base_table);
+                toml::Value::Table(table)
+            }
+            (_, _, value) => {
+                match field.as_str() {
+                    "key" => origins.key = config.origins.key.clone(),
+                    "layer" => origins.layer = config.origins.layer.clone(),
+                    _ => {}
+                }
+                value
+            }
+        };
+        merged.insert(field, value);
+    }
+    Ok(Merged {
+        table: merged,
+        origins,
+    })
+}
+
+/// Keys or layers with the origin of each.
+type Entries = (Vec<toml::Value>, Vec<EntryOrigin>);
+
+/// Overrides keys of `base` by name with `keys`, appending the others.
+fn merge_keys(
+    (mut base, mut origins): Entries,
+    (keys, key_origins): Entries,
+) -> Result<Entries, String> {
+    for (key, origin) in keys.into_iter().zip(key_origins) {
+        let toml::Value::Table(mut key) = key else {
+            return Err("[[key]] entries must be tables".to_string());
+        };
+        let name = key
+            .get("name")
+            .and_then(toml::Value::as_str)
+            .map(str::to_string);
+        match name
+            .as_ref()
+            .map(|name| find_key(&base, name, &key))
+            .transpose()?
+            .flatten()
+        {
+            Some(position) => {
+                let toml::Value::Table(base_key) = base[position].clone() else {
+                    unreachable!()
+                };
+                theme::merge_under(&mut key, base_key);
+                base[position] = toml::Value::Table(key);
+                origins[position] = origin;
+            }
+            None => {
+                if let Some(field) = REQUIRED_KEY_FIELDS
+                    .iter()
+                    .find(|field| !key.contains_key(**field))
+                {
+                    return Err(format!(
+                        "Key '{}' matches no included key, so it needs a '{}' like a new key.",
+                        name.as_deref().unwrap_or("(unnamed)"),
+                        field
+                    ));
+                }
+                base.push(toml::Value::Table(key));
+                origins.push(origin);
+            }
+        }
+    }
+    Ok((base, origins))
+}
+
+/// Finds the key of `base` that `key` overrides, named `name`.
+///
+/// If several keys have the name, `key` must have a `keycode` matching one of them.
+fn find_key(base: &[toml::Value], name: &str, key: &toml::Table) -> Result<Option<usize>, String> {
+    let candidates: Vec<usize> = (0..base.len())
+        .filter(|&i| base[i].get("name").and_then(toml::Value::as_str) == Some(name))
+        .collect();
+    if candidates.len() <= 1 {
+        return Ok(candidates.first().copied());
+    }
+    let Some(keycode) = key.get("keycode").map(|value| table_keycode(value, name)) else {
+        return Err(format!(
+            "Key '{}' matches {} included keys; set its 'keycode' to pick one.",
+            name,
+            candidates.len()
+        ));
+    };
+    let keycode = keycode?;
+    candidates
+        .into_iter()
+        .find(|&i| match base[i].get("keycode") {
+            Some(value) => table_keycode(value, name).ok() == Some(keycode),
+            None => keycodes::get_keycode_from_string(name).ok() == Some(keycode),
+        })
+        .map(Some)
+        .ok_or_else(|| {
+            format!(
+                "Key '{}' with keycode {} matches none of the included keys of that name.",
+                name, keycode
+            )
+        })
+}
+
+/// Resolves the `keycode` value of the key named `name` in a TOML table.
+fn table_keycode(value: &toml::Value, name: &str) -> Result<u32, String> {
+    value
+        .clone()
+        .try_into::<KeycodeRepr>()
+        .map_err(|e| e.to_string())
+        .and_then(|repr| resolve_keycode_repr(&repr))
+        .map_err(|e| format!("Invalid keycode of key '{}': {}", name, e))
+}
+
+/// Replaces layers of `base` by name with `layers`, appending the others.
+fn merge_layers((mut base, mut origins): Entries, (layers, layer_origins): Entries) -> Entries {
+    for (layer, origin) in layers.into_iter().zip(layer_origins) {
+        let name = layer.get("name").and_then(toml::Value::as_str);
+        let same_name = |base_layer: &toml::Value| {
+            name.is_some() && base_layer.get("name").and_then(toml::Value::as_str) == name
+        };
+        match base.iter().position(same_name) {
+            Some(position) => {
+                base[position] = layer;
+                origins[position] = origin;
+            }
+            None => {
+                base.push(layer);
+                origins.push(origin);
+            }
+        }
+    }
+    (base, origins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> toml::Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// Merges `config` over `base`, as if `base` were included from "base.toml".
+    fn merge(base: &str, config: &str) -> Result<Merged, String> {
+        let file = Arc::new(SourceFile {
+            name: "base.toml".to_string(),
+            content: base.to_string(),
+        });
+        merge_over(
+            Merged::from_file(table(base), Some(file)),
+            Merged::from_file(table(config), None),
+        )
+    }
+
+    fn own(index: usize) -> EntryOrigin {
+        EntryOrigin { file: None, index }
+    }
+
+    #[test]
+    fn test_merge_keys_by_name() {
+        let base = r#"
+            [overlay]
+            position = "top"
+            margin_top = 10
+
+            [[key]]
+            name = "A"
+            left = 0.0
+            top = 0.0
+            width = 50.0
+            height = 50.0
+
+            [[key]]
+            name = "Shift"
+            keycode = "leftshift"
+            left = 0.0
+            top = 60.0
+            width = 50.0
+            height = 50.0
+
+            [[key]]
+            name = "Shift"
+            keycode = "rightshift"
+            left = 60.0
+            top = 60.0
+            width = 50.0
+            height = 50.0
+            "#;
+        let config = r##"
+            [overlay]
+            margin_top = 20
+
+            [[key]]
+            name = "Shift"
+            keycode = 54
+            background_color = "#FF0000"
+
+            [[key]]
+            name = "B"
+            left = 60.0
+            top = 0.0
+            width = 50.0
+            height = 50.0
+
+            [[key]]
+            name = "A"
+            text_size = 12.0
+            "##;
+        let Merged {
+            table: merged,
+            origins,
+        } = merge(base, config).unwrap();
+        assert_eq!(
+            origins.key[1].file.as_ref().map(|file| file.name.as_str()),
+            Some("base.toml")
+        );
+        assert_eq!(origins.key[1].index, 1);
+        assert_eq!(
+            [&origins.key[0], &origins.key[2], &origins.key[3]],
+            [&own(2), &own(0), &own(1)]
+        );
+        assert_eq!(merged["overlay"]["position"].as_str(), Some("top"));
+        assert_eq!(merged["overlay"]["margin_top"].as_integer(), Some(20));
+        let keys = merged["key"].as_array().unwrap();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0]["text_size"].as_float(), Some(12.0));
+        assert!(keys[1].get("background_color").is_none());
+        assert_eq!(keys[2]["background_color"].as_str(), Some("#FF0000"));
+        assert_eq!(keys[2]["keycode"].as_integer(), Some(54));
+        assert_eq!(keys[2]["left"].as_float(), Some(60.0));
+        assert_eq!(keys[3]["name"].as_str(), Some("B"));
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let base = r#"
+            [[key]]
+            name = "Shift"
+            keycode = "leftshift"
+            left = 0.0
+            top = 0.0
+            width = 1.0
+            height = 1.0
+
+            [[key]]
+            name = "Shift"
+            keycode = "rightshift"
+            left = 2.0
+            top = 0.0
+            width = 1.0
+            height = 1.0
+            "#;
+        let ambiguous = merge(base, "[[key]]\nname = \"Shift\"\ntext_size = 1.0\n")
+            .err()
+            .unwrap();
+        assert!(
+            ambiguous.contains("matches 2 included keys"),
+            "{}",
+            ambiguous
+        );
+        let no_match = merge(base, "[[key]]\nname = \"Shfit\"\ntext_size = 1.0\n")
+            .err()
+            .unwrap();
+        assert!(no_match.contains("needs a 'left'"), "{}", no_match);
+    }
+
+    #[test]
+    fn test_include_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = "include = [\"colors.toml\"]\n\
+                    [[key]]\nname = \"A\"\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n";
+        fs::write(dir.path().join("base.toml"), base).unwrap();
+        fs::write(
+            dir.path().join("colors.toml"),
+            "[overlay]\ntheme = \"theme.toml\"\n",
+        )
+        .unwrap();
+        let mut config = table("include = [\"base.toml\"]\n[[key]]\nname = \"A\"\nwidth = 60.0\n");
+        let includes = resolve_includes(&mut config, dir.path()).unwrap();
+        assert_eq!(includes.origins.key, vec![own(0)]);
+        let names: Vec<&Path> = includes
+            .files
+            .iter()
+            .map(|file| Path::new(&file.name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                dir.path().join("base.toml"),
+                fs::canonicalize(dir.path()).unwrap().join("colors.toml")
+            ]
+        );
+        assert!(!config.contains_key("include"));
+        assert_eq!(config["key"][0]["width"].as_float(), Some(60.0));
+        let theme = Path::new(config["overlay"]["theme"].as_str().unwrap());
+        assert_eq!(
+            theme,
+            fs::canonicalize(dir.path()).unwrap().join("theme.toml")
+        );
+
+        fs::write(
+            dir.path().join("colors.toml"),
+            "include = [\"base.toml\"]\n",
+        )
+        .unwrap();
+        let mut config = table("include = [\"base.toml\"]\n");
+        let cycle = resolve_includes(&mut config, dir.path()).unwrap_err();
+        assert!(cycle.contains("include each other"), "{}", cycle);
+    }
+}
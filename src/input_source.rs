@@ -0,0 +1,214 @@
+// src/input_source.rs
+
+//! This module defines the `InputSource` abstraction used by the main loop.
+//!
+//! An input source yields key press/release events and exposes a file
+//! descriptor that becomes readable when events are pending, so every source
+//! can be registered with `FdPoller` the same way. Implementations live next
+//! to the code they wrap: libinput here, raw evdev in `evdev.rs` and
+//! recording replay in `replay.rs`.
+
+use input::event::keyboard::{KeyState, KeyboardEvent, KeyboardEventTrait};
+use input::event::Event as LibinputEvent;
+use libc::{O_NONBLOCK, O_RDWR};
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+/// A key press or release, independent of where it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Linux keycode (e.g., 30 for `KEY_A`).
+    pub keycode: u32,
+    /// `true` for a press, `false` for a release.
+    pub pressed: bool,
+}
+
+/// A pollable source of key events.
+///
+/// The file descriptor returned by `as_raw_fd` is polled for readability by
+/// `FdPoller`; when it is readable, `read_events` is called to drain it.
+pub trait InputSource: AsRawFd {
+    /// Short name of the source, used in log messages.
+    fn name(&self) -> &'static str;
+
+    /// Reads all pending key events.
+    ///
+    /// Returns `Err` if the source is no longer usable; the caller is expected
+    /// to drop it.
+    fn read_events(&mut self) -> Result<Vec<KeyEvent>, String>;
+
+    /// Called once right before the main loop starts.
+    fn start(&mut self) {}
+}
+
+/// Input backends selectable on the command line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputBackend {
+    /// libinput with udev seat discovery (default).
+    Libinput,
+    /// Raw evdev devices under /dev/input.
+    Evdev,
+}
+
+pub struct MyLibinputInterface;
+
+impl input::LibinputInterface for MyLibinputInterface {
+    fn open_restricted(&mut self, path: &Path, _flags: i32) -> Result<OwnedFd, i32> {
+        // flags are ignored because O_RDWR | O_NONBLOCK are always used.
+        log::debug!("Attempting to open input device: {:?}", path);
+        OpenOptions::new()
+            .custom_flags(O_RDWR | O_NONBLOCK) // Explicitly use these flags
+            .read(true) // Required by libinput
+            .write(true) // Required by libinput for some operations like LED changes (though not used by this app)
+            .open(path)
+            .map(|file| file.into())
+            .map_err(|e| {
+                let errno = e.raw_os_error().unwrap_or(libc::EIO);
+                match errno {
+                    libc::EPERM => log::error!(
+                        "Permission denied when opening {:?}. Check user permissions (e.g., 'input' group). Error: {}",
+                        path, e
+                    ),
+                    libc::ENOENT => log::error!(
+                        "Device {:?} not found. It might have been unplugged. Error: {}",
+                        path, e
+                    ),
+                    libc::EACCES => log::error!( // EACCES can also mean permission issues
+                        "Access denied when opening {:?}. Similar to EPERM, check permissions. Error: {}",
+                        path, e
+                    ),
+                    _ => log::error!(
+                        "Failed to open input device {:?} with flags O_RDWR | O_NONBLOCK. Error: {} (errno: {})",
+                        path, e, errno
+                    ),
+                }
+                errno // Return the original errno
+            })
+    }
+
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        // OwnedFd handles closing the file descriptor when it's dropped.
+        // We can log the action if desired.
+        log::debug!(
+            "Closing input device (FD: {}) via OwnedFd drop.",
+            fd.as_raw_fd()
+        );
+        drop(fd);
+    }
+}
+
+/// Key events from libinput, using udev to discover devices on `seat0`.
+pub struct LibinputSource {
+    context: input::Libinput,
+}
+
+impl LibinputSource {
+    /// Creates a libinput context and assigns `seat0` to it.
+    pub fn new() -> Result<Self, String> {
+        let mut context = input::Libinput::new_with_udev(MyLibinputInterface);
+        context
+            .udev_assign_seat("seat0")
+            .map_err(|e| format!("Failed to assign seat0 to libinput context: {:?}", e))?;
+        Ok(LibinputSource { context })
+    }
+}
+
+impl AsRawFd for LibinputSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.context.as_raw_fd()
+    }
+}
+
+impl InputSource for LibinputSource {
+    fn name(&self) -> &'static str {
+        "libinput"
+    }
+
+    fn read_events(&mut self) -> Result<Vec<KeyEvent>, String> {
+        // An error from dispatch() typically means libinput is no longer usable.
+        // This might happen if the underlying udev resources are gone.
+        self.context
+            .dispatch()
+            .map_err(|e| format!("Libinput dispatch error: {}", e))?;
+
+        let mut events = Vec::new();
+        for event in self.context.by_ref() {
+            if let LibinputEvent::Keyboard(KeyboardEvent::Key(key_event)) = event {
+                events.push(KeyEvent {
+                    keycode: key_event.key(), // This is the raw scancode from libinput
+                    pressed: key_event.key_state() == KeyState::Pressed,
+                });
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// An in-memory input source for tests.
+///
+/// Events queued with `push` are returned by the next `read_events` call.
+/// An eventfd provides the pollable file descriptor.
+#[cfg(test)]
+pub struct SyntheticSource {
+    queue: std::collections::VecDeque<KeyEvent>,
+    event_fd: OwnedFd,
+}
+
+#[cfg(test)]
+impl SyntheticSource {
+    pub fn new() -> Self {
+        use std::os::unix::io::FromRawFd;
+        let raw_fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        assert!(
+            raw_fd >= 0,
+            "eventfd failed: {}",
+            std::io::Error::last_os_error()
+        );
+        SyntheticSource {
+            queue: std::collections::VecDeque::new(),
+            // SAFETY: raw_fd was just returned by eventfd and is owned by nobody else.
+            event_fd: unsafe { OwnedFd::from_raw_fd(raw_fd) },
+        }
+    }
+
+    /// Queues a key event and marks the fd readable.
+    pub fn push(&mut self, keycode: u32, pressed: bool) {
+        self.queue.push_back(KeyEvent { keycode, pressed });
+        let one: u64 = 1;
+        unsafe {
+            libc::write(
+                self.event_fd.as_raw_fd(),
+                &one as *const u64 as *const libc::c_void,
+                8,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+impl AsRawFd for SyntheticSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.event_fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+impl InputSource for SyntheticSource {
+    fn name(&self) -> &'static str {
+        "synthetic"
+    }
+
+    fn read_events(&mut self) -> Result<Vec<KeyEvent>, String> {
+        let mut counter = [0u8; 8];
+        unsafe {
+            libc::read(
+                self.event_fd.as_raw_fd(),
+                counter.as_mut_ptr() as *mut libc::c_void,
+                8,
+            );
+        }
+        Ok(self.queue.drain(..).collect())
+    }
+}
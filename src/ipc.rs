@@ -0,0 +1,590 @@
+// src/ipc.rs
+
+//! This module implements the Unix-socket control interface.
+//!
+//! Clients connect to the socket (by default
+//! `$XDG_RUNTIME_DIR/wayland-kbd-osd.sock`) and send one JSON command per
+//! line, e.g. `{"command": "toggle"}`. Each command is answered with one JSON
+//! line: `{"ok": true, ...}` on success or `{"ok": false, "error": "..."}`.
+//! The listening socket and the accepted connections are non-blocking and
+//! registered with `FdPoller`. Each connection buffers what it has received
+//! until a line is complete, so a slow or silent client cannot stall the OSD.
+//! A `subscribe` command turns the connection into a key event stream (see
+//! `event_stream`).
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use crate::config::OverlayPosition;
+use crate::event;
+use crate::setup;
+use crate::wayland::AppState;
+
+/// File name of the socket inside `$XDG_RUNTIME_DIR`.
+pub const SOCKET_NAME: &str = "wayland-kbd-osd.sock";
+/// Upper bound on an unfinished command line buffered for a connection.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// A command sent by an IPC client.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcCommand {
+    /// Draw the keyboard.
+    Show,
+    /// Keep the surface but draw it fully transparent.
+    Hide,
+    /// Switch between shown and hidden.
+    Toggle,
+    /// Reload the current configuration file.
+    Reload,
+    /// Load a different configuration file and watch it for changes instead.
+    SwitchLayout { path: String },
+    /// Move the overlay to another position (overlay mode only).
+    SetPosition { position: OverlayPosition },
+    /// Report the current state.
+    Query,
+    /// Keep the connection open and stream key events to it as JSON lines.
+    Subscribe,
+}
+
+/// Returns the default socket path, `$XDG_RUNTIME_DIR/wayland-kbd-osd.sock`.
+pub fn default_socket_path() -> Result<PathBuf, String> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(&dir).join(SOCKET_NAME))
+        .ok_or_else(|| "XDG_RUNTIME_DIR is not set".to_string())
+}
+
+/// The listening IPC socket and its open connections. The socket file is
+/// removed when dropped.
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    connections: Vec<IpcConnection>,
+}
+
+/// A client connection and the part of a command line received so far.
+struct IpcConnection {
+    stream: UnixStream,
+    pending: Vec<u8>,
+}
+
+impl IpcServer {
+    /// Binds the socket at `path`.
+    ///
+    /// A leftover socket from a previous run is replaced, but binding fails
+    /// if another instance is still accepting connections on it, or if `path`
+    /// is some other kind of file.
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if UnixStream::connect(path).is_ok() {
+                    return Err(format!(
+                        "Another instance is already listening on IPC socket '{}'",
+                        path.display()
+                    ));
+                }
+                fs::remove_file(path).map_err(|e| {
+                    format!(
+                        "Failed to remove stale IPC socket '{}': {}",
+                        path.display(),
+                        e
+                    )
+                })?;
+            }
+            Ok(_) => {
+                return Err(format!(
+                    "Cannot create IPC socket '{}': a file that is not a socket exists there",
+                    path.display()
+                ))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(format!(
+                    "Failed to inspect IPC socket path '{}': {}",
+                    path.display(),
+                    e
+                ))
+            }
+        }
+
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Failed to bind IPC socket '{}': {}", path.display(), e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to make IPC socket non-blocking: {}", e))?;
+
+        log::info!("Listening for IPC commands on '{}'.", path.display());
+        Ok(IpcServer {
+            listener,
+            path: path.to_path_buf(),
+            connections: Vec::new(),
+        })
+    }
+
+    /// Returns the file descriptors of the open connections, to poll them.
+    pub fn connection_fds(&self) -> impl Iterator<Item = RawFd> + '_ {
+        self.connections
+            .iter()
+            .map(|connection| connection.stream.as_raw_fd())
+    }
+
+    /// Accepts all pending connections.
+    fn accept_pending(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        log::warn!("Failed to configure IPC connection: {}", e);
+                        continue;
+                    }
+                    self.connections.push(IpcConnection {
+                        stream,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("Failed to accept IPC connection: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl AsRawFd for IpcServer {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::debug!(
+                "Failed to remove IPC socket '{}': {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Accepts new IPC connections and serves the commands received on all of them.
+///
+/// Returns `true` if the set of file descriptors to poll changed (e.g. a
+/// connection was opened or closed, or `switch_layout` replaced the config
+/// watcher), in which case the caller must rebuild its `FdPoller`.
+pub fn handle_ipc_events(app_state: &mut AppState) -> bool {
+    let Some(server) = app_state.ipc_server.as_mut() else {
+        return false;
+    };
+    let connection_count = server.connections.len();
+    server.accept_pending();
+    let mut fds_changed = server.connections.len() != connection_count;
+
+    // Taken out of the server while commands are executed against `app_state`.
+    let connections = std::mem::take(&mut server.connections);
+    let mut open_connections = Vec::with_capacity(connections.len());
+    for mut connection in connections {
+        let (keep_open, switched_layout) = serve_connection(app_state, &mut connection);
+        fds_changed |= switched_layout || !keep_open;
+        if keep_open {
+            open_connections.push(connection);
+        }
+    }
+    if let Some(server) = app_state.ipc_server.as_mut() {
+        server.connections = open_connections;
+    }
+    fds_changed
+}
+
+/// Reads what a connection has sent and executes its complete command lines.
+///
+/// Returns whether to keep the connection open, and whether a command
+/// switched the layout.
+fn serve_connection(app_state: &mut AppState, connection: &mut IpcConnection) -> (bool, bool) {
+    let mut closed = false;
+    let mut buf = [0; 4096];
+    loop {
+        match connection.stream.read(&mut buf) {
+            Ok(0) => {
+                closed = true;
+                break;
+            }
+            Ok(n) => connection.pending.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                log::debug!("Error reading IPC connection: {}", e);
+                return (false, false);
+            }
+        }
+        if connection.pending.len() > MAX_REQUEST_BYTES && !connection.pending.contains(&b'\n') {
+            log::warn!(
+                "IPC client sent a command longer than {} bytes; closing the connection.",
+                MAX_REQUEST_BYTES
+            );
+            return (false, false);
+        }
+    }
+
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    while let Some(end) = connection.pending.iter().position(|&b| b == b'\n') {
+        lines.push(connection.pending.drain(..=end).collect());
+    }
+    // A last command may end without a newline.
+    if closed && !connection.pending.is_empty() {
+        lines.push(std::mem::take(&mut connection.pending));
+    }
+
+    let mut switched_layout = false;
+    for line in lines {
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<IpcCommand>(&line) {
+            Ok(IpcCommand::Subscribe) => {
+                // Acknowledge, then hand the connection over to the event stream.
+                // Any further input from the client is ignored.
+                if let Err(e) = writeln!(connection.stream, "{}", json!({ "ok": true })) {
+                    log::debug!("Failed to write IPC response: {}", e);
+                } else if let Err(e) = connection
+                    .stream
+                    .try_clone()
+                    .and_then(|stream| app_state.event_stream.add_subscriber(stream))
+                {
+                    log::warn!("Failed to add key event subscriber: {}", e);
+                }
+                return (false, switched_layout);
+            }
+            Ok(command) => {
+                log::debug!("IPC command: {:?}", command);
+                let switches_layout = matches!(command, IpcCommand::SwitchLayout { .. });
+                match execute_command(app_state, command) {
+                    Ok(data) => {
+                        switched_layout |= switches_layout;
+                        success_response(data)
+                    }
+                    Err(e) => json!({ "ok": false, "error": e }),
+                }
+            }
+            Err(e) => json!({ "ok": false, "error": format!("Invalid command: {}", e) }),
+        };
+
+        // Replies are short, so a client that does not read them is dropped
+        // rather than buffered for.
+        if let Err(e) = writeln!(connection.stream, "{}", response) {
+            log::debug!("Failed to write IPC response: {}", e);
+            return (false, switched_layout);
+        }
+    }
+    (!closed, switched_layout)
+}
+
+/// Builds `{"ok": true}` merged with the fields of `data` (if it is an object).
+fn success_response(data: Value) -> Value {
+    let mut response = json!({ "ok": true });
+    if let (Value::Object(fields), Some(map)) = (data, response.as_object_mut()) {
+        map.extend(fields);
+    }
+    response
+}
+
+/// Executes a command against the application state.
+///
+/// Returns additional response fields on success.
+pub fn execute_command(app_state: &mut AppState, command: IpcCommand) -> Result<Value, String> {
+    match command {
+        IpcCommand::Show => set_visible(app_state, true),
+        IpcCommand::Hide => set_visible(app_state, false),
+        IpcCommand::Toggle => {
+            let visible = !app_state.visible;
+            set_visible(app_state, visible);
+        }
+        IpcCommand::Reload => {
+            let path = app_state
+                .config_path
+                .clone()
+                .ok_or_else(|| "No configuration file to reload".to_string())?;
+            event::load_and_apply_config(app_state, &path)?;
+            log::info!("Reloaded configuration from '{}' via IPC.", path);
+        }
+        IpcCommand::SwitchLayout { path } => {
+            event::load_and_apply_config(app_state, &path)?;
+            app_state.config_watcher = None;
+            setup::initialize_config_watcher(app_state, &path);
+            log::info!("Switched to configuration '{}' via IPC.", path);
+        }
+        IpcCommand::SetPosition { position } => {
+            if app_state.layer_surface.is_none() {
+                return Err("set_position is only supported in overlay mode".to_string());
+            }
+            app_state.config.overlay.position = position;
+            app_state.apply_overlay_placement();
+            if let Some(surface) = app_state.surface.as_ref() {
+                surface.commit();
+            }
+            app_state.needs_redraw = true;
+        }
+        IpcCommand::Query => return Ok(json!({ "state": query_state(app_state) })),
+        IpcCommand::Subscribe => {
+            return Err("subscribe is only available on a socket connection".to_string())
+        }
+    }
+    Ok(json!({}))
+}
+
+fn set_visible(app_state: &mut AppState, visible: bool) {
+    if app_state.visible != visible {
+        app_state.visible = visible;
+        app_state.needs_redraw = true;
+        log::info!("OSD {} via IPC.", if visible { "shown" } else { "hidden" });
+    }
+}
+
+fn query_state(app_state: &AppState) -> Value {
+    let mut pressed: Vec<u32> = app_state
+        .key_states
+        .iter()
+        .filter(|(_, &is_pressed)| is_pressed)
+        .map(|(&keycode, _)| keycode)
+        .collect();
+    pressed.sort_unstable();
+    let pressed_keys: Vec<Value> = pressed
+        .into_iter()
+        .map(|keycode| {
+            let name = app_state
+                .config
+                .keys_for_layer(app_state.layers.active_layer())
+                .iter()
+                .find(|k| k.keycode == keycode)
+                .map(|k| k.name.clone());
+            json!({ "keycode": keycode, "name": name })
+        })
+        .collect();
+
+    json!({
+        "visible": app_state.visible,
+        "mode": if app_state.is_window_mode { "window" } else { "overlay" },
+        "config_path": app_state.config_path,
+        "position": app_state.config.overlay.position,
+        "width": app_state.configured_width,
+        "height": app_state.configured_height,
+        "key_count": app_state.config.key.len(),
+        "layer": app_state.active_layer_name(),
+        "pressed_keys": pressed_keys,
+        "input_source": app_state.input_source.as_ref().map(|s| s.name()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::process_config_str;
+    use std::io::{BufRead, BufReader};
+
+    fn test_state() -> AppState {
+        let config = process_config_str(
+            r#"
+            [[key]]
+            name = "A"
+            left = 0.0
+            top = 0.0
+            width = 50.0
+            height = 50.0
+            "#,
+            "test",
+            None,
+        )
+        .unwrap();
+        AppState::new(config, true, (0.0, 0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            serde_json::from_str::<IpcCommand>(r#"{"command":"toggle"}"#).unwrap(),
+            IpcCommand::Toggle
+        );
+        assert_eq!(
+            serde_json::from_str::<IpcCommand>(
+                r#"{"command":"set_position","position":"top-left"}"#
+            )
+            .unwrap(),
+            IpcCommand::SetPosition {
+                position: OverlayPosition::TopLeft
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<IpcCommand>(
+                r#"{"command":"switch_layout","path":"/tmp/x.toml"}"#
+            )
+            .unwrap(),
+            IpcCommand::SwitchLayout {
+                path: "/tmp/x.toml".to_string()
+            }
+        );
+        assert!(serde_json::from_str::<IpcCommand>(r#"{"command":"explode"}"#).is_err());
+    }
+
+    #[test]
+    fn test_socket_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join(SOCKET_NAME);
+        let mut app_state = test_state();
+        app_state.ipc_server = Some(IpcServer::bind(&socket_path).unwrap());
+        assert!(IpcServer::bind(&socket_path).is_err()); // Already in use
+
+        let mut client = UnixStream::connect(&socket_path).unwrap();
+        client
+            .write_all(b"{\"command\":\"hide\"}\nnot json\n{\"command\":\"query\"}\n{\"command\":\"set_position\",\"position\":\"top\"}\n")
+            .unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        assert!(handle_ipc_events(&mut app_state)); // Opened and closed a connection
+        assert!(!app_state.visible);
+
+        let responses: Vec<Value> = BufReader::new(client)
+            .lines()
+            .map(|l| serde_json::from_str(&l.unwrap()).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0], json!({ "ok": true }));
+        assert_eq!(responses[1]["ok"], false);
+        assert_eq!(responses[2]["state"]["visible"], false);
+        assert_eq!(responses[2]["state"]["position"], "bottom-center");
+        assert_eq!(responses[3]["ok"], false); // Window mode
+
+        drop(app_state);
+        assert!(!socket_path.exists());
+    }
+
+    #[test]
+    fn test_bind_keeps_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "important").unwrap();
+        let err = IpcServer::bind(&path).err().unwrap();
+        assert!(err.contains("not a socket"), "{}", err);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "important");
+
+        // A stale socket from a previous run is replaced.
+        let socket_path = dir.path().join(SOCKET_NAME);
+        drop(UnixListener::bind(&socket_path).unwrap());
+        assert!(IpcServer::bind(&socket_path).is_ok());
+    }
+
+    #[test]
+    fn test_partial_commands_do_not_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join(SOCKET_NAME);
+        let mut app_state = test_state();
+        app_state.ipc_server = Some(IpcServer::bind(&socket_path).unwrap());
+
+        let _silent = UnixStream::connect(&socket_path).unwrap();
+        let mut client = UnixStream::connect(&socket_path).unwrap();
+        client.write_all(b"{\"command\":").unwrap();
+        assert!(handle_ipc_events(&mut app_state)); // Two connections opened
+        assert_eq!(
+            app_state
+                .ipc_server
+                .as_ref()
+                .unwrap()
+                .connection_fds()
+                .count(),
+            2
+        );
+        assert!(app_state.visible);
+
+        client.write_all(b"\"hide\"}\n").unwrap();
+        assert!(!handle_ipc_events(&mut app_state));
+        assert!(!app_state.visible);
+        let mut response = String::new();
+        BufReader::new(&client).read_line(&mut response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&response).unwrap(),
+            json!({ "ok": true })
+        );
+
+        drop(client);
+        assert!(handle_ipc_events(&mut app_state)); // One connection closed
+        assert_eq!(
+            app_state
+                .ipc_server
+                .as_ref()
+                .unwrap()
+                .connection_fds()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_switch_layout_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("other.toml");
+        fs::write(
+            &path,
+            "[[key]]\nname = \"B\"\nleft = 0.0\ntop = 0.0\nwidth = 50.0\nheight = 50.0\n",
+        )
+        .unwrap();
+
+        let mut app_state = test_state();
+        assert!(execute_command(&mut app_state, IpcCommand::Reload).is_err());
+
+        let path_str = path.to_str().unwrap().to_string();
+        execute_command(
+            &mut app_state,
+            IpcCommand::SwitchLayout {
+                path: path_str.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(app_state.config.key[0].name, "B");
+        assert_eq!(app_state.config_path.as_deref(), Some(path_str.as_str()));
+        assert!(app_state.config_watcher.is_some());
+
+        fs::write(&path, "not toml [").unwrap();
+        assert!(execute_command(&mut app_state, IpcCommand::Reload).is_err());
+        assert_eq!(app_state.config.key[0].name, "B");
+    }
+
+    #[test]
+    fn test_subscribe_streams_key_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join(SOCKET_NAME);
+        let mut app_state = test_state();
+        app_state.ipc_server = Some(IpcServer::bind(&socket_path).unwrap());
+
+        let mut client = UnixStream::connect(&socket_path).unwrap();
+        client.write_all(b"{\"command\":\"subscribe\"}\n").unwrap();
+        handle_ipc_events(&mut app_state);
+
+        event::process_key_event(&mut app_state, 30, true);
+        event::process_key_event(&mut app_state, 30, false);
+
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(
+            serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap(),
+            json!({ "ok": true })
+        );
+        let pressed: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(
+            (pressed["name"].as_str(), pressed["state"].as_str()),
+            (Some("A"), Some("pressed"))
+        );
+        let released: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(released["state"], "released");
+    }
+}
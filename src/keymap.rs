@@ -0,0 +1,170 @@
+// src/keymap.rs
+
+//! This module derives key labels from an XKB keymap.
+//!
+//! With `[keymap]` in the configuration, keys without a `name` are labelled
+//! with what they type in the active layout: either the keymap the compositor
+//! sends for the seat's keyboard, or one compiled from RMLVO names. Labels
+//! come from the first layout group at the base shift level. libxkbcommon is
+//! only linked when the crate is built with the `xkb` feature.
+
+use std::collections::HashMap;
+
+use crate::config::KeymapConfig;
+
+/// Labels derived from a keymap, by evdev keycode.
+pub type KeymapLabels = HashMap<u32, String>;
+
+/// Error returned when the crate is built without the `xkb` feature.
+const UNSUPPORTED: &str =
+    "Key labels from [keymap] need XKB support; rebuild with `--features xkb`.";
+
+/// Returns an error if this build cannot derive labels from XKB keymaps.
+pub fn check_supported() -> Result<(), String> {
+    if cfg!(feature = "xkb") {
+        Ok(())
+    } else {
+        Err(UNSUPPORTED.to_string())
+    }
+}
+
+/// Offset between evdev keycodes and XKB keycodes.
+#[cfg(feature = "xkb")]
+const EVDEV_OFFSET: u32 = 8;
+
+/// Turns a keysym into a keycap label.
+///
+/// `text` is the keysym's UTF-8 representation and `name` its keysym name.
+/// Printable text is used as is, letters uppercased like on a keycap. Keysyms
+/// without printable text fall back to a readable form of their name, e.g.
+/// `"Shift_L"` becomes `"Shift"` and `"space"` becomes `"Space"`.
+#[cfg_attr(not(feature = "xkb"), allow(dead_code))]
+pub fn label_for_keysym(text: &str, name: &str) -> Option<String> {
+    if !text.is_empty() && !text.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        let upper = text.to_uppercase();
+        // Keep letters without a single-character capital (e.g. "ß") as they are.
+        return Some(if upper.chars().count() == text.chars().count() {
+            upper
+        } else {
+            text.to_string()
+        });
+    }
+    if name.is_empty() || name == "NoSymbol" {
+        return None;
+    }
+    let name = name
+        .strip_suffix("_L")
+        .or_else(|| name.strip_suffix("_R"))
+        .unwrap_or(name);
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    Some(
+        first
+            .to_uppercase()
+            .chain(chars)
+            .collect::<String>()
+            .replace('_', " "),
+    )
+}
+
+/// Compiles a keymap from the RMLVO names in `config` and returns its labels.
+#[cfg(feature = "xkb")]
+pub fn labels_from_names(config: &KeymapConfig) -> Result<KeymapLabels, String> {
+    use xkbcommon::xkb;
+
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb::Keymap::new_from_names(
+        &context,
+        &config.rules,
+        &config.model,
+        &config.layout,
+        &config.variant,
+        config.options.clone(),
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or_else(|| {
+        format!(
+            "Failed to compile XKB keymap from names (rules '{}', model '{}', layout '{}', variant '{}', options '{}').",
+            config.rules,
+            config.model,
+            config.layout,
+            config.variant,
+            config.options.as_deref().unwrap_or("")
+        )
+    })?;
+    Ok(labels_from_keymap(&keymap))
+}
+
+/// Compiles a keymap in the XKB text format (as sent by the compositor) and returns its labels.
+#[cfg(feature = "xkb")]
+pub fn labels_from_keymap_text(text: &str) -> Result<KeymapLabels, String> {
+    use xkbcommon::xkb;
+
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let text = text.trim_end_matches('\0').to_string();
+    let keymap = xkb::Keymap::new_from_string(
+        &context,
+        text,
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or_else(|| "Failed to compile the XKB keymap provided by the compositor.".to_string())?;
+    Ok(labels_from_keymap(&keymap))
+}
+
+/// Collects the label of every key in `keymap` that has one.
+#[cfg(feature = "xkb")]
+fn labels_from_keymap(keymap: &xkbcommon::xkb::Keymap) -> KeymapLabels {
+    use xkbcommon::xkb;
+
+    let mut labels = KeymapLabels::new();
+    for raw in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
+        let Some(keycode) = raw.checked_sub(EVDEV_OFFSET) else {
+            continue;
+        };
+        let Some(&keysym) = keymap
+            .key_get_syms_by_level(xkb::Keycode::new(raw), 0, 0)
+            .first()
+        else {
+            continue;
+        };
+        if let Some(label) =
+            label_for_keysym(&xkb::keysym_to_utf8(keysym), &xkb::keysym_get_name(keysym))
+        {
+            labels.insert(keycode, label);
+        }
+    }
+    labels
+}
+
+/// Compiles a keymap from the RMLVO names in `config` and returns its labels.
+#[cfg(not(feature = "xkb"))]
+pub fn labels_from_names(_config: &KeymapConfig) -> Result<KeymapLabels, String> {
+    Err(UNSUPPORTED.to_string())
+}
+
+/// Compiles a keymap in the XKB text format (as sent by the compositor) and returns its labels.
+#[cfg(not(feature = "xkb"))]
+pub fn labels_from_keymap_text(_text: &str) -> Result<KeymapLabels, String> {
+    Err(UNSUPPORTED.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_for_keysym() {
+        assert_eq!(label_for_keysym("a", "a").as_deref(), Some("A"));
+        assert_eq!(label_for_keysym("ö", "odiaeresis").as_deref(), Some("Ö"));
+        assert_eq!(label_for_keysym("ß", "ssharp").as_deref(), Some("ß"));
+        assert_eq!(label_for_keysym("\r", "Return").as_deref(), Some("Return"));
+        assert_eq!(label_for_keysym("", "Shift_L").as_deref(), Some("Shift"));
+        assert_eq!(
+            label_for_keysym("", "Caps_Lock").as_deref(),
+            Some("Caps Lock")
+        );
+        assert_eq!(label_for_keysym(" ", "space").as_deref(), Some("Space"));
+        assert_eq!(label_for_keysym("", "NoSymbol"), None);
+    }
+}
@@ -0,0 +1,648 @@
+// src/kle.rs
+
+//! This module imports layouts from Keyboard Layout Editor (KLE) JSON.
+//!
+//! KLE (keyboard-layout-editor.com) describes a keyboard as rows of keys,
+//! where each key is a legend string optionally preceded by an object that
+//! changes the position, size or rotation of the following keys. Both the
+//! downloaded JSON file and the "Raw data" text (with unquoted property names
+//! and without the outer brackets) are accepted.
+//!
+//! One KLE unit becomes `KLE_UNIT` layout units, leaving a `KLE_GAP` gap
+//! between neighbouring keys like in the bundled `keys.toml`. Keycodes are
+//! guessed from the legends; keys whose keycode cannot be guessed are
+//! imported without one and reported as warnings.
+
+use serde_json::Value;
+use std::fs;
+
+use crate::config::{AppConfig, KeyConfig, KeyShape, LegendConfig, LegendLayer, LegendPosition};
+use crate::diagnostics::EntrySpans;
+use crate::keycodes::{get_keycode_from_string, KeycodeRepr};
+
+/// Distance between the left edges of two adjacent 1u keys, in layout units.
+pub const KLE_UNIT: f32 = 55.0;
+/// Gap between adjacent keys, in layout units.
+pub const KLE_GAP: f32 = 5.0;
+
+/// Legend positions of KLE's 12 label slots (9 on the top face, 3 on the front).
+/// Front legends are placed along the bottom edge.
+const SLOT_POSITIONS: [LegendPosition; 12] = [
+    LegendPosition::TopLeft,
+    LegendPosition::Top,
+    LegendPosition::TopRight,
+    LegendPosition::Left,
+    LegendPosition::Center,
+    LegendPosition::Right,
+    LegendPosition::BottomLeft,
+    LegendPosition::Bottom,
+    LegendPosition::BottomRight,
+    LegendPosition::BottomLeft,
+    LegendPosition::Bottom,
+    LegendPosition::BottomRight,
+];
+
+/// Maps the n-th line of a legend string to a label slot, for each alignment (`a`) value.
+/// Taken from KLE's serializer; `-1` marks lines that are unused for an alignment.
+const LABEL_MAP: [[i8; 12]; 8] = [
+    [0, 6, 2, 8, 9, 11, 3, 5, 1, 4, 7, 10],
+    [1, 7, -1, -1, 9, 11, 4, -1, -1, -1, -1, 10],
+    [3, -1, 5, -1, 9, 11, -1, -1, 4, -1, -1, 10],
+    [4, -1, -1, -1, 9, 11, -1, -1, -1, -1, -1, 10],
+    [0, 6, 2, 8, 10, -1, 3, 5, 1, 4, 7, -1],
+    [1, 7, -1, -1, 10, -1, 4, -1, -1, -1, -1, -1],
+    [3, -1, 5, -1, 10, -1, -1, -1, 4, -1, -1, -1],
+    [4, -1, -1, -1, 10, -1, -1, -1, -1, -1, -1, -1],
+];
+
+/// Slots tried, in order, for the key's main label.
+const NAME_SLOTS: [usize; 9] = [6, 4, 7, 3, 0, 1, 8, 5, 2];
+
+/// Default alignment of KLE legends.
+const DEFAULT_ALIGNMENT: usize = 4;
+
+/// Modifier legends that exist on both sides of the keyboard.
+const SIDED_MODIFIERS: [(&[&str], &str, &str); 4] = [
+    (&["shift"], "leftshift", "rightshift"),
+    (&["ctrl", "control"], "leftctrl", "rightctrl"),
+    (&["alt", "option"], "leftalt", "rightalt"),
+    (
+        &["win", "windows", "super", "meta", "cmd", "command", "gui"],
+        "leftmeta",
+        "rightmeta",
+    ),
+];
+
+/// A key as placed by KLE, in KLE units and before rotation.
+///
+/// QMK and VIA layouts use the same units and rotation rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KleKey {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Rotation in degrees, clockwise around (`rotation_x`, `rotation_y`).
+    pub rotation: f32,
+    pub rotation_x: f32,
+    pub rotation_y: f32,
+    /// Second rectangle of a stepped or L-shaped key (e.g. ISO Enter), as
+    /// `[x2, y2, w2, h2]` with the offset relative to `x`, `y`.
+    pub second_rect: Option<[f32; 4]>,
+    /// Legends by label slot: 0-8 on the top face from the top left, row by
+    /// row, and 9-11 on the front.
+    pub labels: [Option<String>; 12],
+}
+
+/// Reads a KLE JSON file and converts it to a configuration.
+pub fn import_kle_file(path: &str) -> Result<AppConfig, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read KLE file '{}': {}", path, e))?;
+    import_kle_str(&content).map_err(|e| format!("Failed to import KLE file '{}': {}", path, e))
+}
+
+/// Converts KLE JSON (downloaded file or raw data) to a configuration.
+pub fn import_kle_str(content: &str) -> Result<AppConfig, String> {
+    let rows = parse_kle_rows(&parse_kle_json(content)?)?;
+    let kle_keys = place_keys(&rows)?;
+    if kle_keys.is_empty() {
+        return Err("The layout contains no keys.".to_string());
+    }
+
+    let mut keys: Vec<KeyConfig> = kle_keys.iter().map(convert_key).collect();
+
+    // Resolve sided modifiers ("Shift", "Ctrl", ...) by which half of the keyboard they are on.
+    let left_halves = left_halves(&keys);
+    for ((key, kle_key), left_half) in keys.iter_mut().zip(&kle_keys).zip(left_halves) {
+        key.raw_keycode = guess_keycode(kle_key, left_half).map(KeycodeRepr::Text);
+    }
+
+    move_to_origin(&mut keys);
+    for key in &keys {
+        if key.raw_keycode.is_none() {
+            log::warn!(
+                "No keycode found for key '{}' at ({}, {}); set `keycode` for it by hand.",
+                key.name,
+                key.left,
+                key.top
+            );
+        }
+    }
+
+    Ok(AppConfig {
+        include: Vec::new(),
+        key: keys,
+        overlay: Default::default(),
+        keymap: None,
+        layer: Vec::new(),
+        style: Default::default(),
+        spans: EntrySpans::default(),
+        included_files: Vec::new(),
+    })
+}
+
+/// Returns, for each key, whether its center is on the left half of the layout.
+pub fn left_halves(keys: &[KeyConfig]) -> Vec<bool> {
+    let min_x = keys.iter().map(|k| k.left).fold(f32::MAX, f32::min);
+    let max_x = keys
+        .iter()
+        .map(|k| k.left + k.width)
+        .fold(f32::MIN, f32::max);
+    let middle = (min_x + max_x) / 2.0;
+    keys.iter()
+        .map(|k| k.left + k.width / 2.0 < middle)
+        .collect()
+}
+
+/// Moves the keys so that the layout starts at (0, 0), rounding positions to two decimals.
+pub fn move_to_origin(keys: &mut [KeyConfig]) {
+    let min_x = keys.iter().map(|k| k.left).fold(f32::MAX, f32::min);
+    let min_y = keys.iter().map(|k| k.top).fold(f32::MAX, f32::min);
+    for key in keys {
+        key.left = round_coordinate(key.left - min_x);
+        key.top = round_coordinate(key.top - min_y);
+    }
+}
+
+/// Parses KLE JSON, accepting the editor's raw data as well.
+///
+/// Raw data copied from the editor is not strict JSON: property names are
+/// unquoted and the outer array is missing. Both are fixed up before parsing.
+fn parse_kle_json(content: &str) -> Result<Value, String> {
+    let quoted = quote_property_names(content);
+    serde_json::from_str(content)
+        .or_else(|_| serde_json::from_str(&quoted))
+        .or_else(|_| serde_json::from_str(&format!("[{}]", quoted)))
+        .map_err(|e| format!("Invalid KLE JSON: {}", e))
+}
+
+/// Splits parsed KLE JSON into its rows, skipping the optional metadata object.
+pub fn parse_kle_rows(value: &Value) -> Result<Vec<Vec<Value>>, String> {
+    let Value::Array(items) = value else {
+        return Err("Invalid KLE JSON: expected an array of rows.".to_string());
+    };
+
+    let mut rows = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        match item {
+            Value::Array(row) => rows.push(row.clone()),
+            Value::Object(_) if index == 0 => {} // Keyboard metadata (name, author, ...).
+            other => {
+                return Err(format!(
+                    "Invalid KLE JSON: expected a row array, found {}.",
+                    other
+                ))
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Quotes unquoted property names (e.g. `{w:1.5}` becomes `{"w":1.5}`), leaving strings untouched.
+fn quote_property_names(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            result.push(c);
+            match c {
+                '\\' => result.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+            result.push(c);
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut word = c.to_string();
+            while let Some(&next) = chars.peek() {
+                if !(next.is_ascii_alphanumeric() || next == '_') {
+                    break;
+                }
+                word.push(next);
+                chars.next();
+            }
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            if chars.peek() == Some(&':') {
+                result.push_str(&format!("\"{}\"", word));
+            } else {
+                // Literals such as `true`.
+                result.push_str(&word);
+                result.push(' ');
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Reads a numeric property of a KLE key object.
+fn number(props: &serde_json::Map<String, Value>, name: &str) -> Result<Option<f32>, String> {
+    match props.get(name) {
+        None => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(|v| Some(v as f32))
+            .ok_or_else(|| format!("Property '{}' must be a number, found {}.", name, value)),
+    }
+}
+
+/// Places the keys of all rows, following KLE's rules for the running position.
+///
+/// Positions are relative: `x` and `y` shift the next key, widths only apply
+/// to the next key, and every row starts one unit below the previous one at
+/// the current rotation origin. Setting `rx` or `ry` moves the position to
+/// the new rotation origin.
+pub fn place_keys(rows: &[Vec<Value>]) -> Result<Vec<KleKey>, String> {
+    let mut keys = Vec::new();
+    let (mut x, mut y) = (0.0f32, 0.0f32);
+    let (mut rotation, mut rotation_x, mut rotation_y) = (0.0f32, 0.0f32, 0.0f32);
+    let mut alignment = DEFAULT_ALIGNMENT;
+
+    for (row_index, row) in rows.iter().enumerate() {
+        let (mut width, mut height) = (1.0f32, 1.0f32);
+        let mut decal = false;
+        let mut second_rect: [Option<f32>; 4] = [None; 4];
+        for item in row {
+            match item {
+                Value::Object(props) => {
+                    if let Some(r) = number(props, "r")? {
+                        rotation = r;
+                    }
+                    if let Some(rx) = number(props, "rx")? {
+                        rotation_x = rx;
+                        (x, y) = (rotation_x, rotation_y);
+                    }
+                    if let Some(ry) = number(props, "ry")? {
+                        rotation_y = ry;
+                        (x, y) = (rotation_x, rotation_y);
+                    }
+                    if let Some(a) = number(props, "a")? {
+                        alignment = (a as usize).min(LABEL_MAP.len() - 1);
+                    }
+                    x += number(props, "x")?.unwrap_or(0.0);
+                    y += number(props, "y")?.unwrap_or(0.0);
+                    width = number(props, "w")?.unwrap_or(width);
+                    height = number(props, "h")?.unwrap_or(height);
+                    decal = props.get("d").and_then(Value::as_bool).unwrap_or(decal);
+                    for (value, name) in second_rect.iter_mut().zip(["x2", "y2", "w2", "h2"]) {
+                        *value = number(props, name)?.or(*value);
+                    }
+                }
+                Value::String(legend) => {
+                    if !decal {
+                        keys.push(KleKey {
+                            x,
+                            y,
+                            width,
+                            height,
+                            rotation,
+                            rotation_x,
+                            rotation_y,
+                            second_rect: second_rect.iter().any(Option::is_some).then(|| {
+                                let [x2, y2, w2, h2] = second_rect;
+                                [
+                                    x2.unwrap_or(0.0),
+                                    y2.unwrap_or(0.0),
+                                    w2.unwrap_or(width),
+                                    h2.unwrap_or(height),
+                                ]
+                            }),
+                            labels: split_labels(legend, alignment),
+                        });
+                    }
+                    x += width;
+                    (width, height) = (1.0, 1.0);
+                    decal = false;
+                    second_rect = [None; 4];
+                }
+                other => {
+                    return Err(format!(
+                        "Invalid KLE JSON: unexpected {} in row {}.",
+                        other,
+                        row_index + 1
+                    ))
+                }
+            }
+        }
+        y += 1.0;
+        x = rotation_x;
+    }
+    Ok(keys)
+}
+
+/// Splits a KLE legend string into label slots according to the alignment.
+fn split_labels(legend: &str, alignment: usize) -> [Option<String>; 12] {
+    let mut labels: [Option<String>; 12] = Default::default();
+    for (line, text) in legend.split('\n').enumerate().take(12) {
+        let text = clean_legend(text);
+        let slot = LABEL_MAP[alignment][line];
+        if slot >= 0 && !text.is_empty() {
+            labels[slot as usize] = Some(text);
+        }
+    }
+    labels
+}
+
+/// Strips HTML markup from a legend, which KLE allows for icons and line breaks.
+fn clean_legend(text: &str) -> String {
+    let mut result = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                result.push(' ');
+            }
+            _ if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+    let result = result
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Rounds a layout coordinate to two decimals.
+fn round_coordinate(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Converts a placed KLE key to a key configuration, without its keycode.
+///
+/// The main legend becomes the key's name and the others its secondary legends.
+pub fn convert_key(kle_key: &KleKey) -> KeyConfig {
+    // A stepped key spans the bounding box of its two rectangles.
+    let mut rects = vec![[kle_key.x, kle_key.y, kle_key.width, kle_key.height]];
+    if let Some([x2, y2, w2, h2]) = kle_key.second_rect {
+        rects.push([kle_key.x + x2, kle_key.y + y2, w2, h2]);
+    }
+    let min_x = rects.iter().map(|r| r[0]).fold(f32::MAX, f32::min);
+    let min_y = rects.iter().map(|r| r[1]).fold(f32::MAX, f32::min);
+    let max_x = rects.iter().map(|r| r[0] + r[2]).fold(f32::MIN, f32::max);
+    let max_y = rects.iter().map(|r| r[1] + r[3]).fold(f32::MIN, f32::max);
+    let width = (max_x - min_x) * KLE_UNIT - KLE_GAP;
+    let height = (max_y - min_y) * KLE_UNIT - KLE_GAP;
+    let shape = (rects.len() > 1 && rects[0] != rects[1]).then(|| {
+        KeyShape::Rects(
+            rects
+                .iter()
+                .map(|r| {
+                    [
+                        round_coordinate((r[0] - min_x) * KLE_UNIT),
+                        round_coordinate((r[1] - min_y) * KLE_UNIT),
+                        round_coordinate(r[2] * KLE_UNIT - KLE_GAP),
+                        round_coordinate(r[3] * KLE_UNIT - KLE_GAP),
+                    ]
+                })
+                .collect(),
+        )
+    });
+
+    // KLE rotates keys around the rotation origin, while keys here rotate
+    // around their center: rotate the center around the origin instead.
+    let center_x = min_x * KLE_UNIT + width / 2.0;
+    let center_y = min_y * KLE_UNIT + height / 2.0;
+    let (center_x, center_y) = if kle_key.rotation != 0.0 {
+        let (sin, cos) = kle_key.rotation.to_radians().sin_cos();
+        let (origin_x, origin_y) = (kle_key.rotation_x * KLE_UNIT, kle_key.rotation_y * KLE_UNIT);
+        let (dx, dy) = (center_x - origin_x, center_y - origin_y);
+        (
+            origin_x + dx * cos - dy * sin,
+            origin_y + dx * sin + dy * cos,
+        )
+    } else {
+        (center_x, center_y)
+    };
+
+    let mut key = KeyConfig {
+        name: String::new(),
+        name_derived: false,
+        left: center_x - width / 2.0,
+        top: center_y - height / 2.0,
+        width: round_coordinate(width),
+        height: round_coordinate(height),
+        raw_keycode: None,
+        keycode: 0,
+        rotation_degrees: (kle_key.rotation != 0.0).then_some(kle_key.rotation),
+        shape,
+        style: None,
+        text_size: None,
+        corner_radius: None,
+        border_thickness: None,
+        background_color: None,
+        text_color: None,
+        outline_color: None,
+        active_background_color: None,
+        active_text_color: None,
+        active_outline_color: None,
+        font: None,
+        legend_position: None,
+        shift_legend: None,
+        altgr_legend: None,
+        fn_legend: None,
+        spans: EntrySpans::default(),
+    };
+
+    let Some(name_slot) = NAME_SLOTS
+        .iter()
+        .copied()
+        .find(|&slot| kle_key.labels[slot].is_some())
+        .or_else(|| (9..12).find(|&slot| kle_key.labels[slot].is_some()))
+    else {
+        return key;
+    };
+    key.name = kle_key.labels[name_slot].clone().unwrap_or_default();
+
+    // Remaining legends become secondary legends: the top row for Shift, the
+    // right column for AltGr and the front for Fn, then whatever slot is free.
+    let preferred = |slot: usize| match slot {
+        0..=2 => LegendLayer::Shift,
+        5 | 8 => LegendLayer::AltGr,
+        _ => LegendLayer::Fn,
+    };
+    let mut rest: Vec<usize> = (0..12)
+        .filter(|&slot| slot != name_slot && kle_key.labels[slot].is_some())
+        .collect();
+    rest.sort_by_key(|&slot| slot >= 9);
+    let mut unplaced = Vec::new();
+    for slot in rest {
+        let layer = preferred(slot);
+        if key.layer_legend(layer).is_none() {
+            set_layer_legend(&mut key, layer, kle_key, slot);
+        } else {
+            unplaced.push(slot);
+        }
+    }
+    for slot in unplaced {
+        match LegendLayer::PRIORITY
+            .iter()
+            .rev()
+            .copied()
+            .find(|&layer| key.layer_legend(layer).is_none())
+        {
+            Some(layer) => set_layer_legend(&mut key, layer, kle_key, slot),
+            None => log::warn!(
+                "Dropping legend '{}' of key '{}': a key has at most three secondary legends.",
+                kle_key.labels[slot].as_deref().unwrap_or_default(),
+                key.name
+            ),
+        }
+    }
+
+    let name_position = SLOT_POSITIONS[name_slot];
+    if key.has_layer_legends() && name_position != LegendPosition::BottomLeft {
+        key.legend_position = Some(name_position);
+    }
+    key
+}
+
+/// Sets the legend of `layer` to the label in `slot`.
+fn set_layer_legend(key: &mut KeyConfig, layer: LegendLayer, kle_key: &KleKey, slot: usize) {
+    let text = kle_key.labels[slot].clone().unwrap_or_default();
+    let position = SLOT_POSITIONS[slot];
+    let legend = if position == layer.default_position() {
+        LegendConfig::Text(text)
+    } else {
+        LegendConfig::Placed { text, position }
+    };
+    match layer {
+        LegendLayer::Shift => key.shift_legend = Some(legend),
+        LegendLayer::AltGr => key.altgr_legend = Some(legend),
+        LegendLayer::Fn => key.fn_legend = Some(legend),
+    }
+}
+
+/// Guesses the keycode of a key from its legends.
+///
+/// Legends are tried as key names in the order the main label is chosen,
+/// with and without spaces (e.g. "Caps Lock"). Sided modifiers are resolved
+/// with `left_half`. Wide keys without legends are taken to be the space bar.
+pub fn guess_keycode(kle_key: &KleKey, left_half: bool) -> Option<String> {
+    let slots = NAME_SLOTS.iter().copied().chain(9..12);
+    let legends: Vec<&str> = slots
+        .filter_map(|slot| kle_key.labels[slot].as_deref())
+        .collect();
+    if legends.is_empty() {
+        return (kle_key.width >= 4.0).then(|| "space".to_string());
+    }
+    for legend in legends {
+        let compact: String = legend.chars().filter(|c| !c.is_whitespace()).collect();
+        for candidate in [legend, compact.as_str()] {
+            let lower = candidate.to_lowercase();
+            if let Some((_, left, right)) = SIDED_MODIFIERS
+                .iter()
+                .find(|(names, _, _)| names.contains(&lower.as_str()))
+            {
+                return Some(if left_half { left } else { right }.to_string());
+            }
+            if get_keycode_from_string(candidate).is_ok() {
+                return Some(lower);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_raw_data() {
+        let raw = r#"["Esc",{x:1},"F1"],
+[{a:4},"!\n1",{w:1.5},"Tab","Q\n\n\n@"],
+[{w:1.25},"Shift",{w:6.25},"",{w:1.25},"Shift"]"#;
+        let config = import_kle_str(raw).unwrap();
+        let summary: Vec<(&str, Option<KeycodeRepr>, f32, f32, f32)> = config
+            .key
+            .iter()
+            .map(|k| {
+                (
+                    k.name.as_str(),
+                    k.raw_keycode.clone(),
+                    k.left,
+                    k.top,
+                    k.width,
+                )
+            })
+            .collect();
+        let text = |s: &str| Some(KeycodeRepr::Text(s.to_string()));
+        assert_eq!(
+            summary,
+            vec![
+                ("Esc", text("esc"), 0.0, 0.0, 50.0),
+                ("F1", text("f1"), 110.0, 0.0, 50.0),
+                ("1", text("1"), 0.0, 55.0, 50.0),
+                ("Tab", text("tab"), 55.0, 55.0, 77.5),
+                ("Q", text("q"), 137.5, 55.0, 50.0),
+                ("Shift", text("leftshift"), 0.0, 110.0, 63.75),
+                ("", text("space"), 68.75, 110.0, 338.75),
+                ("Shift", text("rightshift"), 412.5, 110.0, 63.75),
+            ]
+        );
+        assert_eq!(
+            config.key[2].shift_legend,
+            Some(LegendConfig::Text("!".to_string()))
+        );
+        assert_eq!(
+            config.key[4].altgr_legend,
+            Some(LegendConfig::Text("@".to_string()))
+        );
+        assert_eq!(config.key[4].legend_position, Some(LegendPosition::TopLeft));
+    }
+
+    #[test]
+    fn test_import_rotation() {
+        // A key rotated by 90 degrees around the top-left corner of the first key.
+        let json = r#"[{"name": "test"}, ["A"], [{"r": 90, "rx": 0, "ry": 0, "x": 1}, "B"]]"#;
+        let config = import_kle_str(json).unwrap();
+        let b = &config.key[1];
+        assert_eq!(b.rotation_degrees, Some(90.0));
+        // Unrotated, B would be centered at (80, 25); rotated it is centered at (-25, 80).
+        let a = &config.key[0];
+        assert!((b.left + b.width / 2.0 - (a.left + 25.0 - 50.0)).abs() < 0.01);
+        assert!((b.top + b.height / 2.0 - (a.top + 80.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_import_iso_enter() {
+        let json =
+            r#"[[{"x": 0.25, "w": 1.25, "h": 2, "w2": 1.5, "h2": 1, "x2": -0.25}, "Enter", "A"]]"#;
+        let config = import_kle_str(json).unwrap();
+        let enter = &config.key[0];
+        assert_eq!(
+            (enter.left, enter.top, enter.width, enter.height),
+            (0.0, 0.0, 77.5, 105.0)
+        );
+        assert_eq!(
+            enter.shape,
+            Some(KeyShape::Rects(vec![
+                [13.75, 0.0, 63.75, 105.0],
+                [0.0, 0.0, 77.5, 50.0]
+            ]))
+        );
+        // The next key follows the first rectangle.
+        assert_eq!(config.key[1].left, 82.5);
+        assert!(config.key[1].shape.is_none());
+    }
+
+    #[test]
+    fn test_import_roundtrip() {
+        let config = import_kle_str(r#"[["Caps Lock","A"]]"#).unwrap();
+        let toml = crate::config::to_toml_string(&config).unwrap();
+        let reloaded = crate::config::process_config_str(&toml, "imported", None).unwrap();
+        let keycodes: Vec<u32> = reloaded.key.iter().map(|k| k.keycode).collect();
+        assert_eq!(keycodes, vec![58, 30]);
+    }
+}
@@ -0,0 +1,353 @@
+// src/layout.rs
+
+//! This module computes where keys are drawn on a surface of a given size.
+//!
+//! It turns the abstract layout units of `AppConfig` into surface pixels
+//! (overall scale and offsets) and prepares the `KeyDisplay` list consumed by
+//! `draw::paint_all_keys`. Nothing here depends on Wayland, so the same code
+//! drives the on-screen overlay and offscreen rendering.
+
+use std::collections::HashMap;
+
+use crate::config::{
+    default_key_background_color_string, parse_color_string, AppConfig, KeyConfig, LabelPosition,
+    LegendLayer, LegendPosition, DEFAULT_BORDER_THICKNESS_UNSCALED, DEFAULT_CORNER_RADIUS_UNSCALED,
+    DEFAULT_ROTATION_DEGREES, DEFAULT_TEXT_SIZE_UNSCALED,
+};
+use crate::draw::{KeyDisplay, Legend};
+use crate::shape;
+
+/// Fraction of the surface height taken by the label band, if enabled.
+pub const LABEL_BAND_FRACTION: f32 = 0.18;
+
+/// Returns the width and height of the bounding box of all keys, in layout units.
+pub fn get_key_layout_bounds(config: &AppConfig) -> (f32, f32) {
+    if config.key.is_empty() {
+        return (0.0, 0.0);
+    }
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for kc in &config.key {
+        min_x = min_x.min(kc.left);
+        max_x = max_x.max(kc.left + kc.width);
+        min_y = min_y.min(kc.top);
+        max_y = max_y.max(kc.top + kc.height);
+    }
+    ((max_x - min_x).max(0.0), (max_y - min_y).max(0.0))
+}
+
+/// Returns the size of everything drawn in keyboard mode, in layout units:
+/// the key bounding box plus the label band, if enabled.
+///
+/// Used to derive the surface aspect ratio.
+pub fn get_content_bounds(config: &AppConfig) -> (f32, f32) {
+    let (layout_w, layout_h) = get_key_layout_bounds(config);
+    match config.overlay.label_position {
+        LabelPosition::None => (layout_w, layout_h),
+        LabelPosition::Above | LabelPosition::Below => {
+            (layout_w, layout_h / (1.0 - LABEL_BAND_FRACTION))
+        }
+    }
+}
+
+/// Returns the vertical offset and height of the part of the surface used for keys.
+fn keyboard_area(config: &AppConfig, surface_height: f32) -> (f32, f32) {
+    let band_height = surface_height * LABEL_BAND_FRACTION;
+    match config.overlay.label_position {
+        LabelPosition::None => (0.0, surface_height),
+        LabelPosition::Above => (band_height, surface_height - band_height),
+        LabelPosition::Below => (0.0, surface_height - band_height),
+    }
+}
+
+/// Returns the label band as `(x, y, width, height)` in surface pixels, if enabled.
+pub fn label_band_rect(
+    config: &AppConfig,
+    surface_width: i32,
+    surface_height: i32,
+) -> Option<(f32, f32, f32, f32)> {
+    let (w, h) = (surface_width as f32, surface_height as f32);
+    let band_height = h * LABEL_BAND_FRACTION;
+    match config.overlay.label_position {
+        LabelPosition::None => None,
+        LabelPosition::Above => Some((0.0, 0.0, w, band_height)),
+        LabelPosition::Below => Some((0.0, h - band_height, w, band_height)),
+    }
+}
+
+/// Calculates the scale and offsets that fit the key layout into a surface.
+///
+/// # Returns
+///
+/// `(scale, offset_x, offset_y)`: a key at layout position `(x, y)` is drawn
+/// at surface position `(x * scale + offset_x, y * scale + offset_y)`.
+pub fn calculate_layout_parameters(
+    config: &AppConfig,
+    surface_width: i32,
+    surface_height: i32,
+) -> (f32, f32, f32) {
+    let (layout_w, layout_h) = get_key_layout_bounds(config);
+    let (area_y, area_height) = keyboard_area(config, surface_height as f32);
+    let padding = if config.overlay.size_width.is_some() || config.overlay.size_height.is_some() {
+        2.0
+    } else {
+        ((surface_width as f32).min(area_height) * 0.05).max(5.0)
+    };
+
+    let drawable_width = (surface_width as f32 - 2.0 * padding).max(0.0);
+    let drawable_height = (area_height - 2.0 * padding).max(0.0);
+
+    let scale = if layout_w > 0.0 && layout_h > 0.0 {
+        let scale_x = drawable_width / layout_w;
+        let scale_y = drawable_height / layout_h;
+        scale_x.min(scale_y).max(0.01)
+    } else {
+        1.0
+    };
+
+    let scaled_layout_width = layout_w * scale;
+    let scaled_layout_height = layout_h * scale;
+
+    let min_coord_x = config
+        .key
+        .iter()
+        .map(|k| k.left)
+        .fold(f32::INFINITY, |a, b| a.min(b));
+    let min_coord_y = config
+        .key
+        .iter()
+        .map(|k| k.top)
+        .fold(f32::INFINITY, |a, b| a.min(b));
+
+    let actual_min_coord_x = if min_coord_x.is_finite() {
+        min_coord_x
+    } else {
+        0.0
+    };
+    let actual_min_coord_y = if min_coord_y.is_finite() {
+        min_coord_y
+    } else {
+        0.0
+    };
+
+    let offset_x =
+        padding + (drawable_width - scaled_layout_width) / 2.0 - (actual_min_coord_x * scale);
+    let offset_y = area_y + padding + (drawable_height - scaled_layout_height) / 2.0
+        - (actual_min_coord_y * scale);
+
+    (scale, offset_x, offset_y)
+}
+
+/// Returns the legend layer whose modifier is held in `key_states`, if any.
+///
+/// If several are held, Fn wins over AltGr, which wins over Shift.
+pub fn active_legend_layer(key_states: &HashMap<u32, bool>) -> Option<LegendLayer> {
+    LegendLayer::PRIORITY.into_iter().find(|layer| {
+        layer
+            .keycodes()
+            .iter()
+            .any(|code| *key_states.get(code).unwrap_or(&false))
+    })
+}
+
+/// Builds the legends of a key with secondary legends or a placed `name`.
+///
+/// Returns an empty list for keys with just a centered `name`. The legend of
+/// `active_layer` is emphasized if the key has one.
+fn legends_for_key(kc: &KeyConfig, active_layer: Option<LegendLayer>) -> Vec<Legend> {
+    if !kc.has_layer_legends() && kc.legend_position.is_none() {
+        return Vec::new();
+    }
+    let default_position = if kc.has_layer_legends() {
+        LegendPosition::BottomLeft
+    } else {
+        LegendPosition::Center
+    };
+    let mut legends = vec![Legend {
+        text: kc.name.clone(),
+        position: kc.legend_position.unwrap_or(default_position),
+        emphasized: false,
+    }];
+    for layer in LegendLayer::PRIORITY {
+        if let Some(legend) = kc.layer_legend(layer) {
+            legends.push(Legend {
+                text: legend.text().to_string(),
+                position: legend.position_or(layer.default_position()),
+                emphasized: active_layer == Some(layer),
+            });
+        }
+    }
+    legends
+}
+
+/// Builds the `KeyDisplay` list for all configured keys.
+///
+/// Keys are taken from `active_layer` (an index into `config.layer`), or from
+/// the base layout for `None`. Appearance is resolved from the key, its style
+/// and the overlay configuration, picking the active colors for keys whose keycode is pressed
+/// in `key_states`. While a Shift, AltGr or Fn key is held, that layer's
+/// legends are emphasized.
+pub fn prepare_keys_for_drawing(
+    config: &AppConfig,
+    key_states: &HashMap<u32, bool>,
+    active_layer: Option<usize>,
+    scale: f32,
+    offset_x: f32,
+    offset_y: f32,
+) -> Vec<KeyDisplay> {
+    let default_fallback_color = (0.1, 0.1, 0.1, 1.0);
+
+    let key_outline_color = parse_color_string(&config.overlay.default_key_outline_color)
+        .unwrap_or(default_fallback_color);
+    let default_key_text_color = parse_color_string(&config.overlay.default_key_text_color)
+        .unwrap_or(default_fallback_color);
+    let active_key_bg_color = parse_color_string(&config.overlay.active_key_background_color)
+        .unwrap_or((0.6, 0.6, 0.9, 1.0));
+    let active_key_text_color =
+        parse_color_string(&config.overlay.active_key_text_color).unwrap_or(default_key_text_color);
+
+    let ultimate_inactive_bg_fallback =
+        parse_color_string(&default_key_background_color_string()).unwrap_or((0.3, 0.3, 0.3, 0.5));
+    let legend_layer = active_legend_layer(key_states);
+
+    config
+        .keys_for_layer(active_layer)
+        .iter()
+        .map(|kc| {
+            let is_pressed = *key_states.get(&kc.keycode).unwrap_or(&false);
+
+            // Properties set on the key or its style win over the overlay
+            // defaults; invalid colors are ignored like invalid defaults.
+            let style = config.key_style(kc);
+            let key_color =
+                |color: &Option<String>| color.as_deref().and_then(|s| parse_color_string(s).ok());
+            let outline_color = key_color(&style.outline_color).unwrap_or(key_outline_color);
+
+            let (bg_color, text_color, border_color) = if is_pressed {
+                (
+                    key_color(&style.active_background_color).unwrap_or(active_key_bg_color),
+                    key_color(&style.active_text_color).unwrap_or(active_key_text_color),
+                    key_color(&style.active_outline_color).unwrap_or(outline_color),
+                )
+            } else {
+                (
+                    key_color(&style.background_color).unwrap_or_else(|| {
+                        parse_color_string(&config.overlay.default_key_background_color)
+                            .unwrap_or(ultimate_inactive_bg_fallback)
+                    }),
+                    key_color(&style.text_color).unwrap_or(default_key_text_color),
+                    outline_color,
+                )
+            };
+
+            KeyDisplay {
+                text: kc.name.clone(),
+                center_x: (kc.left + kc.width / 2.0) * scale + offset_x,
+                center_y: (kc.top + kc.height / 2.0) * scale + offset_y,
+                width: kc.width * scale,
+                height: kc.height * scale,
+                corner_radius: style
+                    .corner_radius
+                    .unwrap_or(DEFAULT_CORNER_RADIUS_UNSCALED)
+                    * scale,
+                border_thickness: style
+                    .border_thickness
+                    .unwrap_or(DEFAULT_BORDER_THICKNESS_UNSCALED)
+                    * scale,
+                rotation_degrees: kc.rotation_degrees.unwrap_or(DEFAULT_ROTATION_DEGREES),
+                text_size: style.text_size.unwrap_or(DEFAULT_TEXT_SIZE_UNSCALED) * scale,
+                border_color,
+                background_color: bg_color,
+                text_color,
+                legends: legends_for_key(kc, legend_layer),
+                outlines: kc
+                    .shape
+                    .as_ref()
+                    .map(|shape| {
+                        shape::shape_outlines(shape)
+                            .into_iter()
+                            .map(|outline| {
+                                outline
+                                    .into_iter()
+                                    .map(|(x, y)| (x * scale, y * scale))
+                                    .collect()
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                font: style.font,
+            }
+        })
+        .collect()
+}
+
+/// Returns the text of the label band: the active layer's name and/or the
+/// modifier chord, e.g. "Lower · Ctrl+C".
+pub fn band_label(layer_name: Option<&str>, chord: Option<String>) -> Option<String> {
+    match (layer_name, chord) {
+        (Some(layer), Some(chord)) => Some(format!("{} · {}", layer, chord)),
+        (Some(layer), None) => Some(layer.to_string()),
+        (None, chord) => chord,
+    }
+}
+
+/// Builds the highlighted label drawn centered in the label band (see `label_band_rect`).
+///
+/// The label uses the active key colors and the default key font. Its width
+/// is estimated for the monospaced built-in font and capped at the band
+/// width; text that still does not fit is shrunk by `layout_text` when drawn.
+pub fn prepare_label_for_drawing(
+    config: &AppConfig,
+    text: &str,
+    band: (f32, f32, f32, f32),
+) -> KeyDisplay {
+    let (band_x, band_y, band_w, band_h) = band;
+    let height = band_h * 0.8;
+    let text_size = height * 0.55;
+    // DejaVu Sans Mono advances 0.6 em per character.
+    let text_width = text.chars().count() as f32 * text_size * 0.6;
+    let width = (text_width + height).clamp(height, band_w * 0.95);
+
+    let fallback = (0.1, 0.1, 0.1, 1.0);
+    let text_color = parse_color_string(&config.overlay.active_key_text_color).unwrap_or(fallback);
+    KeyDisplay {
+        text: text.to_string(),
+        center_x: band_x + band_w / 2.0,
+        center_y: band_y + band_h / 2.0,
+        width,
+        height,
+        corner_radius: height * 0.25,
+        border_thickness: (height * 0.04).max(1.0),
+        rotation_degrees: 0.0,
+        text_size,
+        border_color: parse_color_string(&config.overlay.default_key_outline_color)
+            .unwrap_or(fallback),
+        background_color: parse_color_string(&config.overlay.active_key_background_color)
+            .unwrap_or((0.6, 0.6, 0.9, 1.0)),
+        text_color,
+        legends: Vec::new(),
+        outlines: Vec::new(),
+        font: config.overlay.default_key_font.clone(),
+    }
+}
+
+/// Returns the overlay background color: the active color while any key is
+/// pressed, the inactive color otherwise.
+pub fn overlay_background_color(
+    config: &AppConfig,
+    key_states: &HashMap<u32, bool>,
+) -> (f64, f64, f64, f64) {
+    let is_overlay_active = key_states.values().any(|&pressed| pressed);
+    let bg_color_str = if is_overlay_active {
+        &config.overlay.background_color_active
+    } else {
+        &config.overlay.background_color_inactive
+    };
+    parse_color_string(bg_color_str).unwrap_or_else(|e| {
+        log::warn!(
+            "Failed to parse overlay background color string '{}': {}. Using transparent black.",
+            bg_color_str,
+            e
+        );
+        (0.0, 0.0, 0.0, 0.0)
+    })
+}
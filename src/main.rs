@@ -8,23 +8,42 @@ use std::process; // Used in main loop for poll error
 // Crate-specific modules
 mod check; // Added new module
 mod config;
+mod config_watch;
+mod diagnostics;
 mod draw; // Not directly used in main, but AppState::draw calls it
+mod evdev;
 mod event;
+mod event_stream;
+#[cfg(test)]
+mod golden_tests;
+mod includes;
+mod input_source;
+mod ipc;
 mod keycodes;
+mod keymap;
+mod kle;
+mod layout;
 mod poll_fds; // Added new module
+mod presets;
+mod qmk;
+mod render;
+mod replay;
 mod setup; // Added new module
+mod shape;
+mod strip;
 mod text_utils; // Added new module
+mod theme;
 mod wayland;
 mod wayland_drawing_cache; // Added new module
 
 // External Crate Imports
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 // Using items from the new modules
 use config::{load_and_process_config, AppConfig};
-// MyLibinputInterface is used by setup module
-// handle_libinput_events is called via event::handle_libinput_events
+// handle_input_events is called via event::handle_input_events
 // handle_wayland_events is called via wayland::handle_wayland_events
+use input_source::InputBackend;
 use wayland::AppState;
 use crate::poll_fds::{PollEvent, PollError}; // Using the new polling module
 
@@ -42,9 +61,36 @@ struct Cli {
     #[clap(long)]
     check: bool,
 
-    /// Path to the configuration file
-    #[clap(long, value_parser, default_value = "keys.toml")]
-    config_path: String,
+    /// Output format of --check
+    #[clap(long, value_enum, default_value = "text", requires = "check")]
+    format: check::CheckFormat,
+
+    /// Make --check fail on warnings, e.g. unknown fields, too
+    #[clap(long, requires = "check")]
+    strict: bool,
+
+    /// Path to the configuration file [default: wayland-kbd-osd/keys.toml in
+    /// $XDG_CONFIG_HOME or $XDG_CONFIG_DIRS if it exists, otherwise the ansi-104 layout]
+    #[clap(long, value_parser)]
+    config_path: Option<String>,
+
+    /// Use a built-in layout instead of a configuration file
+    #[clap(
+        long,
+        value_enum,
+        value_name = "PRESET",
+        conflicts_with = "config_path"
+    )]
+    layout: Option<presets::Preset>,
+
+    /// Theme to use instead of the configuration's `overlay.theme`: dark, light,
+    /// high-contrast or the path to a theme file
+    #[clap(long, value_name = "THEME")]
+    theme: Option<String>,
+
+    /// Print the configuration (e.g. a --layout preset) as TOML and exit
+    #[clap(long)]
+    dump_config: bool,
 
     /// Run in window mode instead of the default overlay mode
     #[clap(long)]
@@ -53,6 +99,90 @@ struct Cli {
     /// Set the window background color (e.g., #RRGGBBAA, #RGB)
     #[clap(long, default_value = "#000000FF")]
     window_color: String,
+
+    /// Input backend used to read key events
+    #[clap(long, value_enum, default_value = "libinput")]
+    input: InputBackend,
+
+    /// Replay key events from a `libinput record` YAML file instead of reading input devices
+    #[clap(long, value_parser)]
+    replay: Option<String>,
+
+    /// Replay the recording as fast as possible instead of following its timing
+    #[clap(long, requires = "replay")]
+    replay_fast: bool,
+
+    /// Write processed key events to stdout as JSON lines
+    #[clap(long)]
+    emit_events: bool,
+
+    /// Path of the IPC control socket [default: $XDG_RUNTIME_DIR/wayland-kbd-osd.sock]
+    #[clap(long, value_parser, value_name = "PATH")]
+    ipc_socket: Option<String>,
+
+    /// Render the keyboard to a PNG file and exit, without connecting to Wayland
+    #[clap(long, value_parser, value_name = "PATH")]
+    render_png: Option<String>,
+
+    /// Comma-separated keys to show as pressed in the rendered PNG (e.g., leftshift,a)
+    #[clap(long, value_delimiter = ',', requires = "render_png")]
+    pressed: Vec<String>,
+
+    /// Width of the rendered PNG in pixels (derived from the layout if omitted)
+    #[clap(long, requires = "render_png")]
+    render_width: Option<u32>,
+
+    /// Height of the rendered PNG in pixels (derived from the layout if omitted)
+    #[clap(long, requires = "render_png")]
+    render_height: Option<u32>,
+
+    /// Convert a Keyboard Layout Editor JSON file to a configuration, print it and exit
+    #[clap(long, value_parser, value_name = "PATH")]
+    import_kle: Option<String>,
+
+    /// Convert a QMK info.json or VIA definition to a configuration, print it and exit
+    #[clap(long, value_parser, value_name = "PATH")]
+    import_qmk: Option<String>,
+
+    /// QMK keymap.json or VIA keymap backup providing the keycodes for --import-qmk
+    #[clap(long, value_parser, value_name = "PATH", requires = "import_qmk")]
+    qmk_keymap: Option<String>,
+
+    /// Layout macro of the info.json to import (default: the keymap's layout, LAYOUT, or the first one)
+    #[clap(long, value_name = "NAME", requires = "import_qmk")]
+    qmk_layout: Option<String>,
+}
+
+/// Returns the configuration of a built-in layout and its name, exiting if
+/// `theme` cannot be applied. Without a `preset`, the default one is used.
+fn load_preset(preset: Option<presets::Preset>, theme: Option<&str>) -> (AppConfig, String) {
+    let preset = preset.unwrap_or_else(|| {
+        let searched: Vec<String> = config::config_search_dirs()
+            .iter()
+            .map(|dir| dir.join(config::CONFIG_FILE_NAME).display().to_string())
+            .collect();
+        log::info!(
+            "No configuration file found (looked for {}); using the built-in '{}' layout.",
+            searched.join(", "),
+            presets::DEFAULT_PRESET
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+        );
+        presets::DEFAULT_PRESET
+    });
+    let name = format!(
+        "built-in layout {}",
+        preset.to_possible_value().unwrap().get_name()
+    );
+    let mut app_config = presets::preset_config(preset);
+    if let Some(theme) = theme {
+        if let Err(e) = config::apply_theme(&mut app_config, theme) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    (app_config, name)
 }
 
 fn main() {
@@ -69,23 +199,94 @@ fn main() {
         )
     }
 
-    let app_config: AppConfig = match load_and_process_config(&cli.config_path) {
-        Ok(config) => config,
-        Err(e) => {
-            eprintln!("{}", e);
-            process::exit(1);
+    let imported = if let Some(kle_path) = cli.import_kle.as_deref() {
+        Some(kle::import_kle_file(kle_path))
+    } else {
+        cli.import_qmk.as_deref().map(|qmk_path| {
+            qmk::import_qmk_file(
+                qmk_path,
+                cli.qmk_keymap.as_deref(),
+                cli.qmk_layout.as_deref(),
+            )
+        })
+    };
+    if let Some(imported) = imported {
+        match imported.and_then(|config| config::to_toml_string(&config)) {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
         }
+        process::exit(0);
+    }
+
+    // Use the configuration file if one is given or found, otherwise a built-in layout.
+    let config_path = cli.config_path.clone().or_else(|| {
+        let path = config::find_config_file().filter(|_| cli.layout.is_none())?;
+        log::info!("Using configuration file '{}'.", path.display());
+        Some(path.display().to_string())
+    });
+    let (app_config, config_name) = match (cli.layout, config_path.as_deref()) {
+        (None, Some(path)) => match load_and_process_config(path, cli.theme.as_deref()) {
+            Ok(config) => (config, path.to_string()),
+            Err(e) if cli.check => {
+                check::run_check_failed_load(path, cli.theme.as_deref(), &e, cli.format)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        (preset, _) => load_preset(preset, cli.theme.as_deref()),
     };
+    // A built-in layout has no file to watch or reload.
+    let config_path = config_path.filter(|_| cli.layout.is_none());
+
+    if cli.dump_config {
+        match config::to_toml_string(&app_config) {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
 
     if cli.check {
         // Call the run_check function from the new check module
-        check::run_check(&cli.config_path, &app_config);
+        // The file's content is used to show where problems are.
+        let source = config_path
+            .as_deref()
+            .and_then(|path| config::read_config_file(path).ok());
+        check::run_check(
+            &config_name,
+            source.as_deref(),
+            &app_config,
+            cli.format,
+            cli.strict,
+        );
         // run_check will process::exit(0) on success or process::exit(1) on error.
     }
 
+    if let Some(output_path) = cli.render_png.as_deref() {
+        if let Err(e) = render::render_to_png(
+            &app_config,
+            &cli.pressed,
+            cli.render_width,
+            cli.render_height,
+            output_path,
+        ) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     log::info!(
         "Starting Wayland application with config '{}'...",
-        &cli.config_path
+        config_name
     );
     log::info!("Configuration loaded and processed successfully.");
 
@@ -105,13 +306,25 @@ fn main() {
     };
 
     let mut app_state = AppState::new(app_config.clone(), cli.window, parsed_window_color);
+    app_state.config_path = config_path.clone();
+    app_state.theme = cli.theme.clone();
+    app_state.event_stream = event_stream::EventStream::new(cli.emit_events);
 
     // Bind Wayland globals (compositor, shm, xdg_wm_base, layer_shell, outputs)
     let _registry = conn.display().get_registry(&qh, ()); // Get registry to trigger global events
     setup::initialize_globals_and_outputs(&conn, &mut event_queue, &mut app_state);
 
-    // Initialize libinput
-    setup::initialize_libinput_context(&mut app_state);
+    // Initialize the input source: a recording replay if requested, otherwise the selected backend
+    let replay = cli.replay.as_deref().map(|path| (path, !cli.replay_fast));
+    setup::initialize_input_source(&mut app_state, cli.input, replay);
+
+    // Reload the configuration when the file changes
+    if let Some(path) = config_path.as_deref() {
+        setup::initialize_config_watcher(&mut app_state, path);
+    }
+
+    // Accept control commands (show/hide/toggle/...) on a Unix socket
+    setup::initialize_ipc_server(&mut app_state, cli.ipc_socket.as_deref());
 
     // Create Wayland surface
     setup::create_wayland_surface(&mut app_state, &qh);
@@ -132,10 +345,12 @@ fn main() {
     let mut fd_poller = setup::initialize_fd_poller(&conn, &app_state);
 
     // Log input device status
-    setup::log_input_device_status(&app_state.config, app_state.input_context.is_some());
+    setup::log_input_device_status(&app_state.config, app_state.input_source.is_some());
+    if let Some(source) = app_state.input_source.as_mut() {
+        source.start();
+    }
 
     let poll_timeout_ms = 33;
-    let mut libinput_active = app_state.input_context.is_some();
 
     while app_state.running {
         match fd_poller.poll(poll_timeout_ms) {
@@ -148,9 +363,18 @@ fn main() {
                                 break;
                             }
                         }
-                        PollEvent::LibinputReady => {
-                            if libinput_active {
-                                event::handle_libinput_events(&mut app_state);
+                        PollEvent::InputReady => {
+                            if !event::handle_input_events(&mut app_state) {
+                                // The source failed and was dropped; stop polling its fd.
+                                fd_poller = setup::initialize_fd_poller(&conn, &app_state);
+                            }
+                        }
+                        PollEvent::ConfigChanged => {
+                            event::handle_config_change(&mut app_state);
+                        }
+                        PollEvent::IpcReady => {
+                            if ipc::handle_ipc_events(&mut app_state) {
+                                fd_poller = setup::initialize_fd_poller(&conn, &app_state);
                             }
                         }
                         PollEvent::WaylandError => {
@@ -158,17 +382,23 @@ fn main() {
                             app_state.running = false;
                             break;
                         }
-                        PollEvent::LibinputError => {
-                            log::error!("Libinput FD error/hangup reported by FdPoller. Input monitoring will stop.");
-                            if let Some(ref mut context) = app_state.input_context {
-                                let _ = context.dispatch(); // Attempt to clear pending
-                            }
-                            app_state.input_context = None; // Mark libinput as inactive
+                        PollEvent::InputError => {
+                            log::error!("Input source FD error/hangup reported by FdPoller. Input monitoring will stop.");
+                            app_state.input_source = None; // Mark input as inactive
 
-                            // Recreate FdPoller without libinput - use the setup function
+                            // Recreate FdPoller without the input source - use the setup function
+                            fd_poller = setup::initialize_fd_poller(&conn, &app_state);
+                            log::warn!("Input source removed due to FD error. Key press/release events will no longer be monitored.");
+                        }
+                        PollEvent::ConfigWatchError => {
+                            log::error!("Config watcher FD error/hangup reported by FdPoller. Configuration changes will no longer be reloaded.");
+                            app_state.config_watcher = None;
+                            fd_poller = setup::initialize_fd_poller(&conn, &app_state);
+                        }
+                        PollEvent::IpcError => {
+                            log::error!("IPC socket FD error/hangup reported by FdPoller. IPC control will stop.");
+                            app_state.ipc_server = None;
                             fd_poller = setup::initialize_fd_poller(&conn, &app_state);
-                            libinput_active = false; // Already false due to app_state.input_context = None
-                            log::warn!("Libinput context removed due to FD error. Key press/release events will no longer be monitored.");
                         }
                         PollEvent::Timeout => {
                             // Timeout is fine
@@ -193,6 +423,8 @@ fn main() {
             break;
         }
 
+        app_state.update_animations();
+
         if app_state.needs_redraw && app_state.frame_callback.is_none() {
             if let Some(surface) = app_state.surface.as_ref() {
                 log::debug!("Main loop: needs_redraw is true and no frame_callback pending, requesting frame callback.");
@@ -1,17 +1,45 @@
 // This module contains the FdPoller struct and related types for handling
 // polling of file descriptors using the `poll` syscall.
-// It supports Wayland and libinput file descriptors.
+// It supports the Wayland connection, the active input source's file descriptor,
+// the configuration file watcher, the IPC socket and its client connections.
 
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use wayland_client::Connection;
-use input::Libinput;
 
-// Indices for accessing file descriptors in the `fds` vector.
-const WAYLAND_FD_INDEX: usize = 0;
-const LIBINPUT_FD_INDEX: usize = 1;
-// NOTE: If more file descriptor types are added, this simple indexing approach
-// will need to be made more robust (e.g., using an enum to map FD types to indices
-// or a more dynamic structure).
+/// Identifies what a polled file descriptor belongs to.
+///
+/// Each entry in `FdPoller::fds` has a matching entry in `FdPoller::kinds`,
+/// which determines the `PollEvent` reported for it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FdKind {
+    Wayland,
+    Input,
+    ConfigWatch,
+    Ipc,
+    IpcClient,
+}
+
+impl FdKind {
+    fn ready_event(self) -> PollEvent {
+        match self {
+            FdKind::Wayland => PollEvent::WaylandReady,
+            FdKind::Input => PollEvent::InputReady,
+            FdKind::ConfigWatch => PollEvent::ConfigChanged,
+            FdKind::Ipc | FdKind::IpcClient => PollEvent::IpcReady,
+        }
+    }
+
+    fn error_event(self) -> PollEvent {
+        match self {
+            FdKind::Wayland => PollEvent::WaylandError,
+            FdKind::Input => PollEvent::InputError,
+            FdKind::ConfigWatch => PollEvent::ConfigWatchError,
+            FdKind::Ipc => PollEvent::IpcError,
+            // A client hanging up is noticed when reading its connection.
+            FdKind::IpcClient => PollEvent::IpcReady,
+        }
+    }
+}
 
 /// Error type for FdPoller creation.
 #[derive(Debug)]
@@ -38,7 +66,7 @@ impl std::error::Error for FdPollerCreationError {
 
 pub struct FdPoller {
     fds: Vec<libc::pollfd>,
-    has_libinput: bool,
+    kinds: Vec<FdKind>,
     // Keep the read guard alive for the lifetime of FdPoller if needed,
     // though for just getting the FD, it's not strictly necessary to store it.
     // _wayland_read_guard: Option<wayland_client::backend::ReadEventsGuard<'static>>, // This might be tricky with lifetimes
@@ -47,9 +75,13 @@ pub struct FdPoller {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PollEvent {
     WaylandReady,
-    LibinputReady,
+    InputReady,
+    ConfigChanged, // The config watcher has pending inotify events
+    IpcReady, // IPC clients are waiting to be accepted or have sent data
     WaylandError, // POLLERR or POLLHUP on Wayland FD
-    LibinputError, // POLLERR or POLLHUP on Libinput FD
+    InputError, // POLLERR or POLLHUP on the input source FD
+    ConfigWatchError, // POLLERR or POLLHUP on the config watcher FD
+    IpcError, // POLLERR or POLLHUP on the IPC socket FD
     Timeout,
     // Errors from the poll call itself are returned as Result::Err
 }
@@ -61,12 +93,11 @@ pub enum PollError {
 }
 
 impl FdPoller {
-    pub fn new(
-        wayland_conn: &Connection,
-        libinput_ctx: Option<&Libinput>,
-    ) -> Result<Self, FdPollerCreationError> {
-        let mut fds = Vec::with_capacity(2);
-
+    /// Creates a poller watching the Wayland connection.
+    ///
+    /// Additional file descriptors (e.g. the input source, config watcher or IPC socket) are registered
+    /// with `add_fd`.
+    pub fn new(wayland_conn: &Connection) -> Result<Self, FdPollerCreationError> {
         // Obtain Wayland FD. prepare_read() returns a guard.
         // The FD is valid as long as the Connection is valid.
         // We don't need to keep the guard itself if we're only getting the FD.
@@ -76,34 +107,27 @@ impl FdPoller {
             .connection_fd()
             .as_raw_fd();
 
-        // Setup pollfd for Wayland:
-        // - fd: The Wayland connection file descriptor.
+        let mut poller = FdPoller {
+            fds: Vec::with_capacity(4),
+            kinds: Vec::with_capacity(4),
+        };
+        poller.add_fd(FdKind::Wayland, wayland_fd);
+        Ok(poller)
+    }
+
+    /// Registers a file descriptor to be monitored for readability.
+    pub fn add_fd(&mut self, kind: FdKind, fd: RawFd) {
+        // Setup pollfd:
+        // - fd: The file descriptor to monitor.
         // - events: Flags specifying the events to monitor.
-        //   - libc::POLLIN: Monitor for readable data. This is the primary event for Wayland.
+        //   - libc::POLLIN: Monitor for readable data.
         // - revents: Output field, filled by poll() with events that occurred (e.g., POLLIN, POLLERR).
-        fds.push(libc::pollfd {
-            fd: wayland_fd,
+        self.fds.push(libc::pollfd {
+            fd,
             events: libc::POLLIN,
             revents: 0, // Must be initialized to 0 before calling poll()
         });
-
-        let libinput_raw_fd_opt = libinput_ctx.map(|ctx| ctx.as_raw_fd());
-
-        let has_libinput = if let Some(libinput_fd) = libinput_raw_fd_opt {
-            // Setup pollfd for libinput (if context exists):
-            // - fd: The libinput file descriptor.
-            // - events: libc::POLLIN to monitor for readable input events.
-            fds.push(libc::pollfd {
-                fd: libinput_fd,
-                events: libc::POLLIN,
-                revents: 0, // Must be initialized to 0
-            });
-            true
-        } else {
-            false
-        };
-
-        Ok(FdPoller { fds, has_libinput })
+        self.kinds.push(kind);
     }
 
     pub fn poll(&mut self, timeout_ms: i32) -> Result<Vec<PollEvent>, PollError> {
@@ -143,40 +167,33 @@ impl FdPoller {
         // If num_events > 0, one or more file descriptors have events.
         let mut events_triggered = Vec::with_capacity(num_events as usize);
 
-        // Check Wayland FD events.
+        // Check each FD's events.
         // `revents` contains the events that actually occurred for this FD.
         // - libc::POLLIN: Data is available to read.
         // - libc::POLLERR: An error occurred on the FD.
         // - libc::POLLHUP: Hang up occurred on the FD (e.g., connection closed).
         // - libc::POLLNVAL: Invalid request (e.g., fd not open). Should not happen with valid FDs.
-        let wayland_pfd = &self.fds[WAYLAND_FD_INDEX];
-        if (wayland_pfd.revents & libc::POLLERR) != 0 {
-            log::warn!("POLLERR on Wayland FD");
-            events_triggered.push(PollEvent::WaylandError);
-        } else if (wayland_pfd.revents & libc::POLLHUP) != 0 {
-            log::warn!("POLLHUP on Wayland FD");
-            events_triggered.push(PollEvent::WaylandError);
-        } else if (wayland_pfd.revents & libc::POLLNVAL) != 0 {
-            log::error!("POLLNVAL on Wayland FD - this indicates a serious issue!");
-            events_triggered.push(PollEvent::WaylandError); // Treat as error
-        } else if (wayland_pfd.revents & libc::POLLIN) != 0 {
-            events_triggered.push(PollEvent::WaylandReady);
-        }
-
-        // Check Libinput FD events, if it exists.
-        if self.has_libinput && self.fds.len() > LIBINPUT_FD_INDEX { // Ensure index is valid
-            let libinput_pfd = &self.fds[LIBINPUT_FD_INDEX];
-            if (libinput_pfd.revents & libc::POLLERR) != 0 {
-                log::warn!("POLLERR on Libinput FD");
-                events_triggered.push(PollEvent::LibinputError);
-            } else if (libinput_pfd.revents & libc::POLLHUP) != 0 {
-                log::warn!("POLLHUP on Libinput FD");
-                events_triggered.push(PollEvent::LibinputError);
-            } else if (libinput_pfd.revents & libc::POLLNVAL) != 0 {
-                log::error!("POLLNVAL on Libinput FD - this indicates a serious issue!");
-                events_triggered.push(PollEvent::LibinputError); // Treat as error
-            } else if (libinput_pfd.revents & libc::POLLIN) != 0 {
-                events_triggered.push(PollEvent::LibinputReady);
+        for (pfd, kind) in self.fds.iter().zip(self.kinds.iter()) {
+            if *kind == FdKind::IpcClient {
+                // Any event, including a hangup, means the connection needs
+                // serving; all connections are served for one `IpcReady`.
+                if pfd.revents != 0 && !events_triggered.contains(&PollEvent::IpcReady) {
+                    events_triggered.push(PollEvent::IpcReady);
+                }
+            } else if (pfd.revents & libc::POLLERR) != 0 {
+                log::warn!("POLLERR on {:?} FD", kind);
+                events_triggered.push(kind.error_event());
+            } else if (pfd.revents & libc::POLLHUP) != 0 {
+                log::warn!("POLLHUP on {:?} FD", kind);
+                events_triggered.push(kind.error_event());
+            } else if (pfd.revents & libc::POLLNVAL) != 0 {
+                log::error!(
+                    "POLLNVAL on {:?} FD - this indicates a serious issue!",
+                    kind
+                );
+                events_triggered.push(kind.error_event()); // Treat as error
+            } else if (pfd.revents & libc::POLLIN) != 0 {
+                events_triggered.push(kind.ready_event());
             }
         }
 
@@ -185,10 +202,9 @@ impl FdPoller {
             // (POLLIN, POLLERR, POLLHUP, POLLNVAL) were matched for the known FDs.
             // This is unexpected if FDs are correctly set up.
             log::warn!(
-                "poll() reported {} events, but no specific POLLIN/ERR/HUP/NVAL was handled for known FDs. Wayland revents: {:#X}, Libinput revents: {:#X}",
+                "poll() reported {} events, but no specific POLLIN/ERR/HUP/NVAL was handled for known FDs. revents: {:?}",
                 num_events,
-                wayland_pfd.revents,
-                if self.has_libinput && self.fds.len() > LIBINPUT_FD_INDEX { self.fds[LIBINPUT_FD_INDEX].revents } else { 0 }
+                self.fds.iter().zip(self.kinds.iter()).map(|(p, k)| (*k, p.revents)).collect::<Vec<_>>()
             );
         }
 
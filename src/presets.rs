@@ -0,0 +1,608 @@
+// src/presets.rs
+
+//! This module provides keyboard layouts built into the binary.
+//!
+//! Presets are generated from row tables in keyboard units (1u is the width
+//! of a letter key) and placed like imported KLE layouts: `kle::KLE_UNIT`
+//! layout units per 1u, with a `kle::KLE_GAP` gap between keys. They are
+//! used with `--layout`, and when no configuration file is found.
+
+use crate::config::{self, AppConfig, KeyConfig, KeyShape, LegendConfig};
+use crate::diagnostics::EntrySpans;
+use crate::keycodes::KeycodeRepr;
+use crate::kle::{KLE_GAP, KLE_UNIT};
+
+/// Layouts that can be selected with `--layout`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Full-size ANSI keyboard with 104 keys.
+    #[value(name = "ansi-104")]
+    Ansi104,
+    /// Full-size ISO keyboard with 105 keys.
+    #[value(name = "iso-105")]
+    Iso105,
+    /// ANSI tenkeyless (87 keys, no numpad).
+    Tkl,
+    /// Compact ANSI 75% layout (84 keys).
+    #[value(name = "75")]
+    Compact75,
+    /// ANSI 60% layout (61 keys).
+    #[value(name = "60")]
+    Compact60,
+    /// Stand-alone numeric keypad (17 keys).
+    Numpad,
+    /// Split ortholinear keyboard, 4x6 keys per half plus 4 thumb keys per half.
+    SplitOrtho,
+}
+
+/// Preset used when no configuration file is found.
+pub const DEFAULT_PRESET: Preset = Preset::Ansi104;
+
+/// A key in a row table: name, keycode and width in units.
+/// An empty keycode leaves a gap of the given width.
+type RowKey = (&'static str, &'static str, f32);
+
+/// Gap between the function row and the rest of the keyboard, in units.
+const FUNCTION_ROW_GAP: f32 = 0.5;
+/// Gap between the main block, the navigation cluster and the numpad, in units.
+const CLUSTER_GAP: f32 = 0.25;
+
+const FUNCTION_ROW: &[RowKey] = &[
+    ("Esc", "esc", 1.0),
+    ("", "", 1.0),
+    ("F1", "f1", 1.0),
+    ("F2", "f2", 1.0),
+    ("F3", "f3", 1.0),
+    ("F4", "f4", 1.0),
+    ("", "", 0.5),
+    ("F5", "f5", 1.0),
+    ("F6", "f6", 1.0),
+    ("F7", "f7", 1.0),
+    ("F8", "f8", 1.0),
+    ("", "", 0.5),
+    ("F9", "f9", 1.0),
+    ("F10", "f10", 1.0),
+    ("F11", "f11", 1.0),
+    ("F12", "f12", 1.0),
+];
+
+const DIGITS: &[RowKey] = &[
+    ("1", "1", 1.0),
+    ("2", "2", 1.0),
+    ("3", "3", 1.0),
+    ("4", "4", 1.0),
+    ("5", "5", 1.0),
+    ("6", "6", 1.0),
+    ("7", "7", 1.0),
+    ("8", "8", 1.0),
+    ("9", "9", 1.0),
+    ("0", "0", 1.0),
+    ("-", "minus", 1.0),
+    ("=", "equal", 1.0),
+];
+
+const TOP_LETTERS: &[RowKey] = &[
+    ("Q", "q", 1.0),
+    ("W", "w", 1.0),
+    ("E", "e", 1.0),
+    ("R", "r", 1.0),
+    ("T", "t", 1.0),
+    ("Y", "y", 1.0),
+    ("U", "u", 1.0),
+    ("I", "i", 1.0),
+    ("O", "o", 1.0),
+    ("P", "p", 1.0),
+    ("[", "leftbrace", 1.0),
+    ("]", "rightbrace", 1.0),
+];
+
+const HOME_LETTERS: &[RowKey] = &[
+    ("A", "a", 1.0),
+    ("S", "s", 1.0),
+    ("D", "d", 1.0),
+    ("F", "f", 1.0),
+    ("G", "g", 1.0),
+    ("H", "h", 1.0),
+    ("J", "j", 1.0),
+    ("K", "k", 1.0),
+    ("L", "l", 1.0),
+    (";", "semicolon", 1.0),
+    ("'", "apostrophe", 1.0),
+];
+
+const BOTTOM_LETTERS: &[RowKey] = &[
+    ("Z", "z", 1.0),
+    ("X", "x", 1.0),
+    ("C", "c", 1.0),
+    ("V", "v", 1.0),
+    ("B", "b", 1.0),
+    ("N", "n", 1.0),
+    ("M", "m", 1.0),
+    (",", "comma", 1.0),
+    (".", "dot", 1.0),
+    ("/", "slash", 1.0),
+];
+
+const FULL_BOTTOM_ROW: &[RowKey] = &[
+    ("Ctrl", "leftctrl", 1.25),
+    ("Super", "leftmeta", 1.25),
+    ("Alt", "leftalt", 1.25),
+    ("Space", "space", 6.25),
+    ("AltGr", "rightalt", 1.25),
+    ("Super", "rightmeta", 1.25),
+    ("Menu", "compose", 1.25),
+    ("Ctrl", "rightctrl", 1.25),
+];
+
+/// Shifted symbols of a US layout, by keycode.
+const US_SHIFT_LEGENDS: &[(&str, &str)] = &[
+    ("grave", "~"),
+    ("1", "!"),
+    ("2", "@"),
+    ("3", "#"),
+    ("4", "$"),
+    ("5", "%"),
+    ("6", "^"),
+    ("7", "&"),
+    ("8", "*"),
+    ("9", "("),
+    ("0", ")"),
+    ("minus", "_"),
+    ("equal", "+"),
+    ("leftbrace", "{"),
+    ("rightbrace", "}"),
+    ("backslash", "|"),
+    ("semicolon", ":"),
+    ("apostrophe", "\""),
+    ("comma", "<"),
+    ("dot", ">"),
+    ("slash", "?"),
+];
+
+/// Shifted symbols of a UK layout, by keycode.
+const UK_SHIFT_LEGENDS: &[(&str, &str)] = &[
+    ("grave", "¬"),
+    ("1", "!"),
+    ("2", "\""),
+    ("3", "£"),
+    ("4", "$"),
+    ("5", "%"),
+    ("6", "^"),
+    ("7", "&"),
+    ("8", "*"),
+    ("9", "("),
+    ("0", ")"),
+    ("minus", "_"),
+    ("equal", "+"),
+    ("leftbrace", "{"),
+    ("rightbrace", "}"),
+    ("backslash", "~"),
+    ("102nd", "|"),
+    ("semicolon", ":"),
+    ("apostrophe", "@"),
+    ("comma", "<"),
+    ("dot", ">"),
+    ("slash", "?"),
+];
+
+/// Places keys row by row, in units.
+struct LayoutBuilder {
+    keys: Vec<KeyConfig>,
+    x: f32,
+    y: f32,
+}
+
+impl LayoutBuilder {
+    fn new() -> Self {
+        LayoutBuilder {
+            keys: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+        }
+    }
+
+    /// Continues at `(x, y)`.
+    fn at(&mut self, x: f32, y: f32) -> &mut Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Places keys left to right from the current position.
+    fn row(&mut self, keys: &[RowKey]) -> &mut Self {
+        for &(name, keycode, width) in keys {
+            if !keycode.is_empty() {
+                self.key(name, keycode, width, 1.0);
+            }
+            self.x += width;
+        }
+        self
+    }
+
+    /// Places one key at the current position, without advancing.
+    fn key(&mut self, name: &str, keycode: &str, width: f32, height: f32) -> &mut Self {
+        self.keys.push(KeyConfig {
+            name: name.to_string(),
+            name_derived: false,
+            left: self.x * KLE_UNIT,
+            top: self.y * KLE_UNIT,
+            width: width * KLE_UNIT - KLE_GAP,
+            height: height * KLE_UNIT - KLE_GAP,
+            raw_keycode: Some(KeycodeRepr::Text(keycode.to_string())),
+            keycode: 0,
+            rotation_degrees: None,
+            shape: None,
+            style: None,
+            text_size: None,
+            corner_radius: None,
+            border_thickness: None,
+            background_color: None,
+            text_color: None,
+            outline_color: None,
+            active_background_color: None,
+            active_text_color: None,
+            active_outline_color: None,
+            font: None,
+            legend_position: None,
+            shift_legend: None,
+            altgr_legend: None,
+            fn_legend: None,
+            spans: EntrySpans::default(),
+        });
+        self
+    }
+
+    /// Makes the last placed key a union of rectangles, given in key units
+    /// relative to its top left as `[x, y, width, height]`.
+    fn shape(&mut self, rects: &[[f32; 4]]) -> &mut Self {
+        if let Some(key) = self.keys.last_mut() {
+            let to_layout = |r: &[f32; 4]| {
+                [
+                    r[0] * KLE_UNIT,
+                    r[1] * KLE_UNIT,
+                    r[2] * KLE_UNIT - KLE_GAP,
+                    r[3] * KLE_UNIT - KLE_GAP,
+                ]
+            };
+            key.shape = Some(KeyShape::Rects(rects.iter().map(to_layout).collect()));
+        }
+        self
+    }
+
+    /// Sets the Shift legends of the keys listed in `legends`.
+    fn shift_legends(&mut self, legends: &[(&str, &str)]) -> &mut Self {
+        for key in &mut self.keys {
+            let Some(KeycodeRepr::Text(keycode)) = &key.raw_keycode else {
+                continue;
+            };
+            if let Some((_, legend)) = legends.iter().find(|(k, _)| k == keycode) {
+                key.shift_legend = Some(LegendConfig::Text(legend.to_string()));
+            }
+        }
+        self
+    }
+
+    fn build(&mut self) -> Vec<KeyConfig> {
+        std::mem::take(&mut self.keys)
+    }
+}
+
+/// Places the ANSI alphanumeric block (5 rows, 15u wide) with its top left at `(0, y)`.
+///
+/// `first_key` is the key left of `1`, i.e. `` ` `` or Esc on a 60% board.
+/// A right Shift narrower than 2.75u leaves room on the right of the Shift row.
+fn ansi_main_block(
+    b: &mut LayoutBuilder,
+    y: f32,
+    first_key: RowKey,
+    right_shift_width: f32,
+    bottom_row: &[RowKey],
+) {
+    b.at(0.0, y)
+        .row(&[first_key])
+        .row(DIGITS)
+        .row(&[("Backspace", "backspace", 2.0)]);
+    b.at(0.0, y + 1.0)
+        .row(&[("Tab", "tab", 1.5)])
+        .row(TOP_LETTERS)
+        .row(&[("\\", "backslash", 1.5)]);
+    b.at(0.0, y + 2.0)
+        .row(&[("Caps Lock", "capslock", 1.75)])
+        .row(HOME_LETTERS)
+        .row(&[("Enter", "enter", 2.25)]);
+    b.at(0.0, y + 3.0)
+        .row(&[("Shift", "leftshift", 2.25)])
+        .row(BOTTOM_LETTERS)
+        .row(&[("Shift", "rightshift", right_shift_width)]);
+    b.at(0.0, y + 4.0).row(bottom_row);
+}
+
+/// Places the ISO alphanumeric block (5 rows, 15u wide) with its top left at `(0, y)`.
+fn iso_main_block(b: &mut LayoutBuilder, y: f32) {
+    b.at(0.0, y)
+        .row(&[("`", "grave", 1.0)])
+        .row(DIGITS)
+        .row(&[("Backspace", "backspace", 2.0)]);
+    b.at(0.0, y + 1.0)
+        .row(&[("Tab", "tab", 1.5)])
+        .row(TOP_LETTERS);
+    b.at(13.5, y + 1.0)
+        .key("Enter", "enter", 1.5, 2.0)
+        .shape(&[[0.0, 0.0, 1.5, 1.0], [0.25, 0.0, 1.25, 2.0]]);
+    b.at(0.0, y + 2.0)
+        .row(&[("Caps Lock", "capslock", 1.75)])
+        .row(HOME_LETTERS)
+        .row(&[("#", "backslash", 1.0)]);
+    b.at(0.0, y + 3.0)
+        .row(&[("Shift", "leftshift", 1.25), ("\\", "102nd", 1.0)])
+        .row(BOTTOM_LETTERS)
+        .row(&[("Shift", "rightshift", 2.75)]);
+    b.at(0.0, y + 4.0).row(FULL_BOTTOM_ROW);
+}
+
+/// Places the function row, navigation cluster and arrows of a full-size or TKL board.
+fn function_and_navigation(b: &mut LayoutBuilder) {
+    let nav_x = 15.0 + CLUSTER_GAP;
+    let main_y = 1.0 + FUNCTION_ROW_GAP;
+    b.at(0.0, 0.0).row(FUNCTION_ROW);
+    b.at(nav_x, 0.0).row(&[
+        ("PrtSc", "sysrq", 1.0),
+        ("ScrLk", "scrolllock", 1.0),
+        ("Pause", "pause", 1.0),
+    ]);
+    b.at(nav_x, main_y).row(&[
+        ("Ins", "insert", 1.0),
+        ("Home", "home", 1.0),
+        ("PgUp", "pageup", 1.0),
+    ]);
+    b.at(nav_x, main_y + 1.0).row(&[
+        ("Del", "delete", 1.0),
+        ("End", "end", 1.0),
+        ("PgDn", "pagedown", 1.0),
+    ]);
+    b.at(nav_x + 1.0, main_y + 3.0).row(&[("↑", "up", 1.0)]);
+    b.at(nav_x, main_y + 4.0)
+        .row(&[("←", "left", 1.0), ("↓", "down", 1.0), ("→", "right", 1.0)]);
+}
+
+/// Places a numeric keypad (5 rows, 4u wide) with its top left at `(x, y)`.
+fn numpad(b: &mut LayoutBuilder, x: f32, y: f32) {
+    b.at(x, y).row(&[
+        ("Num", "numlock", 1.0),
+        ("/", "kpslash", 1.0),
+        ("*", "kpasterisk", 1.0),
+        ("-", "kpminus", 1.0),
+    ]);
+    b.at(x, y + 1.0)
+        .row(&[("7", "kp7", 1.0), ("8", "kp8", 1.0), ("9", "kp9", 1.0)]);
+    b.at(x + 3.0, y + 1.0).key("+", "kpplus", 1.0, 2.0);
+    b.at(x, y + 2.0)
+        .row(&[("4", "kp4", 1.0), ("5", "kp5", 1.0), ("6", "kp6", 1.0)]);
+    b.at(x, y + 3.0)
+        .row(&[("1", "kp1", 1.0), ("2", "kp2", 1.0), ("3", "kp3", 1.0)]);
+    b.at(x + 3.0, y + 3.0).key("Enter", "kpenter", 1.0, 2.0);
+    b.at(x, y + 4.0)
+        .row(&[("0", "kp0", 2.0), (".", "kpdot", 1.0)]);
+}
+
+/// Generates the keys of a preset.
+fn preset_keys(preset: Preset) -> Vec<KeyConfig> {
+    let mut b = LayoutBuilder::new();
+    let main_y = 1.0 + FUNCTION_ROW_GAP;
+    let numpad_x = 18.0 + 2.0 * CLUSTER_GAP;
+    match preset {
+        Preset::Ansi104 | Preset::Tkl => {
+            function_and_navigation(&mut b);
+            ansi_main_block(&mut b, main_y, ("`", "grave", 1.0), 2.75, FULL_BOTTOM_ROW);
+            if preset == Preset::Ansi104 {
+                numpad(&mut b, numpad_x, main_y);
+            }
+            b.shift_legends(US_SHIFT_LEGENDS);
+        }
+        Preset::Iso105 => {
+            function_and_navigation(&mut b);
+            iso_main_block(&mut b, main_y);
+            numpad(&mut b, numpad_x, main_y);
+            b.shift_legends(UK_SHIFT_LEGENDS);
+        }
+        Preset::Compact75 => {
+            b.at(0.0, 0.0)
+                .row(
+                    FUNCTION_ROW
+                        .iter()
+                        .filter(|k| !k.1.is_empty())
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .as_slice(),
+                )
+                .row(&[
+                    ("PrtSc", "sysrq", 1.0),
+                    ("Ins", "insert", 1.0),
+                    ("Del", "delete", 1.0),
+                ]);
+            ansi_main_block(
+                &mut b,
+                1.0,
+                ("`", "grave", 1.0),
+                1.75,
+                &[
+                    ("Ctrl", "leftctrl", 1.25),
+                    ("Super", "leftmeta", 1.25),
+                    ("Alt", "leftalt", 1.25),
+                    ("Space", "space", 6.25),
+                    ("AltGr", "rightalt", 1.0),
+                    ("Fn", "fn", 1.0),
+                    ("Ctrl", "rightctrl", 1.0),
+                ],
+            );
+            b.at(14.0, 4.0).row(&[("↑", "up", 1.0)]);
+            b.at(13.0, 5.0)
+                .row(&[("←", "left", 1.0), ("↓", "down", 1.0), ("→", "right", 1.0)]);
+            for (row, (name, keycode)) in [
+                ("Home", "home"),
+                ("PgUp", "pageup"),
+                ("PgDn", "pagedown"),
+                ("End", "end"),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                b.at(15.0, 1.0 + row as f32).row(&[(name, keycode, 1.0)]);
+            }
+            b.shift_legends(US_SHIFT_LEGENDS);
+        }
+        Preset::Compact60 => {
+            ansi_main_block(
+                &mut b,
+                0.0,
+                ("Esc", "esc", 1.0),
+                2.75,
+                &[
+                    ("Ctrl", "leftctrl", 1.25),
+                    ("Super", "leftmeta", 1.25),
+                    ("Alt", "leftalt", 1.25),
+                    ("Space", "space", 6.25),
+                    ("AltGr", "rightalt", 1.25),
+                    ("Fn", "fn", 1.25),
+                    ("Menu", "compose", 1.25),
+                    ("Ctrl", "rightctrl", 1.25),
+                ],
+            );
+            b.shift_legends(US_SHIFT_LEGENDS);
+        }
+        Preset::Numpad => numpad(&mut b, 0.0, 0.0),
+        Preset::SplitOrtho => {
+            let right_x = 7.5;
+            let rows: [[RowKey; 12]; 4] = [
+                [
+                    ("`", "grave", 1.0),
+                    ("1", "1", 1.0),
+                    ("2", "2", 1.0),
+                    ("3", "3", 1.0),
+                    ("4", "4", 1.0),
+                    ("5", "5", 1.0),
+                    ("6", "6", 1.0),
+                    ("7", "7", 1.0),
+                    ("8", "8", 1.0),
+                    ("9", "9", 1.0),
+                    ("0", "0", 1.0),
+                    ("-", "minus", 1.0),
+                ],
+                [
+                    ("Tab", "tab", 1.0),
+                    ("Q", "q", 1.0),
+                    ("W", "w", 1.0),
+                    ("E", "e", 1.0),
+                    ("R", "r", 1.0),
+                    ("T", "t", 1.0),
+                    ("Y", "y", 1.0),
+                    ("U", "u", 1.0),
+                    ("I", "i", 1.0),
+                    ("O", "o", 1.0),
+                    ("P", "p", 1.0),
+                    ("=", "equal", 1.0),
+                ],
+                [
+                    ("Esc", "esc", 1.0),
+                    ("A", "a", 1.0),
+                    ("S", "s", 1.0),
+                    ("D", "d", 1.0),
+                    ("F", "f", 1.0),
+                    ("G", "g", 1.0),
+                    ("H", "h", 1.0),
+                    ("J", "j", 1.0),
+                    ("K", "k", 1.0),
+                    ("L", "l", 1.0),
+                    (";", "semicolon", 1.0),
+                    ("'", "apostrophe", 1.0),
+                ],
+                [
+                    ("Shift", "leftshift", 1.0),
+                    ("Z", "z", 1.0),
+                    ("X", "x", 1.0),
+                    ("C", "c", 1.0),
+                    ("V", "v", 1.0),
+                    ("B", "b", 1.0),
+                    ("N", "n", 1.0),
+                    ("M", "m", 1.0),
+                    (",", "comma", 1.0),
+                    (".", "dot", 1.0),
+                    ("/", "slash", 1.0),
+                    ("Shift", "rightshift", 1.0),
+                ],
+            ];
+            for (y, row) in rows.iter().enumerate() {
+                b.at(0.0, y as f32).row(&row[..6]);
+                b.at(right_x, y as f32).row(&row[6..]);
+            }
+            b.at(2.0, 4.0).row(&[
+                ("Ctrl", "leftctrl", 1.0),
+                ("Super", "leftmeta", 1.0),
+                ("Alt", "leftalt", 1.0),
+                ("Space", "space", 1.0),
+            ]);
+            b.at(right_x, 4.0).row(&[
+                ("Enter", "enter", 1.0),
+                ("Bksp", "backspace", 1.0),
+                ("AltGr", "rightalt", 1.0),
+                ("Del", "delete", 1.0),
+            ]);
+            b.shift_legends(US_SHIFT_LEGENDS);
+        }
+    }
+    b.build()
+}
+
+/// Returns the processed configuration of a preset, with default overlay settings.
+pub fn preset_config(preset: Preset) -> AppConfig {
+    let app_config = AppConfig {
+        include: Vec::new(),
+        key: preset_keys(preset),
+        overlay: Default::default(),
+        keymap: None,
+        layer: Vec::new(),
+        style: Default::default(),
+        spans: EntrySpans::default(),
+        included_files: Vec::new(),
+    };
+    // Presets only use known key names, so processing cannot fail.
+    config::process_config(app_config, "layout preset")
+        .unwrap_or_else(|diagnostics| panic!("built-in layout preset is invalid:\n{}", diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::validate_config;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_presets_are_valid() {
+        let expected_key_counts = [
+            (Preset::Ansi104, 104),
+            (Preset::Iso105, 105),
+            (Preset::Tkl, 87),
+            (Preset::Compact75, 84),
+            (Preset::Compact60, 61),
+            (Preset::Numpad, 17),
+            (Preset::SplitOrtho, 56),
+        ];
+        assert_eq!(expected_key_counts.len(), Preset::value_variants().len());
+        for (preset, count) in expected_key_counts {
+            let config = preset_config(preset);
+            assert_eq!(config.key.len(), count, "{:?}", preset);
+            let diagnostics = validate_config(&config);
+            assert!(diagnostics.is_empty(), "{:?}: {}", preset, diagnostics);
+        }
+    }
+
+    #[test]
+    fn test_preset_dump_roundtrip() {
+        let config = preset_config(Preset::Iso105);
+        let toml = config::to_toml_string(&config).unwrap();
+        let reloaded = config::process_config_str(&toml, "dump", None).unwrap();
+        let keycodes = |c: &AppConfig| c.key.iter().map(|k| k.keycode).collect::<Vec<_>>();
+        assert_eq!(keycodes(&reloaded), keycodes(&config));
+        assert_eq!(reloaded.key[0].left, config.key[0].left);
+    }
+}
@@ -0,0 +1,542 @@
+// src/qmk.rs
+
+//! This module imports layouts from QMK `info.json` files and VIA definitions.
+//!
+//! Both formats describe the physical keys together with their position in
+//! the keyboard's switch matrix: QMK lists them per layout macro under
+//! `layouts`, VIA embeds a KLE layout whose keys are labelled `"row,col"`.
+//! Neither says which key sends what; that comes from a keymap, either a QMK
+//! `keymap.json` (keycodes in layout order) or a VIA keymap backup (keycodes
+//! in matrix order). QMK keycodes such as `KC_A` or `KC_LSFT` are mapped to
+//! the key names understood by `keycodes::get_keycode_from_string`.
+//!
+//! Without a keymap, keycodes are guessed from the key labels of an
+//! `info.json`, like for KLE imports.
+
+use serde_json::Value;
+use std::fs;
+
+use crate::config::{self, AppConfig, KeyConfig};
+use crate::diagnostics::EntrySpans;
+use crate::keycodes::{get_keycode_from_string, KeycodeRepr};
+use crate::kle::{self, KleKey};
+
+/// QMK basic keycodes (without the `KC_` prefix) whose names differ from the
+/// key names used here, with the key name and the label to show.
+///
+/// Letters, digits, F-keys and keycodes whose long QMK name is also a key
+/// name here (e.g. `KC_BACKSPACE`) are handled without this table.
+const QMK_KEYCODES: &[(&[&str], &str, &str)] = &[
+    (&["ENT", "ENTER"], "enter", "Enter"),
+    (&["ESC", "ESCAPE"], "esc", "Esc"),
+    (&["BSPC", "BACKSPACE"], "backspace", "Backspace"),
+    (&["TAB"], "tab", "Tab"),
+    (&["SPC", "SPACE"], "space", "Space"),
+    (&["MINS", "MINUS"], "minus", "-"),
+    (&["EQL", "EQUAL"], "equal", "="),
+    (&["LBRC", "LEFT_BRACKET"], "leftbrace", "["),
+    (&["RBRC", "RIGHT_BRACKET"], "rightbrace", "]"),
+    (&["BSLS", "BACKSLASH"], "backslash", "\\"),
+    (&["NUHS", "NONUS_HASH"], "backslash", "#"),
+    (&["SCLN", "SEMICOLON"], "semicolon", ";"),
+    (&["QUOT", "QUOTE"], "apostrophe", "'"),
+    (&["GRV", "GRAVE"], "grave", "`"),
+    (&["COMM", "COMMA"], "comma", ","),
+    (&["DOT"], "dot", "."),
+    (&["SLSH", "SLASH"], "slash", "/"),
+    (&["NUBS", "NONUS_BACKSLASH"], "102nd", "\\"),
+    (&["CAPS", "CAPS_LOCK"], "capslock", "Caps Lock"),
+    (&["PSCR", "PRINT_SCREEN"], "sysrq", "PrtSc"),
+    (&["SCRL", "SCROLL_LOCK"], "scrolllock", "ScrLk"),
+    (&["PAUS", "BRK", "PAUSE"], "pause", "Pause"),
+    (&["INS", "INSERT"], "insert", "Ins"),
+    (&["HOME"], "home", "Home"),
+    (&["PGUP", "PAGE_UP"], "pageup", "PgUp"),
+    (&["DEL", "DELETE"], "delete", "Del"),
+    (&["END"], "end", "End"),
+    (&["PGDN", "PAGE_DOWN"], "pagedown", "PgDn"),
+    (&["RGHT", "RIGHT"], "right", "→"),
+    (&["LEFT"], "left", "←"),
+    (&["DOWN"], "down", "↓"),
+    (&["UP"], "up", "↑"),
+    (&["NUM", "NUM_LOCK"], "numlock", "Num Lock"),
+    (&["PSLS", "KP_SLASH"], "kpslash", "/"),
+    (&["PAST", "KP_ASTERISK"], "kpasterisk", "*"),
+    (&["PMNS", "KP_MINUS"], "kpminus", "-"),
+    (&["PPLS", "KP_PLUS"], "kpplus", "+"),
+    (&["PENT", "KP_ENTER"], "kpenter", "Enter"),
+    (&["P1", "KP_1"], "kp1", "1"),
+    (&["P2", "KP_2"], "kp2", "2"),
+    (&["P3", "KP_3"], "kp3", "3"),
+    (&["P4", "KP_4"], "kp4", "4"),
+    (&["P5", "KP_5"], "kp5", "5"),
+    (&["P6", "KP_6"], "kp6", "6"),
+    (&["P7", "KP_7"], "kp7", "7"),
+    (&["P8", "KP_8"], "kp8", "8"),
+    (&["P9", "KP_9"], "kp9", "9"),
+    (&["P0", "KP_0"], "kp0", "0"),
+    (&["PDOT", "KP_DOT"], "kpdot", "."),
+    (&["PEQL", "KP_EQUAL"], "kpequal", "="),
+    (&["PCMM", "KP_COMMA"], "kpcomma", ","),
+    (&["APP", "APPLICATION"], "compose", "Menu"),
+    (&["LCTL", "LEFT_CTRL"], "leftctrl", "Ctrl"),
+    (&["LSFT", "LEFT_SHIFT"], "leftshift", "Shift"),
+    (&["LALT", "LOPT", "LEFT_ALT"], "leftalt", "Alt"),
+    (&["LGUI", "LCMD", "LWIN", "LEFT_GUI"], "leftmeta", "Super"),
+    (&["RCTL", "RIGHT_CTRL"], "rightctrl", "Ctrl"),
+    (&["RSFT", "RIGHT_SHIFT"], "rightshift", "Shift"),
+    (&["RALT", "ROPT", "ALGR", "RIGHT_ALT"], "rightalt", "AltGr"),
+    (&["RGUI", "RCMD", "RWIN", "RIGHT_GUI"], "rightmeta", "Super"),
+    (&["MUTE", "AUDIO_MUTE"], "mute", "Mute"),
+    (&["VOLU", "AUDIO_VOL_UP"], "volumeup", "Vol+"),
+    (&["VOLD", "AUDIO_VOL_DOWN"], "volumedown", "Vol-"),
+    (&["MNXT", "MEDIA_NEXT_TRACK"], "nextsong", "Next"),
+    (&["MPRV", "MEDIA_PREV_TRACK"], "previoussong", "Prev"),
+    (&["MSTP", "MEDIA_STOP"], "stopcd", "Stop"),
+    (&["MPLY", "MEDIA_PLAY_PAUSE"], "playpause", "Play"),
+    (&["CALC", "CALCULATOR"], "calc", "Calc"),
+    (&["WSCH", "WWW_SEARCH"], "search", "Search"),
+    (&["BRIU", "BRIGHTNESS_UP"], "brightnessup", "Bri+"),
+    (&["BRID", "BRIGHTNESS_DOWN"], "brightnessdown", "Bri-"),
+    (&["INT1", "INTERNATIONAL_1"], "ro", "Ro"),
+    (&["INT3", "INTERNATIONAL_3"], "yen", "¥"),
+];
+
+/// Maps a QMK keycode to a key name and label.
+///
+/// Keycodes wrapping a basic keycode, like mod-taps (`LCTL_T(KC_ESC)`),
+/// layer-taps (`LT(1, KC_SPC)`) or modified keys (`LSFT(KC_1)`), map to the
+/// wrapped keycode, which is what the OSD sees when the key is tapped.
+/// Keycodes without a Linux equivalent (`MO(1)`, `KC_TRNS`, `QK_BOOT`, ...)
+/// return `None`.
+pub fn qmk_keycode(code: &str) -> Option<(String, String)> {
+    let start = code.rfind("KC_")?;
+    let basic: String = code[start + 3..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    if let Some((_, name, label)) = QMK_KEYCODES
+        .iter()
+        .find(|(codes, _, _)| codes.contains(&basic.as_str()))
+    {
+        return Some((name.to_string(), label.to_string()));
+    }
+    let is_function_key =
+        basic.len() > 1 && basic.starts_with('F') && basic[1..].chars().all(|c| c.is_ascii_digit());
+    if basic.chars().count() == 1 || is_function_key {
+        let name = basic.to_lowercase();
+        return get_keycode_from_string(&name)
+            .is_ok()
+            .then_some((name, basic));
+    }
+    // Long names like KC_BACKSPACE; KC_NO and KC_TRNS don't match any key.
+    let name = basic.to_lowercase();
+    get_keycode_from_string(&name).ok()?;
+    let mut chars = name.chars();
+    let label = chars
+        .next()?
+        .to_uppercase()
+        .chain(chars)
+        .collect::<String>()
+        .replace('_', " ");
+    Some((name, label))
+}
+
+/// A physical key of the keyboard and its matrix position.
+struct MatrixKey {
+    key: KleKey,
+    row: usize,
+    col: usize,
+}
+
+/// Reads an `info.json` or VIA definition, and optionally a keymap, and converts them to a configuration.
+pub fn import_qmk_file(
+    path: &str,
+    keymap_path: Option<&str>,
+    layout_name: Option<&str>,
+) -> Result<AppConfig, String> {
+    let read = |path: &str| {
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))
+    };
+    let content = read(path)?;
+    let keymap = keymap_path.map(read).transpose()?;
+    import_qmk_str(&content, keymap.as_deref(), layout_name)
+        .map_err(|e| format!("Failed to import '{}': {}", path, e))
+}
+
+/// Converts an `info.json` or VIA definition, and optionally a keymap, to a processed configuration.
+///
+/// `layout_name` selects a layout macro of an `info.json`; by default the
+/// keymap's layout, `LAYOUT`, or the first layout is used. Keys that send no
+/// keycode the OSD could see are left out with a warning.
+pub fn import_qmk_str(
+    content: &str,
+    keymap: Option<&str>,
+    layout_name: Option<&str>,
+) -> Result<AppConfig, String> {
+    let definition: Value =
+        serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let keymap: Option<Value> = keymap
+        .map(|k| serde_json::from_str(k).map_err(|e| format!("Invalid keymap JSON: {}", e)))
+        .transpose()?;
+    let keymap_layout = keymap
+        .as_ref()
+        .and_then(|k| k.get("layout"))
+        .and_then(Value::as_str);
+
+    let is_via = definition.pointer("/layouts/keymap").is_some();
+    let (matrix_keys, matrix_size) = if is_via {
+        via_keys(&definition)?
+    } else {
+        info_json_keys(&definition, layout_name.or(keymap_layout))?
+    };
+    if matrix_keys.is_empty() {
+        return Err("The layout contains no keys.".to_string());
+    }
+
+    let keycodes = match &keymap {
+        Some(keymap) => Some(base_layer_keycodes(
+            keymap,
+            &matrix_keys,
+            matrix_size,
+            is_via,
+        )?),
+        None if is_via => {
+            return Err("VIA definitions contain no keycodes; pass a keymap as well.".to_string())
+        }
+        None => None,
+    };
+
+    let mut keys: Vec<KeyConfig> = matrix_keys
+        .iter()
+        .map(|m| kle::convert_key(&m.key))
+        .collect();
+    let left_halves = kle::left_halves(&keys);
+    let mut skipped = Vec::new();
+    for (index, key) in keys.iter_mut().enumerate() {
+        let resolved = match &keycodes {
+            Some(keycodes) => qmk_keycode(&keycodes[index]).map(|(name, label)| {
+                key.name = label;
+                name
+            }),
+            None => kle::guess_keycode(&matrix_keys[index].key, left_halves[index]),
+        };
+        match resolved {
+            Some(name) => key.raw_keycode = Some(KeycodeRepr::Text(name)),
+            None => skipped.push(index),
+        }
+    }
+    for &index in &skipped {
+        let MatrixKey { row, col, .. } = matrix_keys[index];
+        let what = match &keycodes {
+            Some(keycodes) => format!("sends '{}'", keycodes[index]),
+            None => format!("is labelled '{}'", keys[index].name),
+        };
+        log::warn!(
+            "Leaving out the key at matrix position {},{}: it {}, which has no keycode here.",
+            row,
+            col,
+            what
+        );
+    }
+    let mut keys: Vec<KeyConfig> = keys
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !skipped.contains(index))
+        .map(|(_, key)| key)
+        .collect();
+    kle::move_to_origin(&mut keys);
+
+    let app_config = AppConfig {
+        include: Vec::new(),
+        key: keys,
+        overlay: Default::default(),
+        keymap: None,
+        layer: Vec::new(),
+        style: Default::default(),
+        spans: EntrySpans::default(),
+        included_files: Vec::new(),
+    };
+    config::process_config(app_config, "imported layout")
+        .map_err(|diagnostics| diagnostics.to_string())
+}
+
+/// Reads a numeric field of a QMK layout entry.
+fn number(entry: &Value, name: &str, default: f32) -> f32 {
+    entry
+        .get(name)
+        .and_then(Value::as_f64)
+        .map_or(default, |v| v as f32)
+}
+
+/// Returns the matrix size as `(rows, cols)`, from `pointer` or from the keys' positions.
+fn matrix_size(definition: &Value, pointer: &str, keys: &[MatrixKey]) -> (usize, usize) {
+    let dimension = |name: &str, from_keys: usize| {
+        definition
+            .pointer(&format!("{}/{}", pointer, name))
+            .and_then(Value::as_u64)
+            .map_or(from_keys, |v| v as usize)
+    };
+    (
+        dimension("rows", keys.iter().map(|k| k.row + 1).max().unwrap_or(0)),
+        dimension("cols", keys.iter().map(|k| k.col + 1).max().unwrap_or(0)),
+    )
+}
+
+/// Returns the keys of a layout macro of an `info.json`, and the matrix size.
+fn info_json_keys(
+    definition: &Value,
+    layout_name: Option<&str>,
+) -> Result<(Vec<MatrixKey>, (usize, usize)), String> {
+    let layouts = definition
+        .get("layouts")
+        .and_then(Value::as_object)
+        .ok_or("Neither a QMK info.json (no 'layouts' object) nor a VIA definition.")?;
+    let (name, layout) = match layout_name {
+        Some(name) => layouts.get_key_value(name).ok_or_else(|| {
+            let names: Vec<&str> = layouts.keys().map(String::as_str).collect();
+            format!(
+                "No layout '{}'; available layouts: {}.",
+                name,
+                names.join(", ")
+            )
+        })?,
+        None => layouts
+            .get_key_value("LAYOUT")
+            .or_else(|| layouts.iter().next())
+            .ok_or("The info.json defines no layouts.")?,
+    };
+    let entries = layout
+        .get("layout")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("Layout '{}' has no 'layout' array.", name))?;
+
+    let mut keys = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let matrix: Vec<usize> = entry
+            .get("matrix")
+            .and_then(Value::as_array)
+            .map(|m| {
+                m.iter()
+                    .filter_map(Value::as_u64)
+                    .map(|v| v as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let [row, col] = matrix[..] else {
+            return Err(format!(
+                "Key {} of layout '{}' has no valid 'matrix' position.",
+                index + 1,
+                name
+            ));
+        };
+        let mut labels: [Option<String>; 12] = Default::default();
+        labels[4] = entry
+            .get("label")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        keys.push(MatrixKey {
+            key: KleKey {
+                x: number(entry, "x", 0.0),
+                y: number(entry, "y", 0.0),
+                width: number(entry, "w", 1.0),
+                height: number(entry, "h", 1.0),
+                rotation: number(entry, "r", 0.0),
+                rotation_x: number(entry, "rx", 0.0),
+                rotation_y: number(entry, "ry", 0.0),
+                second_rect: None,
+                labels,
+            },
+            row,
+            col,
+        });
+    }
+
+    let size = matrix_size(definition, "/matrix_size", &keys);
+    Ok((keys, size))
+}
+
+/// Returns the keys of a VIA definition's layout, and the matrix size.
+///
+/// Keys are labelled `"row,col"` in the top left. A bottom right label
+/// `"group,option"` marks alternatives of a layout option (e.g. split
+/// Backspace); only the default option (`0`) is kept.
+fn via_keys(definition: &Value) -> Result<(Vec<MatrixKey>, (usize, usize)), String> {
+    let rows = kle::parse_kle_rows(&definition["layouts"]["keymap"])?;
+    let mut keys = Vec::new();
+    for key in kle::place_keys(&rows)? {
+        let parse_pair = |label: Option<&String>| -> Option<(usize, usize)> {
+            let (a, b) = label?.split_once(',')?;
+            Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+        };
+        let Some((row, col)) = parse_pair(key.labels[0].as_ref()) else {
+            log::warn!(
+                "Skipping a VIA key without a 'row,col' label at ({}, {}).",
+                key.x,
+                key.y
+            );
+            continue;
+        };
+        if parse_pair(key.labels[8].as_ref()).is_some_and(|(_, option)| option != 0) {
+            continue;
+        }
+        keys.push(MatrixKey {
+            key: KleKey {
+                labels: Default::default(),
+                ..key
+            },
+            row,
+            col,
+        });
+    }
+    let size = matrix_size(definition, "/matrix", &keys);
+    Ok((keys, size))
+}
+
+/// Returns the keycode of each key on the keymap's first layer.
+///
+/// A layer lists keycodes either in layout order (QMK `keymap.json`) or in
+/// matrix order (VIA keymap backups); the order is told apart by the length,
+/// preferring matrix order if `matrix_first` is set.
+fn base_layer_keycodes(
+    keymap: &Value,
+    keys: &[MatrixKey],
+    (rows, cols): (usize, usize),
+    matrix_first: bool,
+) -> Result<Vec<String>, String> {
+    let layer: Vec<String> = keymap
+        .pointer("/layers/0")
+        .and_then(Value::as_array)
+        .ok_or("The keymap has no 'layers'.")?
+        .iter()
+        .map(|code| code.as_str().unwrap_or_default().to_string())
+        .collect();
+    let in_layout_order = layer.len() == keys.len();
+    let in_matrix_order =
+        layer.len() == rows * cols && keys.iter().all(|k| k.row < rows && k.col < cols);
+    if in_layout_order && !(matrix_first && in_matrix_order) {
+        Ok(layer)
+    } else if in_matrix_order {
+        Ok(keys
+            .iter()
+            .map(|k| layer[k.row * cols + k.col].clone())
+            .collect())
+    } else {
+        Err(format!(
+            "The keymap's first layer has {} keycodes, but the layout has {} keys and a {}x{} matrix.",
+            layer.len(),
+            keys.len(),
+            rows,
+            cols
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qmk_keycode() {
+        let name = |code: &str| qmk_keycode(code).map(|(name, _)| name);
+        assert_eq!(
+            qmk_keycode("KC_A"),
+            Some(("a".to_string(), "A".to_string()))
+        );
+        assert_eq!(
+            qmk_keycode("KC_F12"),
+            Some(("f12".to_string(), "F12".to_string()))
+        );
+        assert_eq!(
+            qmk_keycode("KC_LSFT"),
+            Some(("leftshift".to_string(), "Shift".to_string()))
+        );
+        assert_eq!(name("KC_BACKSPACE").as_deref(), Some("backspace"));
+        assert_eq!(
+            qmk_keycode("KC_INSERT"),
+            Some(("insert".to_string(), "Ins".to_string()))
+        );
+        assert_eq!(
+            qmk_keycode("KC_KP_ENTER"),
+            Some(("kpenter".to_string(), "Enter".to_string()))
+        );
+        assert_eq!(name("LT(1, KC_SPC)").as_deref(), Some("space"));
+        assert_eq!(name("LCTL_T(KC_ESC)").as_deref(), Some("esc"));
+        assert_eq!(name("MO(1)"), None);
+        assert_eq!(name("KC_TRNS"), None);
+        assert_eq!(name("KC_NO"), None);
+        assert_eq!(name("QK_BOOT"), None);
+    }
+
+    const INFO_JSON: &str = r#"{
+        "keyboard_name": "test",
+        "layouts": {
+            "LAYOUT": {
+                "layout": [
+                    {"matrix": [0, 0], "x": 0, "y": 0, "label": "Esc"},
+                    {"matrix": [0, 1], "x": 1, "y": 0, "w": 2, "label": "Space"},
+                    {"matrix": [1, 0], "x": 0, "y": 1, "label": "Fn"},
+                    {"matrix": [1, 1], "x": 1, "y": 1, "r": 15, "rx": 1, "ry": 1, "label": "Layer"}
+                ]
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_import_info_json_with_keymap() {
+        let keymap = r#"{"keyboard": "test", "layout": "LAYOUT", "layers": [["KC_GRV", "LT(1, KC_SPC)", "KC_LCTL", "MO(1)"]]}"#;
+        let config = import_qmk_str(INFO_JSON, Some(keymap), None).unwrap();
+        let keys: Vec<(&str, u32, f32, f32)> = config
+            .key
+            .iter()
+            .map(|k| (k.name.as_str(), k.keycode, k.left, k.width))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("`", 41, 0.0, 50.0),
+                ("Space", 57, 55.0, 105.0),
+                ("Ctrl", 29, 0.0, 50.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_info_json_labels() {
+        let config = import_qmk_str(INFO_JSON, None, None).unwrap();
+        let keys: Vec<(&str, u32)> = config
+            .key
+            .iter()
+            .map(|k| (k.name.as_str(), k.keycode))
+            .collect();
+        assert_eq!(keys, vec![("Esc", 1), ("Space", 57), ("Fn", 464)]);
+    }
+
+    #[test]
+    fn test_import_via_with_matrix_keymap() {
+        let via = r#"{
+            "name": "test",
+            "matrix": {"rows": 2, "cols": 2},
+            "layouts": {"keymap": [
+                ["0,0", {"w": 2}, "0,1\n\n\n0,0", "0,1\n\n\n0,1", "1,1\n\n\n0,1"],
+                ["1,0"]
+            ]}
+        }"#;
+        let backup = r#"{"name": "test", "layers": [["KC_ESC", "KC_BSPC", "KC_A", "KC_DEL"]]}"#;
+        let config = import_qmk_str(via, Some(backup), None).unwrap();
+        let keys: Vec<(&str, u32, f32, f32)> = config
+            .key
+            .iter()
+            .map(|k| (k.name.as_str(), k.keycode, k.left, k.top))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("Esc", 1, 0.0, 0.0),
+                ("Backspace", 14, 55.0, 0.0),
+                ("A", 30, 0.0, 55.0)
+            ]
+        );
+        assert!(import_qmk_str(via, None, None).is_err());
+    }
+}
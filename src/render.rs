@@ -0,0 +1,238 @@
+// src/render.rs
+
+//! This module renders the keyboard offscreen, without a Wayland connection.
+//!
+//! It is used by the `--render-png` CLI mode to produce screenshots of a
+//! configuration with a given set of keys held down. The same layout and
+//! drawing code as the on-screen overlay is used.
+
+use std::collections::HashMap;
+use std::fs::File;
+
+use cairo::{Context, Format, ImageSurface};
+
+use crate::config::AppConfig;
+use crate::draw;
+use crate::event::{LayerState, Modifier, ModifierState};
+use crate::keycodes::get_keycode_from_string;
+use crate::layout;
+
+/// Width used when neither a width nor a height is requested.
+pub const DEFAULT_RENDER_WIDTH: u32 = 1200;
+
+/// Resolves the image size from optional width and height.
+///
+/// A missing dimension is derived from the aspect ratio of the key layout
+/// (including the label band, if enabled).
+pub fn resolve_render_size(
+    config: &AppConfig,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> (u32, u32) {
+    let (layout_w, layout_h) = layout::get_content_bounds(config);
+    let aspect = if layout_w > 0.0 && layout_h > 0.0 {
+        layout_h / layout_w
+    } else {
+        1.0
+    };
+    match (width, height) {
+        (Some(w), Some(h)) => (w.max(1), h.max(1)),
+        (Some(w), None) => (w.max(1), ((w as f32 * aspect).round() as u32).max(1)),
+        (None, Some(h)) => (((h as f32 / aspect).round() as u32).max(1), h.max(1)),
+        (None, None) => {
+            let w = DEFAULT_RENDER_WIDTH;
+            (w, ((w as f32 * aspect).round() as u32).max(1))
+        }
+    }
+}
+
+/// Resolves key names given on the command line to pressed key states.
+///
+/// Each entry may be anything accepted in a key's `keycode` field (a key name
+/// like `"a"` or `"leftshift"`, or a numeric keycode), or the `name` of a key
+/// in the configuration (matched case-insensitively).
+pub fn resolve_pressed_keys(
+    config: &AppConfig,
+    pressed: &[String],
+) -> Result<HashMap<u32, bool>, String> {
+    let mut key_states = HashMap::new();
+    for entry in pressed.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let keycode = match entry.parse::<u32>() {
+            Ok(code) => code,
+            Err(_) => match get_keycode_from_string(entry) {
+                Ok(code) => code,
+                Err(e) => config
+                    .key
+                    .iter()
+                    .find(|k| k.name.eq_ignore_ascii_case(entry))
+                    .map(|k| k.keycode)
+                    .ok_or_else(|| format!("Unknown pressed key '{}': {}", entry, e))?,
+            },
+        };
+        if !config.monitored_keycodes().any(|code| code == keycode) {
+            log::warn!(
+                "Pressed key '{}' (keycode {}) is not part of the layout.",
+                entry,
+                keycode
+            );
+        }
+        key_states.insert(keycode, true);
+    }
+    Ok(key_states)
+}
+
+/// Derives the active layer from a set of pressed keys.
+///
+/// Pressed momentary and toggle keys both activate their layer.
+fn layer_for_pressed_keys(config: &AppConfig, key_states: &HashMap<u32, bool>) -> Option<usize> {
+    let mut layers = LayerState::default();
+    for (&code, _) in key_states.iter().filter(|(_, &p)| p) {
+        layers.handle_key(&config.layer, code, true);
+    }
+    layers.active_layer()
+}
+
+/// Derives the modifier chord from a set of pressed keys.
+///
+/// Modifiers are applied first, then the other pressed keys in keycode order,
+/// so the chord names the highest pressed non-modifier key on `active_layer`.
+fn chord_for_pressed_keys(
+    config: &AppConfig,
+    key_states: &HashMap<u32, bool>,
+    active_layer: Option<usize>,
+) -> ModifierState {
+    let mut pressed: Vec<u32> = key_states
+        .iter()
+        .filter(|(_, &p)| p)
+        .map(|(&code, _)| code)
+        .collect();
+    pressed.sort_by_key(|&code| (Modifier::from_keycode(code).is_none(), code));
+
+    let mut modifiers = ModifierState::default();
+    for code in pressed {
+        let label = config
+            .keys_for_layer(active_layer)
+            .iter()
+            .find(|k| k.keycode == code)
+            .map(|k| k.name.clone())
+            .unwrap_or_else(|| format!("#{}", code));
+        modifiers.handle_key(code, true, &label);
+    }
+    modifiers
+}
+
+/// Renders the keyboard into a new image surface of the given size.
+///
+/// Pressed momentary or toggle keys switch to their layer. If
+/// `overlay.label_position` is set, the active layer and the chord formed by
+/// pressed modifiers are drawn in the label band.
+pub fn render_to_surface(
+    config: &AppConfig,
+    key_states: &HashMap<u32, bool>,
+    width: u32,
+    height: u32,
+) -> Result<ImageSurface, String> {
+    let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32)
+        .map_err(|e| format!("Failed to create {}x{} image surface: {}", width, height, e))?;
+    {
+        let ctx =
+            Context::new(&surface).map_err(|e| format!("Failed to create Cairo context: {}", e))?;
+        let (scale, offset_x, offset_y) =
+            layout::calculate_layout_parameters(config, width as i32, height as i32);
+        let active_layer = layer_for_pressed_keys(config, key_states);
+        let keys_to_draw = layout::prepare_keys_for_drawing(
+            config,
+            key_states,
+            active_layer,
+            scale,
+            offset_x,
+            offset_y,
+        );
+        let background_color = layout::overlay_background_color(config, key_states);
+        let font_face = draw::load_default_font_face();
+        draw::paint_all_keys(&ctx, &keys_to_draw, background_color, &font_face);
+
+        let chord = chord_for_pressed_keys(config, key_states, active_layer).chord_label();
+        let layer_name = active_layer.map(|index| config.layer[index].name.as_str());
+        let label = layout::band_label(layer_name, chord);
+        if let (Some(label), Some(band)) = (
+            label,
+            layout::label_band_rect(config, width as i32, height as i32),
+        ) {
+            draw::draw_single_key_cairo(
+                &ctx,
+                &layout::prepare_label_for_drawing(config, &label, band),
+            );
+        }
+    }
+    surface.flush();
+    Ok(surface)
+}
+
+/// Renders the keyboard with the given keys pressed and writes it to `output_path` as PNG.
+pub fn render_to_png(
+    config: &AppConfig,
+    pressed: &[String],
+    width: Option<u32>,
+    height: Option<u32>,
+    output_path: &str,
+) -> Result<(), String> {
+    let key_states = resolve_pressed_keys(config, pressed)?;
+    let (width, height) = resolve_render_size(config, width, height);
+    let surface = render_to_surface(config, &key_states, width, height)?;
+
+    let mut file = File::create(output_path)
+        .map_err(|e| format!("Failed to create '{}': {}", output_path, e))?;
+    surface
+        .write_to_png(&mut file)
+        .map_err(|e| format!("Failed to write PNG '{}': {}", output_path, e))?;
+
+    log::info!(
+        "Rendered {}x{} image with {} pressed key(s) to '{}'.",
+        width,
+        height,
+        key_states.len(),
+        output_path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_and_process_config;
+
+    fn example_config() -> AppConfig {
+        load_and_process_config(concat!(env!("CARGO_MANIFEST_DIR"), "/keys.toml"), None).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_pressed_keys() {
+        let config = example_config();
+        let states =
+            resolve_pressed_keys(&config, &["a".into(), "leftshift".into(), "57".into()]).unwrap();
+        assert_eq!(states.len(), 3);
+        assert!(states[&30] && states[&42] && states[&57]);
+        assert!(resolve_pressed_keys(&config, &["nosuchkey".into()]).is_err());
+    }
+
+    #[test]
+    fn test_render_to_png_writes_image() {
+        let config = example_config();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.png");
+        render_to_png(
+            &config,
+            &["a".into()],
+            Some(400),
+            None,
+            path.to_str().unwrap(),
+        )
+        .unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let image = ImageSurface::create_from_png(&mut file).unwrap();
+        assert_eq!(image.width(), 400);
+        assert!(image.height() > 0 && image.height() < 400);
+    }
+}
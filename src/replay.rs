@@ -0,0 +1,308 @@
+// src/replay.rs
+
+//! This module replays key events from `libinput record` YAML files.
+//!
+//! Only the raw `evdev` frames of a recording are used: every `EV_KEY`
+//! press/release is extracted, events from all recorded devices are merged
+//! by timestamp, and the result is fed back into the application as if it
+//! came from a real keyboard. This allows demoing and testing the OSD without
+//! access to `/dev/input`. `RecordingReplay` implements `InputSource`.
+
+use regex::Regex;
+use std::fs;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::{Duration, Instant};
+
+use crate::input_source::{InputSource, KeyEvent};
+
+/// `EV_KEY` event type from `linux/input-event-codes.h`.
+const EV_KEY: u32 = 1;
+
+/// A single key press or release extracted from a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedKeyEvent {
+    /// Time of the event in microseconds, relative to the start of the recording.
+    pub time_usec: u64,
+    /// Linux keycode of the key (e.g., 30 for `KEY_A`).
+    pub keycode: u32,
+    /// `true` for a press, `false` for a release.
+    pub pressed: bool,
+}
+
+/// Extracts key press/release events from the contents of a `libinput record` YAML file.
+///
+/// Each evdev frame line in a recording has the form
+/// `- [ sec, usec, type, code, value ] # comment`. Lines with `type == EV_KEY`
+/// and a value of 0 (release) or 1 (press) are kept; key repeats (value 2)
+/// and all other event types are skipped. Events are returned sorted by time.
+///
+/// # Returns
+///
+/// * `Ok(Vec<RecordedKeyEvent>)` with the key events in playback order.
+/// * `Err(String)` if the content does not contain any evdev frames.
+pub fn parse_recording(content: &str) -> Result<Vec<RecordedKeyEvent>, String> {
+    let frame_re =
+        Regex::new(r"^\s*-\s*\[\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*(-?\d+)\s*\]")
+            .expect("Invalid evdev frame regex");
+
+    let mut frames_seen = 0usize;
+    let mut events = Vec::new();
+    for line in content.lines() {
+        let Some(caps) = frame_re.captures(line) else {
+            continue;
+        };
+        frames_seen += 1;
+
+        // The regex guarantees the captures are digits; overflow is the only failure.
+        let parse = |idx: usize| {
+            caps[idx]
+                .parse::<i64>()
+                .map_err(|e| format!("Invalid number in evdev frame '{}': {}", line.trim(), e))
+        };
+        let (sec, usec, ev_type, code, value) =
+            (parse(1)?, parse(2)?, parse(3)?, parse(4)?, parse(5)?);
+
+        if ev_type as u32 != EV_KEY || !(value == 0 || value == 1) {
+            continue;
+        }
+        events.push(RecordedKeyEvent {
+            time_usec: (sec as u64) * 1_000_000 + usec as u64,
+            keycode: code as u32,
+            pressed: value == 1,
+        });
+    }
+
+    if frames_seen == 0 {
+        return Err("No evdev events found. Is this a 'libinput record' YAML file?".to_string());
+    }
+
+    // Recordings of multiple devices list events per device; merge them by time.
+    // sort_by_key is stable, so events within the same frame keep their order.
+    events.sort_by_key(|e| e.time_usec);
+    Ok(events)
+}
+
+/// Replays recorded key events, driven by a `timerfd` so it can be polled
+/// alongside the Wayland connection.
+pub struct RecordingReplay {
+    events: Vec<RecordedKeyEvent>,
+    next_index: usize,
+    start: Option<Instant>,
+    realtime: bool,
+    timer_fd: OwnedFd,
+}
+
+impl RecordingReplay {
+    /// Loads a recording from `path`.
+    ///
+    /// If `realtime` is `true`, events are replayed with the timing of the
+    /// recording. Otherwise they are replayed back to back as fast as the main
+    /// loop can process them.
+    pub fn from_file(path: &str, realtime: bool) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read recording '{}': {}", path, e))?;
+        let events = parse_recording(&content)
+            .map_err(|e| format!("Failed to parse recording '{}': {}", path, e))?;
+
+        let raw_fd = unsafe {
+            libc::timerfd_create(
+                libc::CLOCK_MONOTONIC,
+                libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+            )
+        };
+        if raw_fd < 0 {
+            return Err(format!(
+                "Failed to create timerfd for replay: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: raw_fd was just returned by timerfd_create and is owned by nobody else.
+        let timer_fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+        log::info!(
+            "Loaded {} key events from recording '{}' ({} playback).",
+            events.len(),
+            path,
+            if realtime { "real-time" } else { "fast" }
+        );
+
+        Ok(RecordingReplay {
+            events,
+            next_index: 0,
+            start: None,
+            realtime,
+            timer_fd,
+        })
+    }
+
+    /// Returns `true` once all recorded events have been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next_index >= self.events.len()
+    }
+
+    /// Returns the events that are due now and re-arms the timer for the next one.
+    ///
+    /// Must be called when the timer fd is readable.
+    fn take_due_events(&mut self) -> Vec<RecordedKeyEvent> {
+        // Drain the expiration counter so the fd stops polling as readable.
+        let mut expirations = [0u8; 8];
+        unsafe {
+            libc::read(
+                self.timer_fd.as_raw_fd(),
+                expirations.as_mut_ptr() as *mut libc::c_void,
+                expirations.len(),
+            );
+        }
+
+        let elapsed_usec = self
+            .start
+            .map(|s| s.elapsed().as_micros() as u64)
+            .unwrap_or(0);
+
+        let mut due = Vec::new();
+        while let Some(event) = self.events.get(self.next_index) {
+            if self.realtime && event.time_usec > elapsed_usec {
+                break;
+            }
+            due.push(event.clone());
+            self.next_index += 1;
+            if !self.realtime {
+                break; // One event per wakeup so every state change reaches the main loop.
+            }
+        }
+
+        if self.is_finished() {
+            log::info!("Recording replay finished.");
+        } else {
+            self.arm_for_next_event();
+        }
+        due
+    }
+
+    fn arm_for_next_event(&self) {
+        let Some(event) = self.events.get(self.next_index) else {
+            return;
+        };
+        let delay = if self.realtime {
+            let elapsed = self.start.map(|s| s.elapsed()).unwrap_or_default();
+            Duration::from_micros(event.time_usec).saturating_sub(elapsed)
+        } else {
+            Duration::ZERO
+        };
+        // An all-zero it_value disarms a timerfd, so always wait at least 1ns.
+        let delay = delay.max(Duration::from_nanos(1));
+
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: delay.as_secs() as libc::time_t,
+                tv_nsec: delay.subsec_nanos() as libc::c_long,
+            },
+        };
+        let ret = unsafe {
+            libc::timerfd_settime(self.timer_fd.as_raw_fd(), 0, &spec, std::ptr::null_mut())
+        };
+        if ret < 0 {
+            log::error!(
+                "Failed to arm replay timer: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+impl AsRawFd for RecordingReplay {
+    fn as_raw_fd(&self) -> RawFd {
+        self.timer_fd.as_raw_fd()
+    }
+}
+
+impl InputSource for RecordingReplay {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn read_events(&mut self) -> Result<Vec<KeyEvent>, String> {
+        Ok(self
+            .take_due_events()
+            .into_iter()
+            .map(|e| KeyEvent {
+                keycode: e.keycode,
+                pressed: e.pressed,
+            })
+            .collect())
+    }
+
+    /// Starts playback by arming the timer for the first event.
+    fn start(&mut self) {
+        self.start = Some(Instant::now());
+        self.arm_for_next_event();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixture_recording() {
+        let content = include_str!("../tests/fixtures/libinput-recording-abc.yaml");
+        let events = parse_recording(content).unwrap();
+        let summary: Vec<(u32, bool)> = events.iter().map(|e| (e.keycode, e.pressed)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (28, false),
+                (30, true),
+                (30, false),
+                (48, true),
+                (48, false),
+                (46, true),
+                (46, false)
+            ]
+        );
+        assert_eq!(events[1].time_usec, 720_031);
+        assert_eq!(events[4].time_usec, 1_040_085);
+    }
+
+    #[test]
+    fn test_parse_skips_repeats_and_other_types() {
+        let content = "\
+  - evdev:
+    - [  0,    100,   4,   4,  458756] # EV_MSC / MSC_SCAN
+    - [  0,    100,   1,  30,       1] # EV_KEY / KEY_A 1
+    - [  0,    200,   1,  30,       2] # EV_KEY / KEY_A 2
+    - [  0,    300,   0,   0,       0] # SYN_REPORT
+";
+        let events = parse_recording(content).unwrap();
+        assert_eq!(
+            events,
+            vec![RecordedKeyEvent {
+                time_usec: 100,
+                keycode: 30,
+                pressed: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_merges_devices_by_time() {
+        let content = "\
+  - evdev:
+    - [  0,    500,   1,  30,       1]
+  - evdev:
+    - [  0,    100,   1,  48,       1]
+";
+        let events = parse_recording(content).unwrap();
+        assert_eq!(events[0].keycode, 48);
+        assert_eq!(events[1].keycode, 30);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_recording() {
+        assert!(parse_recording("key = 1\n").is_err());
+    }
+}
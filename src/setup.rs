@@ -1,17 +1,22 @@
 // src/setup.rs
 
-use crate::config::{self, AppConfig};
-use crate::event::MyLibinputInterface;
-use crate::poll_fds::{FdPoller, FdPollerCreationError};
+use crate::config::AppConfig;
+use crate::config_watch::ConfigWatcher;
+use crate::evdev::EvdevSource;
+use crate::input_source::{InputBackend, InputSource, LibinputSource};
+use crate::ipc::{self, IpcServer};
+use crate::poll_fds::{FdKind, FdPoller, FdPollerCreationError};
+use crate::replay::RecordingReplay;
 use crate::wayland::AppState;
 
 use wayland_client::protocol::wl_output;
 use wayland_client::{Connection, EventQueue, QueueHandle};
 use wayland_protocols_wlr::layer_shell::v1::client::{
     zwlr_layer_shell_v1,
-    zwlr_layer_surface_v1::{Anchor, KeyboardInteractivity},
+    zwlr_layer_surface_v1::KeyboardInteractivity,
 };
 
+use std::os::unix::io::AsRawFd;
 use std::process;
 
 pub fn initialize_wayland_connection() -> Connection {
@@ -69,21 +74,81 @@ pub fn initialize_globals_and_outputs(
     }
 }
 
-pub fn initialize_libinput_context(app_state: &mut AppState) {
-    let interface = MyLibinputInterface;
-    let mut libinput_context = input::Libinput::new_with_udev(interface);
-    match libinput_context.udev_assign_seat("seat0") {
-        Ok(_) => {
-            log::info!("Successfully assigned seat0 to libinput context.");
-            app_state.input_context = Some(libinput_context);
+/// Creates the input source selected on the command line and stores it in `app_state`.
+///
+/// A recording replay is fatal if it cannot be loaded, since the user asked for it
+/// explicitly. Device backends only log a warning on failure: the OSD still runs,
+/// it just won't react to key presses.
+pub fn initialize_input_source(
+    app_state: &mut AppState,
+    backend: InputBackend,
+    replay: Option<(&str, bool)>,
+) {
+    if let Some((recording_path, realtime)) = replay {
+        match RecordingReplay::from_file(recording_path, realtime) {
+            Ok(replay) => {
+                log::info!(
+                    "Replaying key events from '{}' instead of reading input devices.",
+                    recording_path
+                );
+                app_state.input_source = Some(Box::new(replay));
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let source: Result<Box<dyn InputSource>, String> = match backend {
+        InputBackend::Libinput => {
+            LibinputSource::new().map(|s| Box::new(s) as Box<dyn InputSource>)
+        }
+        InputBackend::Evdev => EvdevSource::new().map(|s| Box::new(s) as Box<dyn InputSource>),
+    };
+    match source {
+        Ok(source) => {
+            log::info!("Initialized '{}' input source.", source.name());
+            app_state.input_source = Some(source);
         }
         Err(e) => {
-            log::warn!("Failed to assign seat0 to libinput context: {:?}. Input monitoring will be disabled.", e);
+            log::warn!("{}. Input monitoring will be disabled.", e);
             log::warn!("This may be due to permissions issues. Ensure the user is in the 'input' group or has direct access to /dev/input/event* devices.");
         }
     }
 }
 
+/// Starts watching the configuration file so it is reloaded when it changes.
+///
+/// Failure is not fatal: the OSD keeps running with the configuration it was
+/// started with.
+pub fn initialize_config_watcher(app_state: &mut AppState, config_path: &str) {
+    match ConfigWatcher::new(config_path) {
+        Ok(watcher) => app_state.config_watcher = Some(watcher),
+        Err(e) => log::warn!(
+            "{}. Configuration changes will not be reloaded automatically.",
+            e
+        ),
+    }
+}
+
+/// Opens the IPC control socket at `socket_path`, or at the default location
+/// under `$XDG_RUNTIME_DIR` if `None`.
+///
+/// Failure is not fatal: the OSD runs without remote control.
+pub fn initialize_ipc_server(app_state: &mut AppState, socket_path: Option<&str>) {
+    let path = match socket_path {
+        Some(p) => Ok(std::path::PathBuf::from(p)),
+        None => ipc::default_socket_path(),
+    };
+    match path.and_then(|p| IpcServer::bind(&p)) {
+        Ok(server) => app_state.ipc_server = Some(server),
+        Err(e) => log::warn!("{}. IPC control will be unavailable.", e),
+    }
+}
+
 pub fn create_wayland_surface(app_state: &mut AppState, qh: &QueueHandle<AppState>) {
     let surface = app_state
         .compositor
@@ -164,51 +229,14 @@ pub fn setup_overlay_mode(app_state: &mut AppState, qh: &QueueHandle<AppState>)
             (),
         );
 
-        let base_anchor = match app_state.config.overlay.position {
-            config::OverlayPosition::Top | config::OverlayPosition::TopCenter => {
-                    Anchor::Top | Anchor::Left | Anchor::Right
-            }
-            config::OverlayPosition::Bottom | config::OverlayPosition::BottomCenter => {
-                    Anchor::Bottom | Anchor::Left | Anchor::Right
-            }
-                config::OverlayPosition::Left => Anchor::Left,
-                config::OverlayPosition::Right => Anchor::Right,
-            config::OverlayPosition::Center => {
-                    Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right
-            }
-                config::OverlayPosition::TopLeft => Anchor::Top | Anchor::Left,
-                config::OverlayPosition::TopRight => Anchor::Top | Anchor::Right,
-                config::OverlayPosition::BottomLeft => Anchor::Bottom | Anchor::Left,
-                config::OverlayPosition::BottomRight => Anchor::Bottom | Anchor::Right,
-                config::OverlayPosition::CenterLeft => Anchor::Left,
-                config::OverlayPosition::CenterRight => Anchor::Right,
-        };
-
-        log::info!("Setting anchor to: {:?}", base_anchor);
-        layer_surface_obj.set_anchor(base_anchor);
-
-        let margins = &app_state.config.overlay;
-        log::info!(
-            "Setting margins: T={}, R={}, B={}, L={}",
-            margins.margin_top,
-            margins.margin_right,
-            margins.margin_bottom,
-            margins.margin_left
-        );
-        layer_surface_obj.set_margin(
-            margins.margin_top,
-            margins.margin_right,
-            margins.margin_bottom,
-            margins.margin_left,
-        );
-
-            layer_surface_obj.set_keyboard_interactivity(KeyboardInteractivity::None);
+        layer_surface_obj.set_keyboard_interactivity(KeyboardInteractivity::None);
         layer_surface_obj.set_exclusive_zone(0);
 
         log::info!("Setting initial layer surface size to (1,1). Actual size will be configured once screen dimensions are known.");
         layer_surface_obj.set_size(1, 1);
 
         app_state.layer_surface = Some(layer_surface_obj);
+        app_state.apply_overlay_placement();
         log::info!("Created and configured layer surface for overlay mode.");
     } else {
         log::error!("Overlay mode active, but zwlr_layer_shell_v1 is not available from the compositor. Falling back to XDG window mode.");
@@ -273,8 +301,22 @@ pub fn initialize_fd_poller(
     conn: &Connection,
     app_state: &AppState,
 ) -> FdPoller {
-    match FdPoller::new(conn, app_state.input_context.as_ref()) {
-        Ok(poller) => poller,
+    match FdPoller::new(conn) {
+        Ok(mut poller) => {
+            if let Some(source) = app_state.input_source.as_ref() {
+                poller.add_fd(FdKind::Input, source.as_raw_fd());
+            }
+            if let Some(watcher) = app_state.config_watcher.as_ref() {
+                poller.add_fd(FdKind::ConfigWatch, watcher.as_raw_fd());
+            }
+            if let Some(server) = app_state.ipc_server.as_ref() {
+                poller.add_fd(FdKind::Ipc, server.as_raw_fd());
+                for fd in server.connection_fds() {
+                    poller.add_fd(FdKind::IpcClient, fd);
+                }
+            }
+            poller
+        }
         Err(FdPollerCreationError::WaylandConnection(e)) => {
             log::error!("Failed to create FdPoller due to Wayland connection error: {}. Exiting.", e);
             process::exit(1);
@@ -282,16 +324,16 @@ pub fn initialize_fd_poller(
     }
 }
 
-pub fn log_input_device_status(app_config: &AppConfig, input_context_is_some: bool) {
-    if !app_config.key.is_empty() && input_context_is_some {
+pub fn log_input_device_status(app_config: &AppConfig, input_source_is_some: bool) {
+    if !app_config.key.is_empty() && input_source_is_some {
         log::info!(
-            "Libinput context was initialized. If keys do not respond, please check previous log messages \
+            "Input source was initialized. If keys do not respond, please check previous log messages \
             for any 'Failed to open path' errors from the input system. These errors often indicate \
             permission issues (e.g., the user running the application may not be in the 'input' group)."
         );
-    } else if !app_config.key.is_empty() && !input_context_is_some {
+    } else if !app_config.key.is_empty() && !input_source_is_some {
         log::warn!(
-            "Key input is configured in keys.toml, but the libinput context could not be initialized \
+            "Key input is configured in keys.toml, but no input source could be initialized \
             (see previous errors). Key press/release events will not be monitored."
         );
     }
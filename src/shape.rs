@@ -0,0 +1,363 @@
+// src/shape.rs
+
+//! This module handles the geometry of key shapes.
+//!
+//! Keys are rectangles unless their `shape` describes an outline made of
+//! rectangles (e.g. an ISO Enter) or a polygon. This module turns shapes into
+//! outlines for drawing and into convex parts for overlap checks, which take
+//! the key's rotation into account.
+
+use crate::config::{KeyConfig, KeyShape};
+
+/// A point in layout units.
+pub type Point = (f32, f32);
+
+/// Tolerance for comparing coordinates, in layout units.
+const EPSILON: f32 = 1e-3;
+
+/// Returns the closed outlines of a shape, relative to the key's top-left corner.
+///
+/// Rectangles are merged into the outline of their union; a shape with holes
+/// or several separate pieces has several outlines, to be filled with the
+/// even-odd rule.
+pub fn shape_outlines(shape: &KeyShape) -> Vec<Vec<Point>> {
+    match shape {
+        KeyShape::Vertices(vertices) => vec![vertices.iter().map(|&[x, y]| (x, y)).collect()],
+        KeyShape::Rects(rects) => union_outlines(rects),
+    }
+}
+
+/// Cuts a union of rectangles `[left, top, width, height]` into a grid at all
+/// their edges.
+///
+/// Returns the grid lines and the covered cells by column and row index. The
+/// cells do not overlap, unlike the rectangles they came from.
+fn union_cells(rects: &[[f32; 4]]) -> (Vec<f32>, Vec<f32>, Vec<(usize, usize)>) {
+    let mut xs: Vec<f32> = rects.iter().flat_map(|r| [r[0], r[0] + r[2]]).collect();
+    let mut ys: Vec<f32> = rects.iter().flat_map(|r| [r[1], r[1] + r[3]]).collect();
+    for coords in [&mut xs, &mut ys] {
+        coords.sort_by(f32::total_cmp);
+        coords.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+    }
+
+    let mut cells = Vec::new();
+    for i in 0..xs.len().saturating_sub(1) {
+        for j in 0..ys.len().saturating_sub(1) {
+            let (cx, cy) = ((xs[i] + xs[i + 1]) / 2.0, (ys[j] + ys[j + 1]) / 2.0);
+            if rects
+                .iter()
+                .any(|r| cx > r[0] && cx < r[0] + r[2] && cy > r[1] && cy < r[1] + r[3])
+            {
+                cells.push((i, j));
+            }
+        }
+    }
+    (xs, ys, cells)
+}
+
+/// Traces the outlines of a union of rectangles `[left, top, width, height]`.
+///
+/// Every covered grid cell (see `union_cells`) contributes its four edges
+/// clockwise; edges shared by two covered cells cancel out, and the rest are
+/// chained into outlines.
+fn union_outlines(rects: &[[f32; 4]]) -> Vec<Vec<Point>> {
+    let (xs, ys, cells) = union_cells(rects);
+    let mut edges: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for (i, j) in cells {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        for k in 0..4 {
+            let edge = (corners[k], corners[(k + 1) % 4]);
+            match edges.iter().position(|&(from, to)| (to, from) == edge) {
+                Some(index) => {
+                    edges.swap_remove(index);
+                }
+                None => edges.push(edge),
+            }
+        }
+    }
+
+    let mut outlines = Vec::new();
+    while let Some((start, mut next)) = edges.pop() {
+        let mut outline = vec![start];
+        while next != start {
+            outline.push(next);
+            let Some(index) = edges.iter().position(|&(from, _)| from == next) else {
+                break;
+            };
+            next = edges.swap_remove(index).1;
+        }
+        let points: Vec<Point> = outline.iter().map(|&(i, j)| (xs[i], ys[j])).collect();
+        outlines.push(remove_collinear(&points));
+    }
+    outlines
+}
+
+/// Removes vertices that lie on a straight line between their neighbours.
+fn remove_collinear(points: &[Point]) -> Vec<Point> {
+    let n = points.len();
+    (0..n)
+        .filter(|&i| {
+            let (a, p, b) = (points[(i + n - 1) % n], points[i], points[(i + 1) % n]);
+            cross(a, p, b).abs() > EPSILON
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+/// Z component of the cross product of `p - a` and `b - p`.
+fn cross(a: Point, p: Point, b: Point) -> f32 {
+    (p.0 - a.0) * (b.1 - p.1) - (p.1 - a.1) * (b.0 - p.0)
+}
+
+/// Returns a key's shape as non-overlapping convex polygons in layout
+/// coordinates, rotated by `rotation_degrees` around the key's center.
+///
+/// A plain key is its rectangle, a shape made of rectangles is cut into grid
+/// cells, and a polygon is split into triangles.
+pub fn key_convex_parts(key: &KeyConfig) -> Vec<Vec<Point>> {
+    let rect =
+        |x: f32, y: f32, w: f32, h: f32| vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+    let parts = match &key.shape {
+        None => vec![rect(0.0, 0.0, key.width, key.height)],
+        Some(KeyShape::Rects(rects)) => {
+            let (xs, ys, cells) = union_cells(rects);
+            cells
+                .into_iter()
+                .map(|(i, j)| rect(xs[i], ys[j], xs[i + 1] - xs[i], ys[j + 1] - ys[j]))
+                .collect()
+        }
+        Some(KeyShape::Vertices(vertices)) => {
+            triangulate(&vertices.iter().map(|&[x, y]| (x, y)).collect::<Vec<_>>())
+        }
+    };
+
+    // Rotate like `draw_single_key_cairo`: clockwise on screen, around the center.
+    let (sin, cos) = key.rotation_degrees.unwrap_or(0.0).to_radians().sin_cos();
+    let (half_width, half_height) = (key.width / 2.0, key.height / 2.0);
+    parts
+        .into_iter()
+        .map(|part| {
+            part.into_iter()
+                .map(|(x, y)| {
+                    let (dx, dy) = (x - half_width, y - half_height);
+                    (
+                        key.left + half_width + dx * cos - dy * sin,
+                        key.top + half_height + dx * sin + dy * cos,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Splits a simple polygon into triangles by ear clipping.
+fn triangulate(polygon: &[Point]) -> Vec<Vec<Point>> {
+    let mut remaining = remove_collinear(polygon);
+    // Work on a clockwise polygon (in screen coordinates, y pointing down).
+    if polygon_area(&remaining) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, p, b) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            cross(a, p, b) > EPSILON
+                && !remaining
+                    .iter()
+                    .any(|&q| q != a && q != p && q != b && point_in_triangle(q, a, p, b))
+        });
+        // Degenerate (e.g. self-intersecting) polygons have no ear; use what is left as is.
+        let Some(i) = ear else {
+            break;
+        };
+        triangles.push(vec![
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push(remaining);
+    triangles
+}
+
+/// Returns `true` if `q` lies inside or on the clockwise triangle `a`, `b`, `c`.
+fn point_in_triangle(q: Point, a: Point, b: Point, c: Point) -> bool {
+    cross(a, b, q) >= -EPSILON && cross(b, c, q) >= -EPSILON && cross(c, a, q) >= -EPSILON
+}
+
+/// Returns `true` if the interiors of two convex polygons overlap.
+///
+/// Uses the separating axis theorem: the polygons are apart if their
+/// projections onto the normal of some edge do not overlap. Polygons that
+/// only touch do not overlap.
+pub fn convex_overlap(a: &[Point], b: &[Point]) -> bool {
+    let edges = |p: &'_ [Point]| -> Vec<Point> {
+        (0..p.len())
+            .map(|i| {
+                (
+                    p[(i + 1) % p.len()].0 - p[i].0,
+                    p[(i + 1) % p.len()].1 - p[i].1,
+                )
+            })
+            .collect()
+    };
+    let project = |p: &[Point], axis: Point| {
+        p.iter()
+            .map(|q| q.0 * axis.0 + q.1 * axis.1)
+            .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)))
+    };
+    edges(a).into_iter().chain(edges(b)).all(|(dx, dy)| {
+        let length = (dx * dx + dy * dy).sqrt();
+        if length < EPSILON {
+            return true;
+        }
+        let axis = (-dy / length, dx / length);
+        let ((a_lo, a_hi), (b_lo, b_hi)) = (project(a, axis), project(b, axis));
+        a_lo < b_hi - EPSILON && b_lo < a_hi - EPSILON
+    })
+}
+
+/// Returns the intersection of two convex polygons, which may be empty.
+///
+/// Clips `subject` by each edge of `clip` (Sutherland-Hodgman). Both polygons
+/// may be in either orientation.
+fn convex_intersection(subject: &[Point], clip: &[Point]) -> Vec<Point> {
+    let orientation = polygon_area(clip).signum();
+    let mut result = subject.to_vec();
+    for i in 0..clip.len() {
+        let (a, b) = (clip[i], clip[(i + 1) % clip.len()]);
+        // Positive on the inner side of the edge from `a` to `b`.
+        let side = |p: Point| orientation * ((b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0));
+        let input = std::mem::take(&mut result);
+        for j in 0..input.len() {
+            let (p, q) = (input[j], input[(j + 1) % input.len()]);
+            let (side_p, side_q) = (side(p), side(q));
+            if side_p >= 0.0 {
+                result.push(p);
+            }
+            if (side_p >= 0.0) != (side_q >= 0.0) {
+                let t = side_p / (side_p - side_q);
+                result.push((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t));
+            }
+        }
+    }
+    result
+}
+
+/// Signed area of a polygon; positive if clockwise on screen (y pointing down).
+fn polygon_area(points: &[Point]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Returns the area in square layout units where the shapes of two keys
+/// overlap, or 0 if they don't.
+pub fn overlap_area(a: &KeyConfig, b: &KeyConfig) -> f32 {
+    let (parts_a, parts_b) = (key_convex_parts(a), key_convex_parts(b));
+    parts_a
+        .iter()
+        .flat_map(|pa| parts_b.iter().map(move |pb| (pa, pb)))
+        .filter(|(pa, pb)| convex_overlap(pa, pb))
+        .map(|(pa, pb)| polygon_area(&convex_intersection(pa, pb)).abs())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso_enter_outline() {
+        let shape = KeyShape::Rects(vec![[0.0, 0.0, 80.0, 50.0], [12.5, 0.0, 67.5, 105.0]]);
+        let outlines = shape_outlines(&shape);
+        assert_eq!(outlines.len(), 1);
+        let mut outline = outlines[0].clone();
+        outline.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        assert_eq!(
+            outline,
+            vec![
+                (0.0, 0.0),
+                (0.0, 50.0),
+                (12.5, 50.0),
+                (12.5, 105.0),
+                (80.0, 0.0),
+                (80.0, 105.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_triangulate_l_shape() {
+        let l_shape = [
+            (0.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 20.0),
+            (0.0, 20.0),
+        ];
+        let triangles = triangulate(&l_shape);
+        assert_eq!(triangles.len(), 4);
+        let area: f32 = triangles
+            .iter()
+            .map(|t| cross(t[0], t[1], t[2]).abs() / 2.0)
+            .sum();
+        assert!((area - 300.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_convex_overlap() {
+        let square =
+            |x: f32, y: f32| vec![(x, y), (x + 10.0, y), (x + 10.0, y + 10.0), (x, y + 10.0)];
+        assert!(convex_overlap(&square(0.0, 0.0), &square(5.0, 5.0)));
+        assert!(!convex_overlap(&square(0.0, 0.0), &square(10.0, 0.0)));
+        assert!(!convex_overlap(&square(0.0, 0.0), &square(11.0, 11.0)));
+        let diamond = vec![(18.0, 13.0), (23.0, 18.0), (18.0, 23.0), (13.0, 18.0)];
+        assert!(!convex_overlap(&square(4.0, 4.0), &diamond));
+    }
+
+    #[test]
+    fn test_overlap_area() {
+        let key = |left: f32, top: f32, rotation: Option<f32>| {
+            let mut key: KeyConfig = toml::from_str(&format!(
+                "left = {:?}\ntop = {:?}\nwidth = 50.0\nheight = 50.0",
+                left, top
+            ))
+            .unwrap();
+            key.rotation_degrees = rotation;
+            key
+        };
+        assert!((overlap_area(&key(0.0, 0.0, None), &key(40.0, 20.0, None)) - 300.0).abs() < 0.01);
+        assert_eq!(
+            overlap_area(&key(0.0, 0.0, None), &key(55.0, 0.0, None)),
+            0.0
+        );
+        // Rotated by 45 degrees, the corner reaches sqrt(2) * 25 = 35.36 from the center.
+        assert!(overlap_area(&key(0.0, 0.0, Some(45.0)), &key(55.0, 0.0, None)) > 0.0);
+        assert_eq!(
+            overlap_area(&key(0.0, 0.0, Some(45.0)), &key(61.0, 0.0, None)),
+            0.0
+        );
+
+        // Overlapping rectangles of a shape are counted once.
+        let mut enter = key(0.0, 0.0, None);
+        (enter.width, enter.height) = (77.5, 105.0);
+        enter.shape = Some(KeyShape::Rects(vec![
+            [0.0, 0.0, 77.5, 50.0],
+            [13.75, 0.0, 63.75, 105.0],
+        ]));
+        assert!((overlap_area(&enter, &enter) - (77.5 * 50.0 + 63.75 * 55.0)).abs() < 0.1);
+    }
+}
@@ -0,0 +1,464 @@
+// src/strip.rs
+
+//! This module implements the typed-text strip display mode.
+//!
+//! Instead of a keyboard diagram, the OSD shows a row of recently typed keys,
+//! screenkey-style: consecutive printable keys are merged into one text entry
+//! ("hello"), shortcuts become combo entries ("Ctrl+Shift+T"), and special keys
+//! are shown as symbols ("⏎", "⌫"). Entries fade out after
+//! `overlay.strip_timeout_ms`. Characters are derived from a US QWERTY layout;
+//! modifier state comes from the shared `event::ModifierState`.
+
+use cairo::{Context, FontFace as CairoFontFace};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::config::{parse_color_string, AppConfig};
+use crate::draw::{self, KeyDisplay};
+use crate::event::{Modifier, ModifierState};
+
+/// Maximum number of entries kept in the strip.
+const MAX_ENTRIES: usize = 16;
+/// Text entries longer than this keep only their most recent characters.
+const MAX_TEXT_CHARS: usize = 32;
+/// Duration of the fade-out at the end of an entry's timeout.
+const FADE_DURATION: Duration = Duration::from_millis(500);
+/// Width-to-height ratio of the strip surface in overlay mode.
+pub const STRIP_ASPECT_RATIO: f32 = 8.0;
+
+/// Returns the character produced by a key on a US QWERTY layout, if any.
+fn printable_char(keycode: u32, shift: bool) -> Option<char> {
+    const DIGITS: &str = "1234567890";
+    const SHIFTED_DIGITS: &str = "!@#$%^&*()";
+    let pick = |plain: char, shifted: char| Some(if shift { shifted } else { plain });
+    let letter = |c: char| Some(if shift { c.to_ascii_uppercase() } else { c });
+    match keycode {
+        2..=11 => {
+            let i = (keycode - 2) as usize;
+            pick(
+                DIGITS.as_bytes()[i] as char,
+                SHIFTED_DIGITS.as_bytes()[i] as char,
+            )
+        }
+        12 => pick('-', '_'),
+        13 => pick('=', '+'),
+        16..=25 => letter("qwertyuiop".as_bytes()[(keycode - 16) as usize] as char),
+        26 => pick('[', '{'),
+        27 => pick(']', '}'),
+        30..=38 => letter("asdfghjkl".as_bytes()[(keycode - 30) as usize] as char),
+        39 => pick(';', ':'),
+        40 => pick('\'', '"'),
+        41 => pick('`', '~'),
+        43 => pick('\\', '|'),
+        44..=50 => letter("zxcvbnm".as_bytes()[(keycode - 44) as usize] as char),
+        51 => pick(',', '<'),
+        52 => pick('.', '>'),
+        53 => pick('/', '?'),
+        57 => Some('␣'),
+        // Keypad
+        71 => Some('7'),
+        72 => Some('8'),
+        73 => Some('9'),
+        74 => Some('-'),
+        75 => Some('4'),
+        76 => Some('5'),
+        77 => Some('6'),
+        78 => Some('+'),
+        79 => Some('1'),
+        80 => Some('2'),
+        81 => Some('3'),
+        82 => Some('0'),
+        83 => Some('.'),
+        55 => Some('*'),
+        98 => Some('/'),
+        _ => None,
+    }
+}
+
+/// Returns the symbol or short name shown for a non-printable key.
+fn special_key_label(keycode: u32) -> Option<String> {
+    let label = match keycode {
+        1 => "Esc",
+        14 => "⌫",
+        15 => "⇥",
+        28 | 96 => "⏎",
+        58 => "⇪",
+        102 => "Home",
+        103 => "↑",
+        104 => "PgUp",
+        105 => "←",
+        106 => "→",
+        107 => "End",
+        108 => "↓",
+        109 => "PgDn",
+        110 => "Ins",
+        111 => "⌦",
+        59..=68 => return Some(format!("F{}", keycode - 58)),
+        87 => "F11",
+        88 => "F12",
+        _ => return None,
+    };
+    Some(label.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum EntryKind {
+    /// Consecutive printable characters.
+    Text,
+    /// A combo or special key; identical consecutive ones are counted.
+    Key { repeat: u32 },
+}
+
+#[derive(Debug, Clone)]
+struct StripEntry {
+    text: String,
+    kind: EntryKind,
+    updated: Instant,
+}
+
+impl StripEntry {
+    fn display_text(&self) -> String {
+        match self.kind {
+            EntryKind::Key { repeat } if repeat > 1 => format!("{} ×{}", self.text, repeat),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+/// The recently typed keys shown in strip mode.
+pub struct TypedStrip {
+    entries: VecDeque<StripEntry>,
+    timeout: Duration,
+}
+
+impl TypedStrip {
+    /// Creates an empty strip whose entries disappear `timeout` after their last update.
+    pub fn new(timeout: Duration) -> Self {
+        TypedStrip {
+            entries: VecDeque::new(),
+            timeout: timeout.max(FADE_DURATION),
+        }
+    }
+
+    /// Changes the entry timeout, e.g. after a configuration reload.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout.max(FADE_DURATION);
+    }
+
+    /// Records a key press or release.
+    ///
+    /// `modifiers` must already reflect this event. Modifier keys themselves
+    /// are not shown; they only turn later keys into combos. `fallback_name`
+    /// is shown for keys that are neither printable nor have a known symbol,
+    /// typically the key's name in the configuration.
+    ///
+    /// Returns `true` if an entry was added or updated.
+    pub fn handle_key(
+        &mut self,
+        keycode: u32,
+        pressed: bool,
+        modifiers: &ModifierState,
+        fallback_name: Option<&str>,
+        now: Instant,
+    ) -> bool {
+        if !pressed || Modifier::from_keycode(keycode).is_some() {
+            return false;
+        }
+
+        let shift = modifiers.is_held(Modifier::Shift);
+        let is_combo = modifiers.is_held(Modifier::Ctrl)
+            || modifiers.is_held(Modifier::Alt)
+            || modifiers.is_held(Modifier::Super);
+
+        if !is_combo {
+            if let Some(c) = printable_char(keycode, shift) {
+                self.push_char(c, now);
+                return true;
+            }
+        }
+
+        let key_label = special_key_label(keycode)
+            .or_else(|| printable_char(keycode, false).map(|c| c.to_ascii_uppercase().to_string()))
+            .or_else(|| fallback_name.map(str::to_string))
+            .unwrap_or_else(|| format!("#{}", keycode));
+        self.push_key(modifiers.format_combo(&key_label), now);
+        true
+    }
+
+    fn push_char(&mut self, c: char, now: Instant) {
+        let timeout = self.timeout;
+        match self.entries.back_mut() {
+            Some(entry)
+                if entry.kind == EntryKind::Text && now.duration_since(entry.updated) < timeout =>
+            {
+                entry.text.push(c);
+                let len = entry.text.chars().count();
+                if len > MAX_TEXT_CHARS {
+                    let keep: String = entry.text.chars().skip(len - MAX_TEXT_CHARS + 1).collect();
+                    entry.text = format!("…{}", keep);
+                }
+                entry.updated = now;
+            }
+            _ => self.push_entry(StripEntry {
+                text: c.to_string(),
+                kind: EntryKind::Text,
+                updated: now,
+            }),
+        }
+    }
+
+    fn push_key(&mut self, label: String, now: Instant) {
+        let timeout = self.timeout;
+        if let Some(entry) = self.entries.back_mut() {
+            if let EntryKind::Key { repeat } = &mut entry.kind {
+                if entry.text == label && now.duration_since(entry.updated) < timeout {
+                    *repeat += 1;
+                    entry.updated = now;
+                    return;
+                }
+            }
+        }
+        self.push_entry(StripEntry {
+            text: label,
+            kind: EntryKind::Key { repeat: 1 },
+            updated: now,
+        });
+    }
+
+    fn push_entry(&mut self, entry: StripEntry) {
+        self.entries.push_back(entry);
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    fn alpha_at(&self, entry: &StripEntry, now: Instant) -> f64 {
+        let age = now.duration_since(entry.updated);
+        if age >= self.timeout {
+            return 0.0;
+        }
+        let remaining = self.timeout - age;
+        if remaining >= FADE_DURATION {
+            1.0
+        } else {
+            remaining.as_secs_f64() / FADE_DURATION.as_secs_f64()
+        }
+    }
+
+    /// Returns the text and opacity (0.0-1.0) of each visible entry, oldest first.
+    pub fn visible_entries(&self, now: Instant) -> Vec<(String, f64)> {
+        self.entries
+            .iter()
+            .map(|e| (e.display_text(), self.alpha_at(e, now)))
+            .filter(|(_, alpha)| *alpha > 0.0)
+            .collect()
+    }
+
+    /// Drops expired entries and returns `true` if the strip needs to be redrawn,
+    /// i.e. an entry is fading out or has just disappeared.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let before = self.entries.len();
+        let timeout = self.timeout;
+        self.entries
+            .retain(|e| now.duration_since(e.updated) < timeout);
+        let fading = self.entries.iter().any(|e| self.alpha_at(e, now) < 1.0);
+        fading || self.entries.len() != before
+    }
+}
+
+/// Paints strip entries onto a surface of the given size.
+///
+/// Entries are drawn as key-like boxes from right to left, newest on the
+/// right, until the surface is full. Colors come from the overlay
+/// configuration and are scaled by each entry's opacity; the background fades
+/// with the most opaque entry so an empty strip is fully transparent.
+pub fn paint_strip(
+    ctx: &Context,
+    config: &AppConfig,
+    entries: &[(String, f64)],
+    width: i32,
+    height: i32,
+    font_face: &CairoFontFace,
+) {
+    let fallback = (0.1, 0.1, 0.1, 1.0);
+    let color = |s: &str| parse_color_string(s).unwrap_or(fallback);
+    let background = color(&config.overlay.background_color_inactive);
+    let key_background = color(&config.overlay.default_key_background_color);
+    let outline = color(&config.overlay.default_key_outline_color);
+    let text = color(&config.overlay.default_key_text_color);
+    let with_alpha = |(r, g, b, a): (f64, f64, f64, f64), alpha: f64| (r, g, b, a * alpha);
+
+    let max_alpha = entries.iter().map(|(_, a)| *a).fold(0.0, f64::max);
+    let (r, g, b, a) = with_alpha(background, max_alpha);
+    ctx.save().unwrap();
+    ctx.set_source_rgba(r, g, b, a);
+    ctx.set_operator(cairo::Operator::Source);
+    ctx.paint().expect("Cairo paint (clear) failed");
+    ctx.restore().unwrap();
+
+    if entries.is_empty() {
+        return;
+    }
+    ctx.set_font_face(font_face);
+
+    let (width, height) = (width as f64, height as f64);
+    let gap = (height * 0.12).max(2.0);
+    let box_height = height - 2.0 * gap;
+    let font_size = box_height * 0.5;
+    let max_box_width = (width - 2.0 * gap).max(box_height);
+
+    let mut right = width - gap;
+    for (label, alpha) in entries.iter().rev() {
+        ctx.set_font_size(font_size);
+        let text_width = ctx
+            .text_extents(label)
+            .map(|e| e.x_advance())
+            .unwrap_or(0.0);
+        let box_width = (text_width + box_height * 0.6).clamp(box_height, max_box_width);
+        let left = right - box_width;
+        if left < gap && right < width - gap {
+            break; // Out of room; the newest entry is always drawn, shrunk if necessary.
+        }
+
+        draw::draw_single_key_cairo(
+            ctx,
+            &KeyDisplay {
+                text: label.clone(),
+                center_x: (left + box_width / 2.0) as f32,
+                center_y: (height / 2.0) as f32,
+                width: box_width as f32,
+                height: box_height as f32,
+                corner_radius: (box_height * 0.15) as f32,
+                border_thickness: (box_height * 0.04).max(1.0) as f32,
+                rotation_degrees: 0.0,
+                text_size: font_size as f32,
+                border_color: with_alpha(outline, *alpha),
+                background_color: with_alpha(key_background, *alpha),
+                text_color: with_alpha(text, *alpha),
+                legends: Vec::new(),
+                outlines: Vec::new(),
+                font: None,
+            },
+        );
+        right = left - gap;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(strip: &mut TypedStrip, now: Instant, keys: &[(u32, bool)]) {
+        let mut modifiers = ModifierState::default();
+        for &(keycode, pressed) in keys {
+            modifiers.handle_key(keycode, pressed, "");
+            strip.handle_key(keycode, pressed, &modifiers, None, now);
+        }
+    }
+
+    fn texts(strip: &TypedStrip, now: Instant) -> Vec<String> {
+        strip
+            .visible_entries(now)
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    #[test]
+    fn test_text_is_merged_and_specials_are_separate() {
+        let now = Instant::now();
+        let mut strip = TypedStrip::new(Duration::from_secs(2));
+        // "Hi" (with shift), space, "!", then Enter
+        type_keys(
+            &mut strip,
+            now,
+            &[
+                (42, true),
+                (35, true),
+                (35, false),
+                (42, false),
+                (23, true),
+                (23, false),
+                (57, true),
+                (54, true),
+                (2, true),
+                (2, false),
+                (54, false),
+                (28, true),
+                (28, false),
+            ],
+        );
+        assert_eq!(texts(&strip, now), vec!["Hi␣!", "⏎"]);
+    }
+
+    #[test]
+    fn test_combos_and_repeats() {
+        let now = Instant::now();
+        let mut strip = TypedStrip::new(Duration::from_secs(2));
+        type_keys(
+            &mut strip,
+            now,
+            &[
+                (29, true),
+                (42, true),
+                (20, true),
+                (20, false),
+                (42, false),
+                (29, false),
+            ],
+        );
+        type_keys(&mut strip, now, &[(14, true), (14, true), (14, true)]);
+        type_keys(&mut strip, now, &[(42, true), (15, true), (42, false)]);
+        type_keys(
+            &mut strip,
+            now,
+            &[(29, true), (12, true), (29, false), (190, true)],
+        );
+        assert_eq!(
+            texts(&strip, now),
+            vec!["Ctrl+Shift+T", "⌫ ×3", "Shift+⇥", "Ctrl+-", "#190"]
+        );
+    }
+
+    #[test]
+    fn test_entries_fade_and_expire() {
+        let start = Instant::now();
+        let mut strip = TypedStrip::new(Duration::from_secs(2));
+        type_keys(&mut strip, start, &[(30, true)]);
+        assert!(!strip.tick(start));
+
+        let fading = start + Duration::from_millis(1750);
+        assert!(strip.tick(fading));
+        let alpha = strip.visible_entries(fading)[0].1;
+        assert!(alpha > 0.0 && alpha < 1.0);
+
+        let later = start + Duration::from_secs(3);
+        assert!(strip.tick(later));
+        assert!(strip.visible_entries(later).is_empty());
+        assert!(!strip.tick(later));
+    }
+
+    #[test]
+    fn test_long_text_keeps_most_recent_chars() {
+        let now = Instant::now();
+        let mut strip = TypedStrip::new(Duration::from_secs(2));
+        for _ in 0..40 {
+            type_keys(&mut strip, now, &[(30, true), (30, false)]);
+        }
+        type_keys(&mut strip, now, &[(48, true)]);
+        let text = &texts(&strip, now)[0];
+        assert_eq!(text.chars().count(), MAX_TEXT_CHARS);
+        assert!(text.starts_with('…') && text.ends_with('b'));
+    }
+    #[test]
+    fn test_handle_key_reports_changes() {
+        let now = Instant::now();
+        let mut strip = TypedStrip::new(Duration::from_secs(2));
+        let mut modifiers = ModifierState::default();
+        assert!(strip.handle_key(30, true, &modifiers, None, now));
+        assert!(!strip.handle_key(30, false, &modifiers, None, now));
+        modifiers.handle_key(29, true, "");
+        assert!(!strip.handle_key(29, true, &modifiers, None, now));
+        assert!(strip.handle_key(46, true, &modifiers, None, now));
+    }
+}
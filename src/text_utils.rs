@@ -129,8 +129,7 @@ pub fn layout_text(
         while text_width_after_scaling > max_text_width_px && !current_text.is_empty() {
             current_text.pop(); // Remove the last character
             if current_text.is_empty() {
-                text_width_after_scaling = 0.0; // Empty string has zero width
-                break;
+                break; // Empty string has zero width
             }
             text_width_after_scaling = measure_text_width_with_cairo(ctx, &current_text, current_font_size_pts)?;
         }
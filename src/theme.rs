@@ -0,0 +1,241 @@
+// src/theme.rs
+
+//! This module loads themes: the appearance of the overlay, kept apart from
+//! the layout so that a layout can be shared and themed differently.
+//!
+//! A theme is a TOML file with the same `[overlay]` color, font, radius and
+//! border fields and `[style.<name>]` tables as a configuration, and nothing
+//! else. It is merged under the configuration, so settings made in the
+//! configuration itself win. A few themes are built into the binary.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{parse_color_string, struct_fields, StyleConfig};
+use crate::diagnostics::did_you_mean;
+
+/// Themes built into the binary, by name.
+pub const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("../themes/dark.toml")),
+    ("light", include_str!("../themes/light.toml")),
+    (
+        "high-contrast",
+        include_str!("../themes/high-contrast.toml"),
+    ),
+];
+
+/// The fields a theme may set, used to validate theme files.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeConfig {
+    #[serde(default)]
+    overlay: ThemeOverlay,
+    #[serde(default)]
+    style: BTreeMap<String, StyleConfig>,
+}
+
+/// The appearance fields of `OverlayConfig`.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeOverlay {
+    background_color_inactive: Option<String>,
+    background_color_active: Option<String>,
+    default_key_background_color: Option<String>,
+    default_key_text_color: Option<String>,
+    default_key_outline_color: Option<String>,
+    active_key_background_color: Option<String>,
+    active_key_text_color: Option<String>,
+    #[allow(dead_code)] // Only its type is checked.
+    default_key_font: Option<String>,
+    default_key_corner_radius: Option<f32>,
+    default_key_border_thickness: Option<f32>,
+}
+
+/// Loads a theme by name, or from a file if `theme` is not a built-in name.
+///
+/// Relative paths are resolved against `base_dir`, e.g. the directory of the
+/// configuration that selects the theme. Returns the theme as a TOML table.
+pub fn load_theme(theme: &str, base_dir: &Path) -> Result<toml::Table, String> {
+    let source = match BUILTIN_THEMES.iter().find(|(name, _)| *name == theme) {
+        Some((_, source)) => source.to_string(),
+        None => {
+            let path = base_dir.join(theme);
+            if !path.exists() && !theme.contains(['/', '.']) {
+                let names: Vec<&str> = BUILTIN_THEMES.iter().map(|(name, _)| *name).collect();
+                let hint = match did_you_mean(theme, &names) {
+                    Some(suggestion) => format!("Did you mean '{}'? ", suggestion),
+                    None => String::new(),
+                };
+                return Err(format!(
+                    "Unknown theme '{}'. {}Built-in themes are: {}; anything else is read as a file path.",
+                    theme,
+                    hint,
+                    names.join(", ")
+                ));
+            }
+            fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read theme file '{}': {}", path.display(), e))?
+        }
+    };
+    parse_theme(&source).map_err(|e| format!("Invalid theme '{}': {}", theme, e))
+}
+
+/// Parses and validates a theme.
+fn parse_theme(source: &str) -> Result<toml::Table, String> {
+    let theme: ThemeConfig = toml::from_str(source).map_err(|e| e.message().to_string())?;
+    let table: toml::Table = toml::from_str(source).map_err(|e| e.message().to_string())?;
+
+    // Styles are lenient in configurations, where unknown fields are only
+    // warned about by --check, but a theme is checked when it is loaded.
+    let style_fields = struct_fields::<StyleConfig>();
+    if let Some(toml::Value::Table(styles)) = table.get("style") {
+        for (name, style) in styles {
+            let Some(style) = style.as_table() else {
+                continue;
+            };
+            if let Some(field) = style
+                .keys()
+                .find(|field| !style_fields.contains(&field.as_str()))
+            {
+                let hint = did_you_mean(field, style_fields)
+                    .map(|suggestion| format!("; did you mean '{}'?", suggestion))
+                    .unwrap_or_default();
+                return Err(format!(
+                    "unknown field '{}' in [style.{}]{}",
+                    field, name, hint
+                ));
+            }
+        }
+    }
+
+    let overlay = &theme.overlay;
+    let mut colors = vec![
+        (
+            "overlay.background_color_inactive",
+            &overlay.background_color_inactive,
+        ),
+        (
+            "overlay.background_color_active",
+            &overlay.background_color_active,
+        ),
+        (
+            "overlay.default_key_background_color",
+            &overlay.default_key_background_color,
+        ),
+        (
+            "overlay.default_key_text_color",
+            &overlay.default_key_text_color,
+        ),
+        (
+            "overlay.default_key_outline_color",
+            &overlay.default_key_outline_color,
+        ),
+        (
+            "overlay.active_key_background_color",
+            &overlay.active_key_background_color,
+        ),
+        (
+            "overlay.active_key_text_color",
+            &overlay.active_key_text_color,
+        ),
+    ];
+    for style in theme.style.values() {
+        colors.extend([
+            ("style background_color", &style.background_color),
+            ("style text_color", &style.text_color),
+            ("style outline_color", &style.outline_color),
+            (
+                "style active_background_color",
+                &style.active_background_color,
+            ),
+            ("style active_text_color", &style.active_text_color),
+            ("style active_outline_color", &style.active_outline_color),
+        ]);
+    }
+    for (field, color) in colors {
+        if let Some(Err(e)) = color.as_deref().map(parse_color_string) {
+            return Err(format!("invalid {}: {}", field, e));
+        }
+    }
+    for (field, value) in [
+        (
+            "overlay.default_key_corner_radius",
+            overlay.default_key_corner_radius,
+        ),
+        (
+            "overlay.default_key_border_thickness",
+            overlay.default_key_border_thickness,
+        ),
+    ] {
+        if value.is_some_and(|value| value < 0.0) {
+            return Err(format!("{} must not be negative", field));
+        }
+    }
+    Ok(table)
+}
+
+/// Merges `theme` into `config`, keeping the values `config` already has.
+///
+/// Tables present in both (e.g. `[overlay]` or a `[style.<name>]`) are merged
+/// field by field.
+pub fn merge_under(config: &mut toml::Table, theme: toml::Table) {
+    for (key, theme_value) in theme {
+        match (config.get_mut(&key), theme_value) {
+            (Some(toml::Value::Table(config_table)), toml::Value::Table(theme_table)) => {
+                merge_under(config_table, theme_table);
+            }
+            (Some(_), _) => {}
+            (None, theme_value) => {
+                config.insert(key, theme_value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_themes_are_valid() {
+        for (name, _) in BUILTIN_THEMES {
+            load_theme(name, Path::new("")).unwrap_or_else(|e| panic!("{}", e));
+        }
+        let err = load_theme("drak", Path::new("")).unwrap_err();
+        assert!(err.contains("Did you mean 'dark'?"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_themes() {
+        assert!(parse_theme("[overlay]\nposition = \"top\"\n")
+            .unwrap_err()
+            .contains("unknown field `position`"));
+        assert!(parse_theme("[style.mod]\nbakground_color = \"#000\"\n")
+            .unwrap_err()
+            .contains("did you mean 'background_color'?"));
+        assert!(parse_theme("[overlay]\ndefault_key_text_color = \"red\"\n").is_err());
+        assert!(parse_theme("[[key]]\nname = \"A\"\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_under() {
+        let mut config: toml::Table = toml::from_str(
+            "[overlay]\ndefault_key_text_color = \"#FFF\"\n[style.mod]\ntext_size = 10.0\n",
+        )
+        .unwrap();
+        let theme: toml::Table = toml::from_str(
+            "[overlay]\ndefault_key_text_color = \"#000\"\nactive_key_text_color = \"#111\"\n\
+             [style.mod]\ntext_size = 12.0\ntext_color = \"#222\"\n[style.other]\nfont = \"Serif\"\n",
+        )
+        .unwrap();
+        merge_under(&mut config, theme);
+        let expected: toml::Table = toml::from_str(
+            "[overlay]\ndefault_key_text_color = \"#FFF\"\nactive_key_text_color = \"#111\"\n\
+             [style.mod]\ntext_size = 10.0\ntext_color = \"#222\"\n[style.other]\nfont = \"Serif\"\n",
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+}
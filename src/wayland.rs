@@ -1,30 +1,32 @@
 // Wayland interaction
-// Added for input::Libinput
 use wayland_client::protocol::{
-    wl_buffer, wl_callback, wl_compositor, wl_output, wl_registry, wl_shm, wl_shm_pool, wl_surface,
+    wl_buffer, wl_callback, wl_compositor, wl_keyboard, wl_output, wl_registry, wl_seat, wl_shm,
+    wl_shm_pool, wl_surface,
 };
-use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
 use wayland_protocols::xdg::shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
 use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
 use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+use zwlr_layer_surface_v1::Anchor;
 
 use memmap2::MmapMut;
 use std::collections::HashMap;
 use std::fs::File as StdFsFile;
 use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
 
-use crate::config::{
-    default_key_background_color_string, parse_color_string, AppConfig, SizeDimension,
-    DEFAULT_BORDER_THICKNESS_UNSCALED, DEFAULT_CORNER_RADIUS_UNSCALED, DEFAULT_ROTATION_DEGREES,
-    DEFAULT_TEXT_SIZE_UNSCALED,
-};
-use crate::draw::{self, KeyDisplay}; // Import draw module and KeyDisplay
+use crate::config::{AppConfig, DisplayMode, KeymapSource, OverlayPosition, SizeDimension};
+use crate::config_watch::ConfigWatcher;
+use crate::draw; // Import draw module
+use crate::event::{LayerState, ModifierState};
+use crate::event_stream::EventStream;
+use crate::input_source::InputSource;
+use crate::ipc::IpcServer;
+use crate::keymap::{self, KeymapLabels};
+use crate::layout;
+use crate::strip::{self, TypedStrip};
 use crate::wayland_drawing_cache::DrawingCache; // Import DrawingCache
 
-// Graphics and Font rendering (needed for font loading in AppState::draw)
-use cairo::FontFace as CairoFontFace;
-use freetype::Library as FreeTypeLibrary;
-
 pub const WINDOW_WIDTH: i32 = 320;
 pub const WINDOW_HEIGHT: i32 = 240;
 
@@ -42,6 +44,8 @@ pub struct AppState {
     pub xdg_wm_base: Option<xdg_wm_base::XdgWmBase>,
     pub layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
     pub xdg_output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+    pub seat: Option<wl_seat::WlSeat>,
+    pub keyboard: Option<wl_keyboard::WlKeyboard>,
     pub outputs: Vec<(
         u32,
         wl_output::WlOutput,
@@ -56,7 +60,13 @@ pub struct AppState {
     pub configured_width: i32,
     pub configured_height: i32,
     pub running: bool,
-    pub input_context: Option<input::Libinput>,
+    pub input_source: Option<Box<dyn InputSource>>,
+    pub config_watcher: Option<ConfigWatcher>,
+    pub ipc_server: Option<IpcServer>,
+    pub event_stream: EventStream,
+    pub config_path: Option<String>,
+    /// Theme from `--theme`, used instead of `overlay.theme` when reloading.
+    pub theme: Option<String>,
     pub config: AppConfig,
     pub key_states: HashMap<u32, bool>,
     pub needs_redraw: bool,
@@ -67,6 +77,51 @@ pub struct AppState {
     pub frame_callback: Option<wl_callback::WlCallback>,
     pub is_window_mode: bool,
     pub window_background_color: (f64, f64, f64, f64),
+    /// When `false`, frames are drawn fully transparent (toggled over IPC).
+    pub visible: bool,
+    /// Recently typed keys, present when `overlay.display_mode` is `strip`.
+    pub strip: Option<TypedStrip>,
+    /// Held modifier keys and the chord shown in the label band.
+    pub modifiers: ModifierState,
+    /// Layers activated by their momentary and toggle keys.
+    pub layers: LayerState,
+    /// Key labels derived from the keymap the compositor sent, once received.
+    pub compositor_keymap_labels: Option<KeymapLabels>,
+}
+
+/// Creates the typed-text strip if the configuration asks for strip mode.
+fn strip_for_config(config: &AppConfig) -> Option<TypedStrip> {
+    (config.overlay.display_mode == DisplayMode::Strip)
+        .then(|| TypedStrip::new(Duration::from_millis(config.overlay.strip_timeout_ms)))
+}
+
+/// Returns `true` if the configuration labels keys from the compositor's keymap.
+fn uses_compositor_keymap(config: &AppConfig) -> bool {
+    config
+        .keymap
+        .as_ref()
+        .is_some_and(|k| k.source == KeymapSource::Compositor)
+}
+
+/// Returns the layer-shell anchor for an overlay position.
+pub fn anchor_for_position(position: &OverlayPosition) -> Anchor {
+    match position {
+        OverlayPosition::Top | OverlayPosition::TopCenter => {
+            Anchor::Top | Anchor::Left | Anchor::Right
+        }
+        OverlayPosition::Bottom | OverlayPosition::BottomCenter => {
+            Anchor::Bottom | Anchor::Left | Anchor::Right
+        }
+        OverlayPosition::Left => Anchor::Left,
+        OverlayPosition::Right => Anchor::Right,
+        OverlayPosition::Center => Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right,
+        OverlayPosition::TopLeft => Anchor::Top | Anchor::Left,
+        OverlayPosition::TopRight => Anchor::Top | Anchor::Right,
+        OverlayPosition::BottomLeft => Anchor::Bottom | Anchor::Left,
+        OverlayPosition::BottomRight => Anchor::Bottom | Anchor::Right,
+        OverlayPosition::CenterLeft => Anchor::Left,
+        OverlayPosition::CenterRight => Anchor::Right,
+    }
 }
 
 impl AppState {
@@ -75,16 +130,18 @@ impl AppState {
         is_window_mode: bool,
         window_background_color: (f64, f64, f64, f64),
     ) -> Self {
-        let mut key_states_map = HashMap::new();
-        for key_conf in app_config.key.iter() {
-            key_states_map.insert(key_conf.keycode, false);
-        }
+        let key_states_map = app_config
+            .monitored_keycodes()
+            .map(|code| (code, false))
+            .collect();
         AppState {
             compositor: None,
             shm: None,
             xdg_wm_base: None,
             layer_shell: None,
             xdg_output_manager: None,
+            seat: None,
+            keyboard: None,
             outputs: Vec::new(),
             surface: None,
             layer_surface: None,
@@ -94,7 +151,12 @@ impl AppState {
             configured_width: WINDOW_WIDTH,
             configured_height: WINDOW_HEIGHT,
             running: true,
-            input_context: None,
+            input_source: None,
+            config_watcher: None,
+            ipc_server: None,
+            event_stream: EventStream::default(),
+            config_path: None,
+            theme: None,
             config: app_config.clone(),
             key_states: key_states_map,
             needs_redraw: true,
@@ -105,9 +167,107 @@ impl AppState {
             frame_callback: None,
             is_window_mode,
             window_background_color,
+            visible: true,
+            strip: strip_for_config(&app_config),
+            modifiers: ModifierState::default(),
+            layers: LayerState::default(),
+            compositor_keymap_labels: None,
         }
     }
 
+    /// Replaces the active configuration, e.g. after the config file changed.
+    ///
+    /// Keys that exist in both configurations keep their pressed state. The
+    /// layer surface size, anchor and margins are updated for the new
+    /// configuration. The target screen is only chosen when the layer surface
+    /// is created, so a change to it takes effect after a restart.
+    pub fn apply_config(&mut self, new_config: AppConfig) {
+        if self.config.overlay.screen != new_config.overlay.screen {
+            log::warn!("Changes to overlay.screen take effect after a restart.");
+        }
+
+        self.key_states = new_config
+            .monitored_keycodes()
+            .map(|code| (code, *self.key_states.get(&code).unwrap_or(&false)))
+            .collect();
+        self.layers = LayerState::default();
+        match (self.strip.as_mut(), new_config.overlay.display_mode) {
+            (Some(strip), DisplayMode::Strip) => {
+                strip.set_timeout(Duration::from_millis(new_config.overlay.strip_timeout_ms))
+            }
+            _ => self.strip = strip_for_config(&new_config),
+        }
+        self.config = new_config;
+        if let Some(labels) = self.compositor_keymap_labels.as_ref() {
+            if uses_compositor_keymap(&self.config) {
+                self.config.apply_keymap_labels(labels);
+            }
+        }
+        self.drawing_cache.invalidate();
+        self.apply_overlay_placement();
+        self.initial_surface_size_set = false;
+        self.attempt_configure_layer_surface_size();
+        self.needs_redraw = true;
+    }
+
+    /// Returns the name of the active layer, or `None` for the base layout.
+    pub fn active_layer_name(&self) -> Option<&str> {
+        self.layers
+            .active_layer()
+            .and_then(|index| self.config.layer.get(index))
+            .map(|layer| layer.name.as_str())
+    }
+
+    /// Stores labels derived from the compositor's keymap and applies them
+    /// if the configuration labels keys from it.
+    pub fn apply_compositor_keymap_labels(&mut self, labels: KeymapLabels) {
+        if uses_compositor_keymap(&self.config) {
+            self.config.apply_keymap_labels(&labels);
+            self.drawing_cache.invalidate();
+            self.needs_redraw = true;
+        }
+        self.compositor_keymap_labels = Some(labels);
+    }
+
+    /// Advances time-based state and requests a redraw if needed.
+    ///
+    /// Called once per main loop iteration so strip entries fade out even
+    /// when no events arrive.
+    pub fn update_animations(&mut self) {
+        if let Some(strip) = self.strip.as_mut() {
+            if strip.tick(Instant::now()) {
+                self.needs_redraw = true;
+            }
+        }
+    }
+
+    /// Sets the layer surface anchor and margins from the overlay configuration.
+    ///
+    /// Does nothing in window mode. The caller is responsible for committing the surface.
+    pub fn apply_overlay_placement(&self) {
+        let Some(layer_surface) = self.layer_surface.as_ref() else {
+            return;
+        };
+        let overlay = &self.config.overlay;
+        let anchor = anchor_for_position(&overlay.position);
+        log::info!("Setting anchor to: {:?}", anchor);
+        layer_surface.set_anchor(anchor);
+
+        log::info!(
+            "Setting margins: T={}, R={}, B={}, L={}",
+            overlay.margin_top,
+            overlay.margin_right,
+            overlay.margin_bottom,
+            overlay.margin_left
+        );
+        layer_surface.set_margin(
+            overlay.margin_top,
+            overlay.margin_right,
+            overlay.margin_bottom,
+            overlay.margin_left,
+        );
+    }
+
     pub fn attempt_configure_layer_surface_size(&mut self) {
         if self.initial_surface_size_set || self.layer_surface.is_none() || self.surface.is_none() {
             return;
@@ -174,8 +334,10 @@ impl AppState {
                 );
             }
         }
-        let (layout_w, layout_h) = self.get_key_layout_bounds();
-        let aspect = if layout_h > 0.0 {
+        let (layout_w, layout_h) = layout::get_content_bounds(&self.config);
+        let aspect = if self.strip.is_some() {
+            strip::STRIP_ASPECT_RATIO
+        } else if layout_h > 0.0 {
             layout_w / layout_h
         } else {
             16.0 / 9.0
@@ -199,7 +361,8 @@ impl AppState {
         } else if target_h > 0 && target_w == 0 {
             target_w = (target_h as f32 * aspect).round() as u32;
         } else if target_w == 0 && target_h == 0 {
-            target_h = (screen_height_px as f32 * 0.3).round() as u32;
+            let default_height_ratio = if self.strip.is_some() { 0.08 } else { 0.3 };
+            target_h = (screen_height_px as f32 * default_height_ratio).round() as u32;
             target_w = (target_h as f32 * aspect).round() as u32;
             log::warn!("Overlay size 0x0. Defaulting: {}x{}", target_w, target_h);
         }
@@ -252,20 +415,6 @@ impl AppState {
         }
     }
 
-    pub fn get_key_layout_bounds(&self) -> (f32, f32) {
-        if self.config.key.is_empty() {
-            return (0.0, 0.0);
-        }
-        let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
-        for kc in &self.config.key {
-            min_x = min_x.min(kc.left);
-            max_x = max_x.max(kc.left + kc.width);
-            min_y = min_y.min(kc.top);
-            max_y = max_y.max(kc.top + kc.height);
-        }
-        ((max_x - min_x).max(0.0), (max_y - min_y).max(0.0))
-    }
-
     fn prepare_drawing_surface(
         &mut self,
         qh: &QueueHandle<AppState>,
@@ -335,104 +484,13 @@ impl AppState {
             );
         }
 
-        let (layout_w, layout_h) = self.get_key_layout_bounds();
-        let padding = if self.config.overlay.size_width.is_some()
-            || self.config.overlay.size_height.is_some()
-        {
-            2.0
-        } else {
-            (surface_width.min(surface_height) as f32 * 0.05).max(5.0)
-        };
-
-        let drawable_width = (surface_width as f32 - 2.0 * padding).max(0.0);
-        let drawable_height = (surface_height as f32 - 2.0 * padding).max(0.0);
-
-        let scale = if layout_w > 0.0 && layout_h > 0.0 {
-            let scale_x = drawable_width / layout_w;
-            let scale_y = drawable_height / layout_h;
-            scale_x.min(scale_y).max(0.01)
-        } else {
-            1.0
-        };
-
-        let scaled_layout_width = layout_w * scale;
-        let scaled_layout_height = layout_h * scale;
-
-        let min_coord_x = self.config.key.iter().map(|k| k.left).fold(f32::INFINITY, |a, b| a.min(b));
-        let min_coord_y = self.config.key.iter().map(|k| k.top).fold(f32::INFINITY, |a, b| a.min(b));
-
-        let actual_min_coord_x = if min_coord_x.is_finite() { min_coord_x } else { 0.0 };
-        let actual_min_coord_y = if min_coord_y.is_finite() { min_coord_y } else { 0.0 };
-
-        let offset_x = padding + (drawable_width - scaled_layout_width) / 2.0 - (actual_min_coord_x * scale);
-        let offset_y = padding + (drawable_height - scaled_layout_height) / 2.0 - (actual_min_coord_y * scale);
+        let (scale, offset_x, offset_y) =
+            layout::calculate_layout_parameters(&self.config, surface_width, surface_height);
 
         self.drawing_cache.update(surface_width, surface_height, scale, offset_x, offset_y);
         (scale, offset_x, offset_y)
     }
 
-    fn prepare_keys_for_drawing(&self, scale: f32, offset_x: f32, offset_y: f32) -> Vec<KeyDisplay> {
-        let default_fallback_color = (0.1, 0.1, 0.1, 1.0);
-
-        let key_outline_color =
-            parse_color_string(&self.config.overlay.default_key_outline_color)
-                .unwrap_or(default_fallback_color);
-        let default_key_text_color =
-            parse_color_string(&self.config.overlay.default_key_text_color)
-                .unwrap_or(default_fallback_color);
-        let active_key_bg_color =
-            parse_color_string(&self.config.overlay.active_key_background_color)
-                .unwrap_or((0.6, 0.6, 0.9, 1.0));
-        let active_key_text_color =
-            parse_color_string(&self.config.overlay.active_key_text_color)
-                .unwrap_or(default_key_text_color);
-
-        let ultimate_inactive_bg_fallback =
-            parse_color_string(&default_key_background_color_string())
-                .unwrap_or((0.3, 0.3, 0.3, 0.5));
-
-        self.config
-            .key
-            .iter()
-            .map(|kc| {
-                let is_pressed = *self.key_states.get(&kc.keycode).unwrap_or(&false);
-
-                let bg_color = if is_pressed {
-                    active_key_bg_color
-                } else {
-                    kc.background_color
-                        .as_ref()
-                        .and_then(|s| parse_color_string(s).ok())
-                        .unwrap_or_else(|| {
-                            parse_color_string(&self.config.overlay.default_key_background_color)
-                                .unwrap_or(ultimate_inactive_bg_fallback)
-                        })
-                };
-
-                let text_color = if is_pressed {
-                    active_key_text_color
-                } else {
-                    default_key_text_color
-                };
-
-                KeyDisplay {
-                    text: kc.name.clone(),
-                    center_x: (kc.left + kc.width / 2.0) * scale + offset_x,
-                    center_y: (kc.top + kc.height / 2.0) * scale + offset_y,
-                    width: kc.width * scale,
-                    height: kc.height * scale,
-                    corner_radius: kc.corner_radius.unwrap_or(DEFAULT_CORNER_RADIUS_UNSCALED) * scale,
-                    border_thickness: kc.border_thickness.unwrap_or(DEFAULT_BORDER_THICKNESS_UNSCALED) * scale,
-                    rotation_degrees: kc.rotation_degrees.unwrap_or(DEFAULT_ROTATION_DEGREES),
-                    text_size: kc.text_size.unwrap_or(DEFAULT_TEXT_SIZE_UNSCALED) * scale,
-                    border_color: key_outline_color,
-                    background_color: bg_color,
-                    text_color,
-                }
-            })
-            .collect()
-    }
-
     pub fn draw(&mut self, qh: &QueueHandle<AppState>) {
         // Clone the surface proxy to avoid borrow checker issues with later &mut self calls.
         // wl_surface::WlSurface is typically a lightweight handle (Rc-like).
@@ -463,42 +521,63 @@ impl AppState {
             }
         };
 
-        let (scale, offset_x, offset_y) = self.calculate_layout_parameters(width, height);
-        let keys_to_draw = self.prepare_keys_for_drawing(scale, offset_x, offset_y);
+        if self.visible && self.strip.is_some() {
+            let entries = self
+                .strip
+                .as_ref()
+                .map(|s| s.visible_entries(Instant::now()))
+                .unwrap_or_default();
+            let cairo_font_face = draw::load_default_font_face();
+            strip::paint_strip(
+                &ctx,
+                &self.config,
+                &entries,
+                width,
+                height,
+                &cairo_font_face,
+            );
+        } else if self.visible {
+            let (scale, offset_x, offset_y) = self.calculate_layout_parameters(width, height);
+            let keys_to_draw = layout::prepare_keys_for_drawing(
+                &self.config,
+                &self.key_states,
+                self.layers.active_layer(),
+                scale,
+                offset_x,
+                offset_y,
+            );
 
-        let font_data: &[u8] = include_bytes!("../default-font/DejaVuSansMono.ttf");
-        let ft_library = FreeTypeLibrary::init().expect("FT init failed");
-        let ft_face = ft_library
-            .new_memory_face(font_data.to_vec(), 0)
-            .expect("FT face load failed");
-        let cairo_font_face =
-            CairoFontFace::create_from_ft(&ft_face).expect("Cairo FT face creation failed");
+            let cairo_font_face = draw::load_default_font_face();
 
-        let final_background_color = if self.is_window_mode {
-            self.window_background_color
-        } else {
-            let is_overlay_active = self.key_states.values().any(|&pressed| pressed);
-            let bg_color_str = if is_overlay_active {
-                &self.config.overlay.background_color_active
+            let final_background_color = if self.is_window_mode {
+                self.window_background_color
             } else {
-                &self.config.overlay.background_color_inactive
+                layout::overlay_background_color(&self.config, &self.key_states)
             };
-            parse_color_string(bg_color_str).unwrap_or_else(|e| {
-                log::warn!(
-                    "Failed to parse overlay background color string '{}': {}. Using transparent black.",
-                    bg_color_str,
-                    e
-                );
-                (0.0, 0.0, 0.0, 0.0)
-            })
-        };
 
-        draw::paint_all_keys(
-            &ctx,
-            &keys_to_draw,
-            final_background_color,
-            &cairo_font_face,
-        );
+            draw::paint_all_keys(
+                &ctx,
+                &keys_to_draw,
+                final_background_color,
+                &cairo_font_face,
+            );
+
+            let label = layout::band_label(self.active_layer_name(), self.modifiers.chord_label());
+            if let (Some(label), Some(band)) =
+                (label, layout::label_band_rect(&self.config, width, height))
+            {
+                draw::draw_single_key_cairo(
+                    &ctx,
+                    &layout::prepare_label_for_drawing(&self.config, &label, band),
+                );
+            }
+        } else {
+            // Hidden: keep the surface mapped but fully transparent.
+            ctx.set_operator(cairo::Operator::Clear);
+            if let Err(e) = ctx.paint() {
+                log::error!("Failed to clear hidden surface: {}", e);
+            }
+        }
 
         // Ensure all drawing operations are flushed to the underlying SHM buffer.
         // The cairo_image_surface is the direct representation of that buffer.
@@ -681,6 +760,10 @@ impl Dispatch<wl_registry::WlRegistry, ()> for AppState {
                     state.xdg_output_manager = Some(registry.bind(name, 3.min(version), qh, ()));
                     log::info!("Bound zxdg_output_manager_v1 v{}", 3.min(version));
                 }
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, 1.min(version), qh, ()));
+                    log::info!("Bound wl_seat v{}", 1.min(version));
+                }
                 "wl_output" => {
                     let out =
                         registry.bind::<wl_output::WlOutput, _, _>(name, 4.min(version), qh, ());
@@ -705,6 +788,69 @@ impl Dispatch<wl_registry::WlRegistry, ()> for AppState {
         }
     }
 }
+impl Dispatch<wl_seat::WlSeat, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            // The keyboard is only used for its keymap; key events come from the input source.
+            let has_keyboard = capabilities.contains(wl_seat::Capability::Keyboard);
+            if has_keyboard && state.keyboard.is_none() && keymap::check_supported().is_ok() {
+                state.keyboard = Some(seat.get_keyboard(qh, ()));
+            } else if !has_keyboard {
+                state.keyboard = None;
+            }
+        }
+    }
+}
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        _: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_keyboard::Event::Keymap { format, fd, size } = event else {
+            return;
+        };
+        if format != WEnum::Value(wl_keyboard::KeymapFormat::XkbV1) {
+            log::warn!(
+                "Compositor sent a keymap in unsupported format {:?}. Ignoring it.",
+                format
+            );
+            return;
+        }
+        let file = StdFsFile::from(fd);
+        // SAFETY: The compositor shares the keymap read-only and does not modify it.
+        let text = match unsafe { memmap2::MmapOptions::new().len(size as usize).map(&file) } {
+            Ok(map) => String::from_utf8_lossy(&map).into_owned(),
+            Err(e) => {
+                log::error!("Failed to map the compositor's keymap: {}", e);
+                return;
+            }
+        };
+        match keymap::labels_from_keymap_text(&text) {
+            Ok(labels) => {
+                log::info!(
+                    "Received keymap from the compositor with {} key labels.",
+                    labels.len()
+                );
+                state.apply_compositor_keymap_labels(labels);
+            }
+            Err(e) => log::error!("{}", e),
+        }
+    }
+}
 impl Dispatch<wl_output::WlOutput, ()> for AppState {
     fn event(
         _s: &mut Self,
@@ -755,27 +901,25 @@ impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for AppState {
         {
             let mut changed = false;
             match e {
-                zxdg_output_v1::Event::LogicalSize { width, height } => {
-                    if info.logical_width != width || info.logical_height != height {
-                        log::debug!("Output ID {} ({:?}) logical size changed: {}x{} -> {}x{}", id, info.name.as_deref().unwrap_or("?"), info.logical_width, info.logical_height, width, height);
-                        info.logical_width = width;
-                        info.logical_height = height;
-                        changed = true;
-                    }
+                zxdg_output_v1::Event::LogicalSize { width, height }
+                    if info.logical_width != width || info.logical_height != height =>
+                {
+                    log::debug!("Output ID {} ({:?}) logical size changed: {}x{} -> {}x{}", id, info.name.as_deref().unwrap_or("?"), info.logical_width, info.logical_height, width, height);
+                    info.logical_width = width;
+                    info.logical_height = height;
+                    changed = true;
                 }
-                zxdg_output_v1::Event::Name { name } => {
-                    if info.name.as_deref() != Some(&name) {
-                        log::debug!("Output ID {} name changed: {:?} -> {}", id, info.name.as_deref().unwrap_or("?"), name);
-                        info.name = Some(name);
-                        changed = true; // Name change might affect target_output_identifier logic
-                    }
+                zxdg_output_v1::Event::Name { name } if info.name.as_deref() != Some(&name) => {
+                    log::debug!("Output ID {} name changed: {:?} -> {}", id, info.name.as_deref().unwrap_or("?"), name);
+                    info.name = Some(name);
+                    changed = true; // Name change might affect target_output_identifier logic
                 }
-                zxdg_output_v1::Event::Description { description } => {
-                     if info.description.as_deref() != Some(&description) {
-                        log::debug!("Output ID {} description changed: {:?} -> {}", id, info.description.as_deref().unwrap_or("?"), description);
-                        info.description = Some(description);
-                        changed = true; // Description change might affect target_output_identifier logic
-                    }
+                zxdg_output_v1::Event::Description { description }
+                    if info.description.as_deref() != Some(&description) =>
+                {
+                    log::debug!("Output ID {} description changed: {:?} -> {}", id, info.description.as_deref().unwrap_or("?"), description);
+                    info.description = Some(description);
+                    changed = true; // Description change might affect target_output_identifier logic
                 }
                 zxdg_output_v1::Event::Done => {
                     // The 'Done' event signals the end of a batch of updates.
@@ -17,6 +17,10 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    /// The example configuration, passed explicitly so the tests don't depend
+    /// on a configuration in the XDG config directories of the host.
+    const EXAMPLE_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/keys.toml");
+
     /// Helper function to extract the `WAYLAND_DISPLAY` name from Sway's stderr output.
     /// Sway, when run headlessly, often prints the display name it's using to stderr.
     /// This function uses a regex to find and capture that name.
@@ -65,13 +69,11 @@ mod tests {
 
         // Thread to read Sway's stderr and print/store it.
         let stderr_thread = thread::spawn(move || {
-            for line_result in stderr_reader.lines() {
-                if let Ok(line) = line_result {
-                    println!("[SWAY STDERR] {}", line);
-                    let mut stderr_lock = sway_stderr_clone.lock().unwrap();
-                    stderr_lock.push_str(&line);
-                    stderr_lock.push('\n');
-                }
+            for line in stderr_reader.lines().map_while(Result::ok) {
+                println!("[SWAY STDERR] {}", line);
+                let mut stderr_lock = sway_stderr_clone.lock().unwrap();
+                stderr_lock.push_str(&line);
+                stderr_lock.push('\n');
             }
         });
 
@@ -80,10 +82,8 @@ mod tests {
             sway_process.stdout.take().expect("Failed to get sway stdout stream."),
         );
         let stdout_thread = thread::spawn(move || {
-            for line_result in stdout_reader.lines() {
-                 if let Ok(line) = line_result {
-                    println!("[SWAY STDOUT] {}", line);
-                }
+            for line in stdout_reader.lines().map_while(Result::ok) {
+                println!("[SWAY STDOUT] {}", line);
             }
         });
 
@@ -129,6 +129,8 @@ mod tests {
 
         println!("[TEST] Sway running. Starting application on WAYLAND_DISPLAY={}", wayland_display_name_str);
         let mut app_process = Command::new(env!("CARGO_BIN_EXE_wayland-kbd-osd")) // Use env var for binary path
+            .arg("--config-path")
+            .arg(EXAMPLE_CONFIG)
             .env("WAYLAND_DISPLAY", wayland_display_name_str)
             .stdout(Stdio::inherit()) // Show app's stdout directly.
             .stderr(Stdio::inherit()) // Show app's stderr directly.
@@ -241,6 +243,8 @@ mod tests {
 
         println!("[TEST_DEFAULT_OVERLAY] Starting application in default overlay mode on {}...", wayland_display_name_str);
         let mut app_process = Command::new(env!("CARGO_BIN_EXE_wayland-kbd-osd"))
+            .arg("--config-path")
+            .arg(EXAMPLE_CONFIG)
             .env("WAYLAND_DISPLAY", &wayland_display_name_str)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
@@ -336,6 +340,8 @@ mod tests {
 
         println!("[TEST_WINDOW] Starting application with --window on {}...", wayland_display_name_str);
         let mut app_process = Command::new(env!("CARGO_BIN_EXE_wayland-kbd-osd"))
+            .arg("--config-path")
+            .arg(EXAMPLE_CONFIG)
             .arg("--window") // Specify window mode.
             .env("WAYLAND_DISPLAY", &wayland_display_name_str)
             .stdout(Stdio::inherit())